@@ -1,12 +1,21 @@
 //! Attack table generation.
 //!
 //! This module generates attack look-up tables for knights, kings and pawns.
+//! Leaper tables are `const` and built at compile time, the remaining tables are built on first use.
 //! Tables provide possible moves for a given piece type and square, queried via `[square]` or `[color][square]`.
+//! It also precomputes position-independent masks used by evaluation (pawn front spans, king zones) and the
+//! square-to-square distances.
 //!
-//! Sliding pieces are handled differently (in magics.rs) because of board occupancy.
+//! Sliding pieces are handled differently (in magics.rs) because of board occupancy. Their magics are baked in by
+//! default, `set_magic_mode` selects the magic search instead.
+//!
+//! The tables are built once, on first use, and shared: boards hold a reference to them, and the free functions
+//! `knight_attacks`, `rook_attacks`, etc. read them so that attacks can be queried without a board.
+
+use std::sync::OnceLock;
 
 use crate::bitboard::Bitboard;
-use crate::magics::MagicTables;
+use crate::magics::{MagicMode, MagicTables};
 use crate::types::{Color, Square};
 
 /// Contains the attack look-up tables per piece.
@@ -17,94 +26,146 @@ pub struct AttackTables {
     pub pawn_push: [[Bitboard; 64]; 2],
     pub pawn_double_push: [[Bitboard; 64]; 2],
 
+    pub front_span: [[Bitboard; 64]; 2], // Squares ahead on the same and adjacent files, per color
+    pub king_zone: [Bitboard; 64],       // King square and its neighbours
+
+    pub between: [[Bitboard; 64]; 64], // Squares strictly between two aligned squares
+    pub line: [[Bitboard; 64]; 64],    // Full line through two aligned squares, edge to edge
+
     pub magic_tables: MagicTables,
 }
 
 const KNIGHT_DELTAS: [(i8, i8); 8] = [(2, 1), (2, -1), (1, 2), (1, -2), (-1, 2), (-1, -2), (-2, 1), (-2, -1)];
 const KING_DELTAS: [(i8, i8); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
 
-impl AttackTables {
-    pub fn new() -> Self {
-        let mut knight = [Bitboard(0); 64];
-        let mut king = [Bitboard(0); 64];
-        let mut pawn_capture = [[Bitboard(0); 64]; 2]; //Color-dependent
-        let mut pawn_push = [[Bitboard(0); 64]; 2]; //Color-dependent
-        let mut pawn_double_push = [[Bitboard(0); 64]; 2]; //Color-dependent
+/// Leaper attack tables, computed at compile time. They do not depend on the position, so no runtime initialization
+/// is needed: `AttackTables` simply copies them.
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = leaper_table(&KNIGHT_DELTAS);
+pub const KING_ATTACKS: [Bitboard; 64] = leaper_table(&KING_DELTAS);
+pub const PAWN_CAPTURES: [[Bitboard; 64]; 2] = [leaper_table(&[(1, -1), (1, 1)]), leaper_table(&[(-1, -1), (-1, 1)])];
+pub const PAWN_PUSHES: [[Bitboard; 64]; 2] = [leaper_table(&[(1, 0)]), leaper_table(&[(-1, 0)])];
+pub const PAWN_DOUBLE_PUSHES: [[Bitboard; 64]; 2] = [double_push_table(1, 2), double_push_table(6, -2)];
 
-        // Initializes the attack table for each square
-        for sq in Square::ALL {
-            let from_rank = sq.rank() as i8;
-            let from_file = sq.file() as i8;
-
-            // ****************** KNIGHT ******************
-            for (delta_rank, delta_file) in KNIGHT_DELTAS {
-                let to_rank = from_rank + delta_rank;
-                let to_file = from_file + delta_file;
+/// Builds a leaper table from (rank, file) deltas, discarding targets that fall off the board.
+const fn leaper_table(deltas: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [Bitboard(0); 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let from_rank = (sq / 8) as i8;
+        let from_file = (sq % 8) as i8;
 
-                if (0..8).contains(&to_rank) && (0..8).contains(&to_file) {
-                    let to = Square::new((to_rank * 8 + to_file) as u8);
-                    knight[sq] |= to.bb();
-                }
+        let mut i = 0;
+        while i < deltas.len() {
+            let to_rank = from_rank + deltas[i].0;
+            let to_file = from_file + deltas[i].1;
+            if to_rank >= 0 && to_rank < 8 && to_file >= 0 && to_file < 8 {
+                table[sq].0 |= 1u64 << (to_rank * 8 + to_file);
             }
+            i += 1;
+        }
+        sq += 1;
+    }
+    table
+}
 
-            // ****************** KING ******************
-            for (delta_rank, delta_file) in KING_DELTAS {
-                let to_rank = from_rank + delta_rank;
-                let to_file = from_file + delta_file;
+/// Chebyshev distance between two squares, the number of king moves from one to the other. Used by evaluation terms
+/// that reward pieces near the enemy king.
+pub const DISTANCE: [[u8; 64]; 64] = distance_table(false);
+/// Manhattan distance between two squares, the number of rook steps from one to the other.
+pub const MANHATTAN_DISTANCE: [[u8; 64]; 64] = distance_table(true);
 
-                if (0..8).contains(&to_rank) && (0..8).contains(&to_file) {
-                    let to = Square::new((to_rank * 8 + to_file) as u8);
-                    king[sq] |= to.bb();
-                }
-            }
+/// Builds a distance table: the sum of the rank and file distances if `manhattan`, their maximum otherwise.
+const fn distance_table(manhattan: bool) -> [[u8; 64]; 64] {
+    let mut table = [[0u8; 64]; 64];
+    let mut from: usize = 0;
+    while from < 64 {
+        let mut to: usize = 0;
+        while to < 64 {
+            let rank_distance = (from / 8).abs_diff(to / 8);
+            let file_distance = (from % 8).abs_diff(to % 8);
+            table[from][to] = if manhattan {
+                rank_distance + file_distance
+            } else if rank_distance > file_distance {
+                rank_distance
+            } else {
+                file_distance
+            } as u8;
+            to += 1;
+        }
+        from += 1;
+    }
+    table
+}
 
-            // ****************** PAWN CAPTURE ******************
-            //White
-            if from_rank < 7 {
-                if from_file > 0 {
-                    pawn_capture[Color::White][sq] |= sq.north_west().bb();
-                }
-                if from_file < 7 {
-                    pawn_capture[Color::White][sq] |= sq.north_east().bb();
-                }
-            }
-            //Black
-            if from_rank > 0 {
-                if from_file > 0 {
-                    pawn_capture[Color::Black][sq] |= sq.south_west().bb();
-                }
-                if from_file < 7 {
-                    pawn_capture[Color::Black][sq] |= sq.south_east().bb();
-                }
-            }
+/// Builds a double pawn push table: only pawns on their starting rank can move two squares.
+const fn double_push_table(start_rank: usize, delta_rank: i8) -> [Bitboard; 64] {
+    let mut table = [Bitboard(0); 64];
+    let mut file = 0;
+    while file < 8 {
+        let sq = start_rank * 8 + file;
+        table[sq].0 = 1u64 << (sq as i8 + delta_rank * 8);
+        file += 1;
+    }
+    table
+}
 
-            // ****************** PAWN PUSH ******************
-            if from_rank < 7 {
-                pawn_push[Color::White][sq] = sq.north().bb();
-            }
-            if from_rank > 0 {
-                pawn_push[Color::Black][sq] = sq.south().bb();
-            }
+impl AttackTables {
+    /// Builds the tables, with magics obtained as selected by `set_magic_mode`.
+    pub fn new() -> Self {
+        Self::with_magic_mode(MAGIC_MODE.get().copied().unwrap_or_default())
+    }
 
-            // ****************** DOUBLE PAWN PUSH ******************
-            if from_rank == 1 {
-                pawn_double_push[Color::White][sq] = sq.north().north().bb();
-            }
-            if from_rank == 6 {
-                pawn_double_push[Color::Black][sq] = sq.south().south().bb();
-            }
+    /// Builds the tables, with magics obtained as `mode` says.
+    pub fn with_magic_mode(mode: MagicMode) -> Self {
+        let mut front_span = [[Bitboard(0); 64]; 2]; //Color-dependent
+        let mut king_zone = [Bitboard(0); 64];
+
+        // Initializes the evaluation masks for each square
+        for sq in Square::ALL {
+            let from_rank = sq.rank() as i8;
+
+            // ****************** FRONT SPAN ******************
+            let file = Bitboard::square_to_file(sq);
+            let span_files = file | Bitboard((file.0 << 1) & !Bitboard::file_a().0) | Bitboard((file.0 >> 1) & !Bitboard::file_h().0);
+            let ranks_north = if from_rank < 7 { u64::MAX << ((from_rank + 1) * 8) } else { 0 };
+            let ranks_south = if from_rank > 0 { u64::MAX >> ((8 - from_rank) * 8) } else { 0 };
+            front_span[Color::White][sq] = span_files & Bitboard(ranks_north);
+            front_span[Color::Black][sq] = span_files & Bitboard(ranks_south);
+
+            // ****************** KING ZONE ******************
+            king_zone[sq] = KING_ATTACKS[sq] | sq.bb();
         }
 
         // Generates sliding piece attacks
         let mut magic_tables = MagicTables::new();
-        magic_tables.generate_magics();
+        magic_tables.init(mode);
+
+        // Generates between and line tables, which need sliding attacks
+        let mut between = [[Bitboard(0); 64]; 64];
+        let mut line = [[Bitboard(0); 64]; 64];
+        for from in Square::ALL {
+            for to in Square::ALL {
+                let empty = Bitboard(0);
+                if magic_tables.bishop_lookup(from, empty) & to.bb() != Bitboard(0) {
+                    between[from][to] = magic_tables.bishop_lookup(from, to.bb()) & magic_tables.bishop_lookup(to, from.bb());
+                    line[from][to] = (magic_tables.bishop_lookup(from, empty) & magic_tables.bishop_lookup(to, empty)) | from.bb() | to.bb();
+                } else if magic_tables.rook_lookup(from, empty) & to.bb() != Bitboard(0) {
+                    between[from][to] = magic_tables.rook_lookup(from, to.bb()) & magic_tables.rook_lookup(to, from.bb());
+                    line[from][to] = (magic_tables.rook_lookup(from, empty) & magic_tables.rook_lookup(to, empty)) | from.bb() | to.bb();
+                }
+            }
+        }
 
         Self {
-            knight,
-            king,
-            pawn_capture,
-            pawn_push,
-            pawn_double_push,
+            knight: KNIGHT_ATTACKS,
+            king: KING_ATTACKS,
+            pawn_capture: PAWN_CAPTURES,
+            pawn_push: PAWN_PUSHES,
+            pawn_double_push: PAWN_DOUBLE_PUSHES,
+            front_span,
+            king_zone,
+            between,
+            line,
             magic_tables,
         }
     }
@@ -145,7 +206,162 @@ impl AttackTables {
         print_section(&mut out, "PAWN PUSH (BLACK)", &self.pawn_push[Color::Black]).unwrap();
         print_section(&mut out, "PAWN DOUBLE PUSH (BLACK)", &self.pawn_double_push[Color::Black]).unwrap();
 
-        println!("");
+        // Evaluation masks
+        print_section(&mut out, "FRONT SPAN (WHITE)", &self.front_span[Color::White]).unwrap();
+        print_section(&mut out, "FRONT SPAN (BLACK)", &self.front_span[Color::Black]).unwrap();
+        print_section(&mut out, "KING ZONE", &self.king_zone).unwrap();
+
+        println!();
         self.magic_tables.print();
     }
 }
+
+static ATTACK_TABLES: OnceLock<AttackTables> = OnceLock::new();
+static MAGIC_MODE: OnceLock<MagicMode> = OnceLock::new();
+
+/// Selects how the global attack tables get their magics, baked by default.
+///
+/// The tables are built on first use and boards keep a reference to them, so the mode can only be chosen once, at
+/// startup, before any board is created.
+pub fn set_magic_mode(mode: MagicMode) -> Result<(), &'static str> {
+    if ATTACK_TABLES.get().is_some() {
+        return Err("Attack tables already built, MagicMode must be set at startup");
+    }
+    MAGIC_MODE.set(mode).map_err(|_| "MagicMode already set")
+}
+
+/// Returns the global attack tables, building them on the first call.
+pub fn attack_tables() -> &'static AttackTables {
+    ATTACK_TABLES.get_or_init(AttackTables::new)
+}
+
+/// Returns the squares attacked by a knight on `sq`.
+#[inline(always)]
+pub fn knight_attacks(sq: Square) -> Bitboard {
+    KNIGHT_ATTACKS[sq]
+}
+
+/// Returns the squares attacked by a king on `sq`.
+#[inline(always)]
+pub fn king_attacks(sq: Square) -> Bitboard {
+    KING_ATTACKS[sq]
+}
+
+/// Returns the squares attacked by a pawn of `color` on `sq`.
+#[inline(always)]
+pub fn pawn_attacks(color: Color, sq: Square) -> Bitboard {
+    PAWN_CAPTURES[color][sq]
+}
+
+/// Returns the squares attacked by a rook on `sq`, given the board occupancy.
+#[inline(always)]
+pub fn rook_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
+    attack_tables().magic_tables.rook_lookup(sq, occupancy)
+}
+
+/// Returns the squares attacked by a bishop on `sq`, given the board occupancy.
+#[inline(always)]
+pub fn bishop_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
+    attack_tables().magic_tables.bishop_lookup(sq, occupancy)
+}
+
+/// Returns the squares attacked by a queen on `sq`, given the board occupancy.
+#[inline(always)]
+pub fn queen_attacks(sq: Square, occupancy: Bitboard) -> Bitboard {
+    rook_attacks(sq, occupancy) | bishop_attacks(sq, occupancy)
+}
+
+/// Returns the Chebyshev distance between `a` and `b`.
+#[inline(always)]
+pub fn distance(a: Square, b: Square) -> u8 {
+    DISTANCE[a][b]
+}
+
+/// Returns the Manhattan distance between `a` and `b`.
+#[inline(always)]
+pub fn manhattan_distance(a: Square, b: Square) -> u8 {
+    MANHATTAN_DISTANCE[a][b]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_span() {
+        let tables = AttackTables::new();
+        let ahead_of_e4 = Bitboard(0x3838_3838_0000_0000); // d5 to f8
+        assert_eq!(tables.front_span[Color::White][Square::E4], ahead_of_e4);
+        assert_eq!(tables.front_span[Color::Black][Square::E5], Bitboard(0x0000_0000_3838_3838)); // d4 to f1
+        assert_eq!(tables.front_span[Color::White][Square::A8], Bitboard(0));
+        assert_eq!(tables.front_span[Color::White][Square::A2], Bitboard(0x0303_0303_0303_0000)); // a3 to b8
+    }
+
+    #[test]
+    fn king_zone() {
+        let tables = AttackTables::new();
+        let around_e1 = Square::D1.bb() | Square::E1.bb() | Square::F1.bb() | Square::D2.bb() | Square::E2.bb() | Square::F2.bb();
+        assert_eq!(tables.king_zone[Square::E1], around_e1);
+        assert_eq!(tables.king_zone[Square::A8], Square::A8.bb() | Square::B8.bb() | Square::A7.bb() | Square::B7.bb());
+        assert_eq!(tables.king_zone[Square::E4].popcnt(), 9);
+    }
+
+    /// Builds a leaper table at runtime, square by square, as the tables were built before being made `const`.
+    fn runtime_leaper_table(deltas: &[(i8, i8)]) -> [Bitboard; 64] {
+        let mut table = [Bitboard(0); 64];
+        for sq in Square::ALL {
+            for &(rank_delta, file_delta) in deltas {
+                let (rank, file) = (sq.rank() as i8 + rank_delta, sq.file() as i8 + file_delta);
+                if (0..8).contains(&rank) && (0..8).contains(&file) {
+                    table[sq] |= Square::new((rank * 8 + file) as u8).bb();
+                }
+            }
+        }
+        table
+    }
+
+    #[test]
+    fn const_leaper_tables() {
+        assert_eq!(KNIGHT_ATTACKS, runtime_leaper_table(&KNIGHT_DELTAS));
+        assert_eq!(KING_ATTACKS, runtime_leaper_table(&KING_DELTAS));
+        assert_eq!(PAWN_CAPTURES[Color::White], runtime_leaper_table(&[(1, -1), (1, 1)]));
+        assert_eq!(PAWN_CAPTURES[Color::Black], runtime_leaper_table(&[(-1, -1), (-1, 1)]));
+        assert_eq!(AttackTables::new().knight, KNIGHT_ATTACKS);
+        assert_eq!(KNIGHT_ATTACKS[Square::A1], Square::B3.bb() | Square::C2.bb());
+    }
+
+    /// Checks the free attack functions: a queen on d4 must attack the union of the rook and bishop squares, and the
+    /// sliders must agree with the tables of a board.
+    #[test]
+    fn free_attacks_match_the_tables() {
+        let occupancy = Square::D7.bb() | Square::F6.bb() | Square::B4.bb() | Square::D2.bb();
+        let queen = queen_attacks(Square::D4, occupancy);
+        assert_eq!(queen, rook_attacks(Square::D4, occupancy) | bishop_attacks(Square::D4, occupancy));
+        assert_eq!(queen.popcnt(), 22);
+
+        let tables = AttackTables::new();
+        for sq in Square::ALL {
+            assert_eq!(rook_attacks(sq, occupancy), tables.magic_tables.rook_lookup(sq, occupancy));
+            assert_eq!(bishop_attacks(sq, occupancy), tables.magic_tables.bishop_lookup(sq, occupancy));
+            assert_eq!(knight_attacks(sq), tables.knight[sq]);
+            assert_eq!(pawn_attacks(Color::Black, sq), tables.pawn_capture[Color::Black][sq]);
+        }
+    }
+
+    /// Checks the distance tables on corner to corner, adjacent and knight-move square pairs, and their symmetry.
+    #[test]
+    fn distances_are_symmetric() {
+        assert_eq!([distance(Square::A1, Square::H8), distance(Square::E4, Square::E5), distance(Square::G1, Square::F3)], [7, 1, 2]);
+        assert_eq!(
+            [manhattan_distance(Square::A1, Square::H8), manhattan_distance(Square::E4, Square::E5), manhattan_distance(Square::G1, Square::F3)],
+            [14, 1, 3]
+        );
+        for a in Square::ALL {
+            for b in Square::ALL {
+                assert_eq!(distance(a, b), distance(b, a));
+                assert_eq!(manhattan_distance(a, b), manhattan_distance(b, a));
+                assert_eq!(distance(a, b) == 0, a == b);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Search benchmark.
+//!
+//! Searches a fixed set of positions to a fixed depth and reports the total node count and the aggregate speed, as in
+//! the OpenBench `bench` convention. The node count is a signature of the search: a change that is meant to be a pure
+//! speedup must leave it untouched, while any change in pruning or ordering alters it.
+//!
+//! Every position is searched with a fresh searcher, so the result does not depend on the order of the positions or on
+//! earlier searches.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::search::{NullSink, Searcher};
+
+/// Default depth of `bench`, when none is given on the command line.
+pub const BENCH_DEPTH: usize = 7;
+
+/// Positions searched by `bench`: openings, middlegames with tactics, and endgames.
+const BENCH_POSITIONS: [&str; 20] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+    "r1bq1rk1/ppp1nppp/4n3/3p3Q/3P4/1BP1B3/PP1N2PP/R4RK1 w - - 1 16",
+    "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
+    "2rqkb1r/ppp2p2/2npb1p1/1N1Nn2p/2P1PP2/8/PP2B1PP/R1BQK2R b KQ - 0 11",
+    "r1bq1r1k/b1p1npp1/p2p3p/1p6/3PP3/1B2NN2/PP3PPP/R2Q1RK1 w - - 1 16",
+    "3r1rk1/p5pp/bpp1pp2/8/q1PP1P2/b3P3/P2NQRPP/1R2B1K1 b - - 6 22",
+    "r1q2rk1/2p1bppp/2Pp4/p6b/Q1PNp3/4B3/PP1R1PPP/2K4R w - - 2 18",
+    "4k2r/1pb2ppp/1p2p3/1R1p4/3P4/2r1PN2/P4PPP/1R4K1 b - - 3 22",
+    "3q2k1/pb3p1p/4pbp1/2r5/PpN2N2/1P2P2P/5PP1/Q2R2K1 b - - 4 26",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+    "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
+    "8/6pk/1p6/8/PP3p1p/5P2/4KP1q/3Q4 w - - 0 1",
+    "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 1",
+];
+
+/// Totals of a `bench` run.
+#[derive(Copy, Clone, Debug)]
+pub struct BenchResult {
+    pub positions: usize,
+    pub nodes: u64,
+    pub time: Duration,
+    pub nps: u64,
+}
+
+impl fmt::Display for BenchResult {
+    /// Formats the single summary line, in the OpenBench format.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} nodes {} nps", self.nodes, self.nps)
+    }
+}
+
+/// Searches every bench position to `depth`, prints the summary line and returns the totals.
+pub fn bench(depth: usize) -> BenchResult {
+    let mut board = Board::new();
+    let mut nodes = 0;
+    let start = Instant::now();
+
+    for fen in BENCH_POSITIONS {
+        board.from_fen(fen).unwrap();
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(NullSink));
+        nodes += searcher.iterative_deepening(depth).nodes;
+    }
+
+    let time = start.elapsed();
+    let result = BenchResult {
+        positions: BENCH_POSITIONS.len(),
+        nodes,
+        time,
+        nps: (nodes as u128 * 1_000_000_000 / time.as_nanos().max(1)) as u64,
+    };
+    println!("{}", result);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the bench node count is deterministic: two runs at a small depth must search exactly the same nodes.
+    #[test]
+    fn bench_is_deterministic() {
+        const DEPTH: usize = 4;
+        let (first, second) = (bench(DEPTH), bench(DEPTH));
+        assert_eq!(first.nodes, second.nodes);
+        assert!(first.nodes > 0);
+        assert_eq!(first.positions, BENCH_POSITIONS.len());
+    }
+}
@@ -11,7 +11,7 @@ use crate::types::Square;
 
 /// Bitboard object defined as a struct with unnamed u64 field.
 #[repr(transparent)]
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
 pub struct Bitboard(pub u64);
 
 impl Bitboard {
@@ -39,6 +39,12 @@ impl Bitboard {
         Self(0x8080_8080_8080_8080u64)
     }
 
+    /// Returns the light squares (b1, a2, ...) as a bitboard.
+    #[inline(always)]
+    pub fn light_squares() -> Self {
+        Self(0x55AA_55AA_55AA_55AAu64)
+    }
+
     /// Returns the square's corresponding rank as a bitboard.
     #[inline(always)]
     pub fn square_to_rank(sq: Square) -> Self {
@@ -80,6 +86,23 @@ impl Bitboard {
     pub fn square(&self) -> Square {
         Square::new(self.lsb() as u8)
     }
+
+    /// Wraps a raw u64, usable in constants.
+    #[inline(always)]
+    pub const fn from_u64(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the bitboard with exactly the given squares set.
+    pub fn from_squares(squares: &[Square]) -> Self {
+        squares.iter().fold(Self(0), |bb, &sq| bb | sq.bb())
+    }
+
+    /// Iterates over the set squares, from A1 to H8.
+    pub fn squares(self) -> impl Iterator<Item = Square> {
+        let mut remaining = self;
+        std::iter::from_fn(move || if remaining.0 == 0 { None } else { Some(Square::new(remaining.pop_lsb() as u8)) })
+    }
 }
 
 impl fmt::Display for Bitboard {
@@ -96,12 +119,6 @@ impl fmt::Display for Bitboard {
     }
 }
 
-impl Default for Bitboard {
-    fn default() -> Self {
-        Bitboard(0u64)
-    }
-}
-
 impl BitAnd for Bitboard {
     type Output = Self;
 
@@ -151,3 +168,16 @@ impl Not for Bitboard {
         Self(!self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that `from_squares` and `squares` round-trip, in ascending square order.
+    #[test]
+    fn from_squares_round_trips() {
+        assert_eq!(Bitboard::from_squares(&[Square::A1, Square::H8]).squares().collect::<Vec<_>>(), [Square::A1, Square::H8]);
+        assert_eq!(Bitboard::from_squares(&[Square::H8, Square::D4, Square::D4]), Bitboard::from_u64((1 << 63) | (1 << 27)));
+        assert_eq!(Bitboard::from_squares(&[]).squares().next(), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Black magic bitboards with a shared attack table.
+//!
+//! An alternative layout to `magics.rs`, selected with the `black-magic` feature. Black magics hash the occupancy with
+//! the mask negated, `((occ | !mask) * magic) >> shift`, and all rook and bishop squares index into one shared table.
+//! Each square's entries are placed at the lowest offset where they agree with what is already stored: slots left
+//! unused by a square, and slots holding the same attack set, are shared with other squares, which shrinks the table
+//! and its cache footprint. How much depends on the magics: the random ones found here leave few unused slots, magics
+//! searched specifically for overlap share far more.
+//!
+//! Attacks are identical to the ones of the default layout, as checked by the `black_magics_match_the_flat_tables`
+//! test. The effect on speed is measured by running `benchmark_perft` with and without the feature.
+
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
+
+use crate::bitboard::Bitboard;
+use crate::magics::{BISHOP_DELTAS, MagicTables, ROOK_DELTAS};
+use crate::types::Square;
+
+/// Candidate magics tried per square before giving up.
+const MAX_MAGIC_ATTEMPTS: usize = 10_000_000;
+
+/// Lookup data of one square for one slider.
+#[derive(Copy, Clone, Default)]
+struct BlackMagic {
+    not_mask: u64, // Complement of the relevant occupancy mask
+    magic: u64,
+    shift: u32,
+    offset: usize, // Start of the square's entries in the shared table
+}
+
+impl BlackMagic {
+    /// Setting every bit outside the mask makes the index depend only on the relevant occupancy, no masking needed.
+    #[inline(always)]
+    fn index(&self, occupancy: Bitboard) -> usize {
+        self.offset + ((occupancy.0 | self.not_mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+/// Rook and bishop black magics, indexing a single shared attack table.
+pub struct BlackMagicTables {
+    rook: [BlackMagic; 64],
+    bishop: [BlackMagic; 64],
+    attacks: Vec<Bitboard>,
+}
+
+impl BlackMagicTables {
+    /// Tables with no entries, to be replaced by `new` before any lookup.
+    pub fn empty() -> Self {
+        Self {
+            rook: [BlackMagic::default(); 64],
+            bishop: [BlackMagic::default(); 64],
+            attacks: Vec::new(),
+        }
+    }
+
+    /// Searches black magics for every square and packs their attacks into the shared table.
+    ///
+    /// Rooks are placed first: their large per-square tables leave gaps that the small bishop tables can fill.
+    pub fn new() -> Self {
+        let mut shared: Vec<Option<Bitboard>> = Vec::new();
+        let mut rook = [BlackMagic::default(); 64];
+        let mut bishop = [BlackMagic::default(); 64];
+
+        for (magics, deltas, salt) in [(&mut rook, &ROOK_DELTAS, 0x800Cu64), (&mut bishop, &BISHOP_DELTAS, 0xB15Cu64)] {
+            for sq in Square::ALL {
+                let (mut magic, entries) = Self::search_magic(sq, deltas, salt);
+                magic.offset = Self::place(&mut shared, &entries);
+                magics[sq] = magic;
+            }
+        }
+
+        Self {
+            rook,
+            bishop,
+            attacks: shared.into_iter().map(|entry| entry.unwrap_or(Bitboard(0))).collect(),
+        }
+    }
+
+    /// Returns rook attacks from `sq` under the given board occupancy.
+    #[inline(always)]
+    pub fn rook_lookup(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        self.attacks[self.rook[sq].index(occupancy)]
+    }
+
+    /// Returns bishop attacks from `sq` under the given board occupancy.
+    #[inline(always)]
+    pub fn bishop_lookup(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        self.attacks[self.bishop[sq].index(occupancy)]
+    }
+
+    /// Returns the number of entries of the shared table.
+    pub fn len(&self) -> usize {
+        self.attacks.len()
+    }
+
+    /// Finds a collision-free black magic for `sq`, returns it with the square's entries, `None` for unused slots.
+    fn search_magic(sq: Square, deltas: &[(i8, i8)], salt: u64) -> (BlackMagic, Vec<Option<Bitboard>>) {
+        let mut rng = SmallRng::seed_from_u64(salt ^ (sq as u64 * 0xD10BE571A));
+
+        let mask = MagicTables::relevant_occupancy_mask(sq, deltas);
+        let relevant_bits = mask.0.count_ones();
+        let occupancies = MagicTables::enumerate_occupancies(mask);
+        let attacks = MagicTables::attacks_for_square(sq, deltas);
+
+        let mut entries = vec![None; 1 << relevant_bits];
+        'search: for _attempt in 0..MAX_MAGIC_ATTEMPTS {
+            let candidate = BlackMagic {
+                not_mask: !mask.0,
+                magic: MagicTables::sparse_random(&mut rng),
+                shift: 64 - relevant_bits,
+                offset: 0,
+            };
+
+            entries.fill(None);
+            for (&occ, &attack) in occupancies.iter().zip(&attacks) {
+                let index = candidate.index(occ);
+                match entries[index] {
+                    None => entries[index] = Some(attack),
+                    Some(existing) if existing == attack => {} // Two different occupancies may produce the same attack
+                    _ => continue 'search,                     // Collision
+                }
+            }
+            return (candidate, entries);
+        }
+        panic!("No black magic found for square {} after {} attempts", sq, MAX_MAGIC_ATTEMPTS);
+    }
+
+    /// Stores `entries` at the lowest offset of `shared` where they do not conflict, returns that offset.
+    fn place(shared: &mut Vec<Option<Bitboard>>, entries: &[Option<Bitboard>]) -> usize {
+        let fits = |shared: &[Option<Bitboard>], offset: usize| {
+            entries.iter().enumerate().all(|(i, entry)| match (entry, shared.get(offset + i)) {
+                (None, _) | (_, None) | (_, Some(None)) => true,
+                (Some(attack), Some(Some(existing))) => attack == existing,
+            })
+        };
+        let offset = (0..=shared.len()).find(|&offset| fits(shared, offset)).unwrap();
+
+        if shared.len() < offset + entries.len() {
+            shared.resize(offset + entries.len(), None);
+        }
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.is_some() {
+                shared[offset + i] = *entry;
+            }
+        }
+        offset
+    }
+}
+
+// Building the shared table is slow, so the tests only run when the layout is in use
+#[cfg(all(test, feature = "black-magic"))]
+mod tests {
+    use super::*;
+    use crate::magics::MagicMode;
+
+    /// Checks that black magics give exactly the attacks of the default layout, for every square and every relevant
+    /// occupancy, and that the shared table is smaller than the two flat tables.
+    #[test]
+    fn black_magics_match_the_flat_tables() {
+        let mut flat = Box::new(MagicTables::new());
+        flat.init(MagicMode::Baked);
+        let black = BlackMagicTables::new();
+        for sq in Square::ALL {
+            for occ in MagicTables::enumerate_occupancies(flat.rook_masks[sq]) {
+                // Bits outside the mask must not matter either
+                let noisy = occ | Bitboard(!flat.rook_masks[sq].0 & 0x8142_2418_1824_4281);
+                assert_eq!(black.rook_lookup(sq, noisy), flat.flat_rook_lookup(sq, noisy), "rook on {sq}");
+            }
+            for occ in MagicTables::enumerate_occupancies(flat.bishop_masks[sq]) {
+                let noisy = occ | Bitboard(!flat.bishop_masks[sq].0 & 0x00FF_0000_0000_FF00);
+                assert_eq!(black.bishop_lookup(sq, noisy), flat.flat_bishop_lookup(sq, noisy), "bishop on {sq}");
+            }
+        }
+        assert!(black.len() < flat.rook_attacks.len() + flat.bishop_attacks.len());
+    }
+}
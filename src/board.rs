@@ -3,18 +3,27 @@
 //! This module contains the implementation of the Board object, representing a Bord configuration along with its
 //! present state and past states, allowing for make/unmake move. The State object is memorized in a stack inside Board.
 
-use crate::attack::AttackTables;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use crate::attack::{AttackTables, attack_tables};
 use crate::bitboard::Bitboard;
+use crate::eval::{KING_ATTACK_WEIGHTS, eval_position, psqt};
+use crate::movegen::{Alfè, Argina, Attacker, Caval, MoveList, Re, Tor, generate_all_moves, generate_legal_moves};
 use crate::moves::Move;
-use crate::types::{Color, Piece, PieceType, Square, piece_value};
+use crate::types::{CastlingRights, CastlingSide, Color, Piece, PieceType, PieceValues, Square, piece_value};
+use crate::zobrist::ZOBRIST;
+
+const SEE_KING_VALUE: i32 = 20_000; // Capturing the king ends an exchange, so the king only recaptures last
 
-const MAX_PLY: usize = 128;
+/// Piece values of the static exchange evaluation.
+#[inline(always)]
+fn see_value(values: &PieceValues, piece_type: PieceType) -> i32 {
+    if piece_type == PieceType::King { SEE_KING_VALUE } else { values.get(piece_type) }
+}
 
-// Castling encoding in a u8.
-pub const WK: u8 = 0b0001;
-pub const WQ: u8 = 0b0010;
-pub const BK: u8 = 0b0100;
-pub const BQ: u8 = 0b1000;
+/// Size of the binary position format, see `Board::to_bytes`.
+pub const POSITION_BYTES: usize = 30;
 
 /// Chess board representation.
 ///
@@ -25,52 +34,84 @@ pub struct Board {
     pieces: [Bitboard; PieceType::NUM], // p,n,b,r,q,k, color agnostic
     colors: [Bitboard; 2],              // Per-color occupancy
     side_to_move: Color,
+    king_sq: [Square; 2], // Redundant with `pieces`, kept to skip the bitboard scan in check detection
 
-    state_stack: [State; MAX_PLY], // Array of states for move unmake
-    state_idx: usize,
+    state_stack: Vec<State>, // States for move unmake, the current one last
+    key_history: Vec<u64>,   // Zobrist key of each position, parallel to `state_stack`
+    state_idx: usize,        // Index of the current state, `state_stack.len() - 1`
 
-    eval: i32, // Cached static evaluation score
+    eval: i32,    // Material balance, white minus black
+    psqt_mg: i32, // Midgame PST sum, white minus black
+    psqt_eg: i32, // Endgame PST sum, white minus black
 
-    pub attack_tables: AttackTables,
+    pub attack_tables: &'static AttackTables, // Shared by all boards, never owned
 }
 
 /// Incremental game state information.
 ///
 /// This structure stores the minimal information required to unmake a move and restore the previous position.
 /// It is intended to be pushed onto `state_stack` during move execution.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default)]
 pub struct State {
-    castling: u8, // From LSB on, white-king, white-queen, black-king, black-queen side castling
+    castling: CastlingRights,
     en_passant: Option<Square>,
     halfmove: usize,
+    fullmove: usize,         // Starts at 1, incremented after each black move
     captured: Option<Piece>, // Which piece was captured in the last move
-
-    #[allow(dead_code)]
-    zobrist: Bitboard,
 }
 
 impl Board {
+    /// Creates an empty board on the global attack tables, built on the first call.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Creates an empty board on `tables`, for callers already holding initialized tables.
+    ///
+    /// The global tables are not touched, and the board only stores the reference: creating boards this way is cheap,
+    /// which is what parallel perft and multithreaded search rely on to give each thread its own board.
+    pub fn with_tables(tables: &'static AttackTables) -> Self {
+        Self {
+            mailbox: [Option::None; 64],
+            pieces: [Bitboard(0); PieceType::NUM],
+            colors: [Bitboard(0); 2],
+            side_to_move: Color::White,
+            king_sq: [Square::E1, Square::E8],
+
+            state_stack: vec![State::default()],
+            key_history: vec![0],
+            state_idx: 0,
+
+            eval: 0,
+            psqt_mg: 0,
+            psqt_eg: 0,
+
+            attack_tables: tables,
+        }
+    }
+
     /// Makes a pseudo-legal move incrementally.
     ///
-    /// Does NOT check legality (king safety). Must be paired with `unmake_move`.
+    /// Does NOT check legality (king safety), debug builds only check that the moved piece belongs to the side to move.
+    /// Must be paired with `unmake_move`. Moves from outside the engine should go through `try_make_move`.
     pub fn make_move(&mut self, m: Move) {
         let (from, to) = (m.from(), m.to());
         let (us, them) = (self.side_to_move, !self.side_to_move);
+        debug_assert!(self.mailbox[from].is_some_and(|piece| piece.get_color() == us), "Move {} for the side not to move", m);
 
         // 1 - Prepare state change variables
-        let newstate_halfmove;
         let mut newstate_en_passant = None;
         let mut newstate_captured = None;
         let mut newstate_castling = self.state_stack[self.state_idx].castling;
+        // The old en-passant key is removed now, while the pawns that could capture are still in place
+        let mut key = self.key_history[self.state_idx] ^ ZOBRIST.side ^ self.en_passant_hash(self.state_stack[self.state_idx].en_passant, us);
 
         // 2 - Remove from origin
         debug_assert!(self.mailbox[from].is_some()); // There must be a piece in the origin square
         let moved_piece = self.piece_on_unchecked(from);
         let moved_type = moved_piece.get_type();
+        key ^= ZOBRIST.pieces[moved_piece as usize][from];
+        self.apply_psqt_delta(moved_piece, from, -1);
         self.mailbox[from] = None;
         self.pieces[moved_type] ^= from.bb();
         self.colors[us] ^= from.bb();
@@ -85,7 +126,9 @@ impl Board {
             self.colors[them] ^= captured_sq.bb();
 
             newstate_captured = Some(captured_piece);
+            key ^= ZOBRIST.pieces[captured_piece as usize][captured_sq];
             self.apply_material_delta(them, captured_piece.get_type(), -1); // Update material
+            self.apply_psqt_delta(captured_piece, captured_sq, -1);
         } else if m.is_capture() {
             debug_assert!(self.mailbox[to].is_some()); // There must be a piece in the destination square
             let captured_piece = self.piece_on_unchecked(to);
@@ -93,14 +136,16 @@ impl Board {
             self.colors[them] ^= to.bb();
 
             newstate_captured = Some(captured_piece);
+            key ^= ZOBRIST.pieces[captured_piece as usize][to];
             self.apply_material_delta(them, captured_piece.get_type(), -1); // Update material
+            self.apply_psqt_delta(captured_piece, to, -1);
         }
-        if moved_type == PieceType::Pawn || m.is_capture() || m.is_enpassant() {
-            // m.is_enpassant() SHOULD be redundant
-            newstate_halfmove = 0; // Halfmove reset
+        // m.is_enpassant() SHOULD be redundant
+        let newstate_halfmove = if moved_type == PieceType::Pawn || m.is_capture() || m.is_enpassant() {
+            0 // Halfmove reset
         } else {
-            newstate_halfmove = self.state_stack[self.state_idx].halfmove + 1;
-        }
+            self.state_stack[self.state_idx].halfmove.saturating_add(1) // Still the old state, the new one is pushed in step 9
+        };
 
         // 4 - Handle destination square
         if m.is_promotion() {
@@ -109,14 +154,21 @@ impl Board {
 
             self.mailbox[to] = Some(promoted_piece);
             self.pieces[promoted_type] ^= to.bb();
+            key ^= ZOBRIST.pieces[promoted_piece as usize][to];
 
             self.apply_material_delta(us, PieceType::Pawn, -1);
             self.apply_material_delta(us, promoted_type, 1); // Yaaaasss queeeen (?)
+            self.apply_psqt_delta(promoted_piece, to, 1);
         } else {
             self.mailbox[to] = Some(moved_piece); //Normal piece move
             self.pieces[moved_type] ^= to.bb();
+            key ^= ZOBRIST.pieces[moved_piece as usize][to];
+            self.apply_psqt_delta(moved_piece, to, 1);
         }
         self.colors[us] ^= to.bb();
+        if moved_type == PieceType::King {
+            self.king_sq[us] = to;
+        }
 
         // 5 - Castling
         if m.is_castling() {
@@ -136,17 +188,14 @@ impl Board {
 
             self.pieces[PieceType::Rook] ^= rook_from.bb() | rook_to.bb();
             self.colors[us] ^= rook_from.bb() | rook_to.bb();
+            key ^= ZOBRIST.pieces[rook as usize][rook_from] ^ ZOBRIST.pieces[rook as usize][rook_to];
+            self.apply_psqt_delta(rook, rook_from, -1);
+            self.apply_psqt_delta(rook, rook_to, 1);
         }
 
-        // 6 - Update castling rights (branchless)
-        let mut castling_mask: u8 = 0xFF; // Default: no change
-        castling_mask &= !((from == Square::E1) as u8 * (WK | WQ)); // White king move
-        castling_mask &= !((from == Square::E8) as u8 * (BK | BQ)); // Black king move
-        castling_mask &= !((from == Square::H1 || to == Square::H1) as u8 * WK); // Rook move or capture
-        castling_mask &= !((from == Square::A1 || to == Square::A1) as u8 * WQ);
-        castling_mask &= !((from == Square::H8 || to == Square::H8) as u8 * BK);
-        castling_mask &= !((from == Square::A8 || to == Square::A8) as u8 * BQ);
-        newstate_castling &= castling_mask;
+        // 6 - Update castling rights (king move, rook move or rook capture)
+        newstate_castling.remove_square(from);
+        newstate_castling.remove_square(to);
 
         // 7 - Handle double push
         if m.is_double_push() {
@@ -154,19 +203,18 @@ impl Board {
             newstate_en_passant = Some(ep_sq);
         }
 
-        // 8 - Update zobrist
-        //TODO
+        // 8 - Update zobrist (castling rights and en-passant file, pieces were hashed above)
+        key ^= ZOBRIST.castling[self.state_stack[self.state_idx].castling.bits() as usize] ^ ZOBRIST.castling[newstate_castling.bits() as usize];
+        key ^= self.en_passant_hash(newstate_en_passant, them);
 
         // 9 - Push new state
-        let old_state = self.state_stack[self.state_idx];
-        self.state_idx += 1;
-        let new_state = &mut self.state_stack[self.state_idx];
-        *new_state = old_state; // Struct assign
+        let mut new_state = self.state_stack[self.state_idx]; // Struct copy
         new_state.en_passant = newstate_en_passant;
         new_state.captured = newstate_captured;
         new_state.castling = newstate_castling;
         new_state.halfmove = newstate_halfmove;
-        debug_assert!(self.state_idx < MAX_PLY);
+        new_state.fullmove += (us == Color::Black) as usize;
+        self.push_state(new_state, key);
 
         // 10 - Flip side
         self.side_to_move = !self.side_to_move;
@@ -185,9 +233,10 @@ impl Board {
 
         // 2 - Pop state
         let state = self.state_stack[self.state_idx];
-        self.state_idx -= 1;
+        self.pop_state();
 
         // 3 - Undo destination square
+        self.apply_psqt_delta(moved_piece, to, -1);
         self.pieces[moved_piece.get_type()] ^= to.bb();
         self.colors[us] ^= to.bb();
         self.mailbox[to] = None;
@@ -199,18 +248,23 @@ impl Board {
             self.pieces[captured.get_type()] ^= captured_sq.bb();
             self.colors[them] ^= captured_sq.bb();
             self.apply_material_delta(them, captured.get_type(), 1); // Update material
+            self.apply_psqt_delta(captured, captured_sq, 1);
         }
 
         // 5 - Restore origin square
         if m.is_promotion() {
             let promoted_type = moved_piece.get_type();
-            moved_piece = Piece::new(!self.side_to_move, PieceType::Pawn);
+            moved_piece = Piece::new(us, PieceType::Pawn);
             self.apply_material_delta(us, promoted_type, -1); // Remove promoted piece value
             self.apply_material_delta(us, PieceType::Pawn, 1); // Restore pawn value
         }
         self.mailbox[from] = Some(moved_piece);
         self.pieces[moved_piece.get_type()] ^= from.bb();
         self.colors[us] ^= from.bb();
+        self.apply_psqt_delta(moved_piece, from, 1);
+        if moved_piece.get_type() == PieceType::King {
+            self.king_sq[us] = from;
+        }
 
         // 6 - Undo castling
         if m.is_castling() {
@@ -226,24 +280,41 @@ impl Board {
             self.mailbox[rook_from] = Some(rook);
             self.pieces[PieceType::Rook] ^= rook_from.bb() | rook_to.bb();
             self.colors[us] ^= rook_from.bb() | rook_to.bb();
+            self.apply_psqt_delta(rook, rook_to, -1);
+            self.apply_psqt_delta(rook, rook_from, 1);
         }
     }
 
     /// Makes a null move, used for null-move pruning.
     #[inline(always)]
     pub fn make_null_move(&mut self) {
-        self.side_to_move = !self.side_to_move;
         let old_state = self.state_stack[self.state_idx];
-        self.state_idx += 1;
-        let new_state = &mut self.state_stack[self.state_idx];
-        *new_state = old_state; // Struct assign
-        new_state.en_passant = None;
+        let key = self.key_history[self.state_idx] ^ ZOBRIST.side ^ self.en_passant_hash(old_state.en_passant, self.side_to_move);
+
+        self.side_to_move = !self.side_to_move;
+        self.push_state(State { en_passant: None, ..old_state }, key);
     }
 
     /// Unmakes the null move, used for null-move pruning.
     #[inline(always)]
     pub fn unmake_null_move(&mut self) {
         self.side_to_move = !self.side_to_move;
+        self.pop_state();
+    }
+
+    /// Pushes the state and key of the position a move leads to. The stacks grow as needed, so games of any length fit.
+    #[inline(always)]
+    fn push_state(&mut self, state: State, key: u64) {
+        self.state_stack.push(state);
+        self.key_history.push(key);
+        self.state_idx += 1;
+    }
+
+    /// Pops the state and key of the current position, going back to the previous one.
+    #[inline(always)]
+    fn pop_state(&mut self) {
+        self.state_stack.pop();
+        self.key_history.pop();
         self.state_idx -= 1;
     }
 
@@ -256,22 +327,59 @@ impl Board {
         self.eval += piece_value(piece_type) * delta * sign;
     }
 
+    /// Updates the incremental PST sums for `piece` appearing on (`delta` = 1) or leaving (`delta` = -1) `sq`.
+    #[inline(always)]
+    fn apply_psqt_delta(&mut self, piece: Piece, sq: Square, delta: i32) {
+        let (mg, eg) = psqt(piece, sq);
+        self.psqt_mg += mg * delta;
+        self.psqt_eg += eg * delta;
+    }
+
+    /// Returns the material balance, white minus black.
+    #[inline(always)]
+    pub fn material_balance(&self) -> i32 {
+        self.eval
+    }
+
+    /// Returns the material of `color` without pawns and king.
+    ///
+    /// Zero means only pawns are left, where zugzwang is common and null-move pruning is unsound.
+    pub fn non_pawn_material(&self, color: Color) -> i32 {
+        [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen]
+            .into_iter()
+            .map(|pt| self.piece_count(pt, color) as i32 * piece_value(pt))
+            .sum()
+    }
+
+    /// Returns the midgame PST sum, white minus black.
+    #[inline(always)]
+    pub fn psqt_mg(&self) -> i32 {
+        self.psqt_mg
+    }
+
+    /// Returns the endgame PST sum, white minus black.
+    #[inline(always)]
+    pub fn psqt_eg(&self) -> i32 {
+        self.psqt_eg
+    }
+
+    /// Computes the (midgame, endgame) PST sums from scratch. Useful for debugging the incremental update.
+    pub fn compute_psqt(&self) -> (i32, i32) {
+        Square::ALL.iter().filter_map(|&sq| self.mailbox[sq].map(|piece| psqt(piece, sq))).fold((0, 0), |(mg, eg), (pmg, peg)| (mg + pmg, eg + peg))
+    }
+
     /// Returns color-relative static evaluation of the position.
     #[inline(always)]
     pub fn evaluate_relative(&mut self) -> i32 {
         let sign = 1 - ((self.side_to_move as i32) << 1); // Branchless
-        self.eval * sign
+        eval_position(self) * sign
     }
 
     /// Returns true if `color`'s king is in check.
     ///
     /// Locates king square and calls `is_square_attacked`.
     pub fn king_in_check(&self, color: Color) -> bool {
-        let king_bb = self.pieces[PieceType::King] & self.colors[color];
-        debug_assert!(king_bb != Bitboard(0));
-
-        let king_sq = Square::new(king_bb.lsb() as u8);
-        self.is_square_attacked(king_sq, !color)
+        self.is_square_attacked(self.king_square(color), !color)
     }
 
     /// Returns true if square `sq` is attacked by color `by`.
@@ -298,231 +406,1967 @@ impl Board {
         }
 
         // Bishop/Queen (diagonals)
-        {
-            let mt = &attack_tables.magic_tables;
-            let mask = mt.bishop_masks[sq];
-            let relevant = occupancy & mask;
-            let magic = mt.bishop_magics[sq];
-            let idx = ((relevant.0.wrapping_mul(magic)) >> (64 - mask.0.count_ones())) as usize;
-            let attacks = mt.bishop_attacks[mt.bishop_offsets[sq] + idx];
-
-            if attacks & ((self.piece(PieceType::Bishop) | self.piece(PieceType::Queen)) & their_pieces) != Bitboard(0) {
-                return true;
-            }
+        let bishop_attacks = attack_tables.magic_tables.bishop_lookup(sq, occupancy);
+        if bishop_attacks & ((self.piece(PieceType::Bishop) | self.piece(PieceType::Queen)) & their_pieces) != Bitboard(0) {
+            return true;
         }
 
         // Rook/Queen (orthogonal)
-        {
-            let mt = &attack_tables.magic_tables;
-            let mask = mt.rook_masks[sq];
-            let relevant = occupancy & mask;
-            let magic = mt.rook_magics[sq];
-            let idx = ((relevant.0.wrapping_mul(magic)) >> (64 - mask.0.count_ones())) as usize;
-            let attacks = mt.rook_attacks[mt.rook_offsets[sq] + idx];
-
-            if attacks & ((self.piece(PieceType::Rook) | self.piece(PieceType::Queen)) & their_pieces) != Bitboard(0) {
-                return true;
-            }
+        let rook_attacks = attack_tables.magic_tables.rook_lookup(sq, occupancy);
+        if rook_attacks & ((self.piece(PieceType::Rook) | self.piece(PieceType::Queen)) & their_pieces) != Bitboard(0) {
+            return true;
         }
 
         false
     }
 
-    /// Returns a specific bitboard from `self.pieces`.
-    #[inline(always)]
-    pub fn piece(&self, piece_type: PieceType) -> Bitboard {
-        self.pieces[piece_type as usize]
+    /// Returns the pieces of both colors attacking `sq`, given a custom board occupancy.
+    ///
+    /// The occupancy only affects sliding attacks, which allows asking "what would attack this square if the board
+    /// looked like this" without making a move.
+    pub fn attackers_to(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        let attack_tables = &self.attack_tables;
+        let pawns = self.pieces[PieceType::Pawn];
+        let diagonal = self.pieces[PieceType::Bishop] | self.pieces[PieceType::Queen];
+        let orthogonal = self.pieces[PieceType::Rook] | self.pieces[PieceType::Queen];
+
+        (attack_tables.pawn_capture[Color::Black][sq] & pawns & self.colors[Color::White])
+            | (attack_tables.pawn_capture[Color::White][sq] & pawns & self.colors[Color::Black])
+            | (attack_tables.knight[sq] & self.pieces[PieceType::Knight])
+            | (attack_tables.king[sq] & self.pieces[PieceType::King])
+            | (attack_tables.magic_tables.bishop_lookup(sq, occupancy) & diagonal)
+            | (attack_tables.magic_tables.rook_lookup(sq, occupancy) & orthogonal)
     }
 
-    /// Returns the piece on a specific square. Does not check if a piece is actually present.
-    #[inline(always)]
-    pub fn piece_on_unchecked(&self, sq: Square) -> Piece {
-        debug_assert!(!self.mailbox[sq].is_none()); // There must be a piece in the square
-        unsafe { self.mailbox[sq].unwrap_unchecked() }
+    /// Returns the attack set of a piece of type `piece_type` and color `color` standing on `sq`.
+    ///
+    /// Dispatches to the matching `Attacker` under the current occupancy. Pawns need the color and only attack diagonally.
+    pub fn attacks_from(&self, piece_type: PieceType, sq: Square, color: Color) -> Bitboard {
+        match piece_type {
+            PieceType::Pawn => self.attack_tables.pawn_capture[color][sq],
+            PieceType::Knight => Caval::get_attacks(sq, self),
+            PieceType::Bishop => Alfè::get_attacks(sq, self),
+            PieceType::Rook => Tor::get_attacks(sq, self),
+            PieceType::Queen => Argina::get_attacks(sq, self),
+            PieceType::King => Re::get_attacks(sq, self),
+        }
     }
 
-    /// Returns a specific bitboard from `self.colors`.
-    #[inline(always)]
-    pub fn color(&self, color: Color) -> Bitboard {
-        self.colors[color as usize]
+    /// Returns every square attacked by `color`'s pieces under the current occupancy, defended own pieces included.
+    ///
+    /// Computed in one pass over the pieces, so several questions about the same side are cheaper answered from this
+    /// set than with one `is_square_attacked` each.
+    pub fn all_attacks(&self, color: Color) -> Bitboard {
+        self.all_attacks_with_occupancy(color, self.occupied_squares())
     }
 
-    /// Returns black or white.
-    #[inline(always)]
-    pub fn side_to_move(&self) -> Color {
-        self.side_to_move
+    /// Like `all_attacks`, but sliders see through the given occupancy instead of the current one.
+    pub(crate) fn all_attacks_with_occupancy(&self, color: Color, occupancy: Bitboard) -> Bitboard {
+        let mut attacks = Bitboard(0);
+        for sq in self.colors[color].squares() {
+            attacks |= self.attacks_with_occupancy(self.piece_on_unchecked(sq).get_type(), sq, color, occupancy);
+        }
+        attacks
     }
 
-    /// Returns which squares are occupied by a piece of any color.
-    #[inline(always)]
-    pub fn occupied_squares(&self) -> Bitboard {
-        self.colors[Color::White] | self.colors[Color::Black]
+    /// Returns how many pieces of color `by` attack at least one square of `zone`, and their summed weight.
+    ///
+    /// Each attacker counts once, with the weight of its type in `KING_ATTACK_WEIGHTS`, however many squares of the zone
+    /// it hits. With the king zone this is the core of a king safety term, with any other zone it measures threats.
+    pub fn attack_count_on(&self, zone: Bitboard, by: Color) -> (u32, i32) {
+        let (mut attackers, mut weight) = (0, 0);
+        let mut pieces = self.colors[by];
+        while pieces != Bitboard(0) {
+            let sq = Square::new(pieces.pop_lsb() as u8);
+            let piece_type = self.piece_on_unchecked(sq).get_type();
+            if self.attacks_from(piece_type, sq, by) & zone != Bitboard(0) {
+                attackers += 1;
+                weight += KING_ATTACK_WEIGHTS[piece_type as usize];
+            }
+        }
+        (attackers, weight)
     }
 
-    /// Returns empty squares.
-    #[inline(always)]
-    pub fn empty_squares(&self) -> Bitboard {
-        !(self.colors[Color::White] | self.colors[Color::Black])
+    /// Returns true if the pseudo-legal move `m` gives check to the opponent, either directly or by discovery.
+    ///
+    /// Works on a copy of the occupancy like `leaves_king_safe`, so the move does not need to be made.
+    pub fn gives_check(&self, m: Move) -> bool {
+        let (from, to) = (m.from(), m.to());
+        let (us, them) = (self.side_to_move, !self.side_to_move);
+        let their_king = self.king_square(them);
+
+        let mut occupancy = (self.occupied_squares() ^ from.bb()) | to.bb();
+        if m.is_enpassant() {
+            occupancy ^= if us == Color::White { to.south() } else { to.north() }.bb();
+        }
+
+        // Direct check by the piece landing on `to`
+        let landed = if m.is_promotion() { m.promotion_piece() } else { self.piece_on_unchecked(from).get_type() };
+        if self.attacks_with_occupancy(landed, to, us, occupancy) & their_king.bb() != Bitboard(0) {
+            return true;
+        }
+
+        // Castling rook, it lands between the king squares
+        if m.is_castling() {
+            let rook_from = if to.file() == 6 { Square::new(to as u8 + 1) } else { Square::new(to as u8 - 2) };
+            let rook_to = Square::new((from as u8 + to as u8) / 2);
+            occupancy ^= rook_from.bb() | rook_to.bb();
+            if self.attack_tables.magic_tables.rook_lookup(rook_to, occupancy) & their_king.bb() != Bitboard(0) {
+                return true;
+            }
+        }
+
+        // Discovered check by a slider, the moved piece is excluded since it was handled above
+        self.attackers_to(their_king, occupancy) & self.colors[us] & !from.bb() != Bitboard(0)
     }
 
-    /// Returns the en-passant capture square, if existing.
+    /// Like `attacks_from`, but sliders see through the given occupancy instead of the current one.
     #[inline(always)]
-    pub fn en_passant_square(&self) -> Option<Square> {
-        self.state_stack[self.state_idx].en_passant
+    fn attacks_with_occupancy(&self, piece_type: PieceType, sq: Square, color: Color, occupancy: Bitboard) -> Bitboard {
+        let mt = &self.attack_tables.magic_tables;
+        match piece_type {
+            PieceType::Pawn => self.attack_tables.pawn_capture[color][sq],
+            PieceType::Knight => self.attack_tables.knight[sq],
+            PieceType::Bishop => mt.bishop_lookup(sq, occupancy),
+            PieceType::Rook => mt.rook_lookup(sq, occupancy),
+            PieceType::Queen => mt.bishop_lookup(sq, occupancy) | mt.rook_lookup(sq, occupancy),
+            PieceType::King => self.attack_tables.king[sq],
+        }
     }
 
-    /// Returns the castling rights, encoded in a u8.
-    #[inline(always)]
-    pub fn castling_rights(&self) -> u8 {
-        self.state_stack[self.state_idx].castling
+    /// Returns the enemy pieces giving check to the side to move.
+    pub fn checkers(&self) -> Bitboard {
+        let us = self.side_to_move;
+        let king_sq = self.king_square(us);
+        self.attackers_to(king_sq, self.occupied_squares()) & self.colors[!us]
     }
 
-    /// Sets board to the starting position.
-    /// # Panics
-    /// Panics if the internal FEN parser fails.
-    pub fn set_startpos(&mut self) {
-        self.from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    /// Returns the pieces of the side to move that are pinned to their own king.
+    ///
+    /// An enemy slider aligned with our king pins a piece of ours if it is the only piece standing between them.
+    pub fn pinned(&self) -> Bitboard {
+        let (us, them) = (self.side_to_move, !self.side_to_move);
+        let king_sq = self.king_square(us);
+        let occupancy = self.occupied_squares();
+        let mt = &self.attack_tables.magic_tables;
+
+        let diagonal = (self.pieces[PieceType::Bishop] | self.pieces[PieceType::Queen]) & self.colors[them];
+        let orthogonal = (self.pieces[PieceType::Rook] | self.pieces[PieceType::Queen]) & self.colors[them];
+        let mut snipers = (mt.bishop_lookup(king_sq, Bitboard(0)) & diagonal) | (mt.rook_lookup(king_sq, Bitboard(0)) & orthogonal);
+
+        let mut pinned = Bitboard(0);
+        while snipers != Bitboard(0) {
+            let sniper_sq = Square::new(snipers.pop_lsb() as u8);
+            let blockers = self.attack_tables.between[king_sq][sniper_sq] & occupancy;
+            if blockers.popcnt() == 1 {
+                pinned |= blockers & self.colors[us];
+            }
+        }
+        pinned
     }
 
-    /// Sets board state from a FEN string
-    pub fn from_fen(&mut self, fen: &str) -> Result<(), &'static str> {
-        let mut parts = fen.split_whitespace();
-        let board_part = parts.next().ok_or("FEN missing board part")?;
-        let side_part = parts.next().ok_or("FEN missing side to move")?;
-        let castling_part = parts.next().unwrap_or("-");
-        let en_passant_part = parts.next().unwrap_or("-");
-        let halfmove_part = parts.next().unwrap_or("0");
-        let _ = parts.next().unwrap_or("1"); //fullmove
+    /// Returns true if the pseudo-legal move `m` is legal, given the precomputed `pinned()` set.
+    ///
+    /// When not in check, only king moves, en passant and moves of pinned pieces can expose the king, so every
+    /// other move is accepted without running `leaves_king_safe`.
+    #[inline(always)]
+    pub fn is_legal_fast(&self, m: Move, pinned: Bitboard, in_check: bool) -> bool {
+        let from = m.from();
+        let needs_check = in_check || m.is_enpassant() || pinned & from.bb() != Bitboard(0) || self.piece_on_unchecked(from).get_type() == PieceType::King;
 
-        //R eset board
-        self.mailbox.fill(Option::None);
-        self.pieces.fill(Bitboard(0));
-        self.colors = [Bitboard(0); 2];
-        self.state_idx = 0;
+        !needs_check || self.leaves_king_safe(m)
+    }
 
-        // ===== Parse board squares =====
-        for (rank_idx, rank) in board_part.split('/').enumerate() {
-            let rank_num = 7 - rank_idx; //FEN top rank = 7
-            let mut file = 0;
+    /// Returns true if the pseudo-legal move `m` does not leave the side to move in check.
+    ///
+    /// Works on a copy of the occupancy instead of making the move, so it can be called on a shared board.
+    /// Castling is assumed safe, since the king path is already checked during generation.
+    pub fn leaves_king_safe(&self, m: Move) -> bool {
+        if m.is_castling() {
+            return true;
+        }
 
-            for ch in rank.chars() {
-                if ch.is_digit(10) {
-                    let skip = ch.to_digit(10).unwrap();
-                    file += skip;
-                } else {
-                    let sq = rank_num * 8 + file as usize;
-                    let piece = Piece::from_char(ch);
-                    self.mailbox[sq] = Some(piece);
+        let (from, to) = (m.from(), m.to());
+        let (us, them) = (self.side_to_move, !self.side_to_move);
 
-                    let color = piece.get_color();
-                    let ptype = piece.get_type();
-                    let sq_bb = Square::new(sq as u8).bb();
-                    self.pieces[ptype] |= sq_bb;
-                    self.colors[color] |= sq_bb;
+        let mut occupancy = (self.occupied_squares() ^ from.bb()) | to.bb();
+        let mut captured = to.bb(); // A captured piece no longer attacks anything
+        if m.is_enpassant() {
+            let captured_sq = if us == Color::White { to.south() } else { to.north() };
+            occupancy ^= captured_sq.bb();
+            captured = captured_sq.bb();
+        }
 
-                    self.apply_material_delta(color, ptype, 1);
+        let king_bb = self.pieces_of(us, PieceType::King);
+        let king_sq = if king_bb == from.bb() { to } else { king_bb.square() };
 
-                    file += 1;
-                }
-            }
-            if file != 8 {
-                return Err("Invalid FEN rank length");
+        self.attackers_to(king_sq, occupancy) & self.colors[them] & !captured == Bitboard(0)
+    }
+
+    /// Static exchange evaluation: the material balance, for the side to move, of the capture sequence started by `m`
+    /// on its destination square, each side recapturing with its least valuable attacker and free to stop when going
+    /// on would lose material. Returns 0 for quiet moves.
+    ///
+    /// Sliders uncovered behind a capturing piece join the exchange, since attackers are recomputed on the shrinking
+    /// occupancy. Pins are ignored, as usual for SEE.
+    pub fn see(&self, m: Move) -> i32 {
+        self.see_with(m, &PieceValues::default())
+    }
+
+    /// `see` with the given piece values. Attackers still recapture in piece type order, pawns first.
+    pub fn see_with(&self, m: Move, values: &PieceValues) -> i32 {
+        let (from, to) = (m.from(), m.to());
+        let mut occupancy = self.occupied_squares() ^ from.bb();
+        let victim = if m.is_enpassant() {
+            occupancy ^= if self.side_to_move == Color::White { to.south() } else { to.north() }.bb();
+            PieceType::Pawn
+        } else {
+            match self.mailbox[to] {
+                Some(piece) => piece.get_type(),
+                None => return 0,
             }
+        };
+
+        // gain[d] is the balance for the side making the d-th capture, if the exchange stopped right after it. Each capture
+        // removes a piece, so one slot per square is enough whatever the position
+        let mut gain = [0i32; 64];
+        gain[0] = see_value(values, victim);
+        let mut on_square = self.piece_on_unchecked(from).get_type();
+        if m.is_promotion() {
+            on_square = m.promotion_piece();
+            gain[0] += see_value(values, on_square) - see_value(values, PieceType::Pawn);
         }
 
-        // ===== Parse side to move =====
-        self.side_to_move = match side_part {
-            "w" => Color::White,
-            "b" => Color::Black,
-            _ => return Err("Invalid side to move"),
-        };
+        let mut side = !self.side_to_move;
+        let mut depth = 0;
+        loop {
+            let attackers = self.attackers_to(to, occupancy) & occupancy & self.colors[side];
+            let Some(attacker) = self.least_valuable_attacker(attackers) else {
+                break;
+            };
+            depth += 1;
+            gain[depth] = see_value(values, on_square) - gain[depth - 1];
+            occupancy ^= (attackers & self.pieces[attacker]).square().bb();
+            on_square = attacker;
+            side = !side;
+        }
 
-        // ===== Parse castling rights =====
-        let mut castling = 0u8;
-        for ch in castling_part.chars() {
-            match ch {
-                'K' => castling |= 1 << 0,
-                'Q' => castling |= 1 << 1,
-                'k' => castling |= 1 << 2,
-                'q' => castling |= 1 << 3,
-                '-' => {}
-                _ => return Err("Invalid castling"),
-            }
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
         }
+        gain[0]
+    }
 
-        // ===== Parse en passant square =====
-        let en_passant = if en_passant_part == "-" {
-            None
+    /// Returns true if the static exchange of `m` is at least `threshold`, as `see(m) >= threshold` but faster.
+    ///
+    /// Meant for pruning, which only needs the comparison: instead of the whole capture sequence, only a running
+    /// balance is kept, and the loop ends as soon as the side to recapture can stop with the threshold decided.
+    pub fn see_ge(&self, m: Move, threshold: i32) -> bool {
+        self.see_ge_with(m, threshold, &PieceValues::default())
+    }
+
+    /// `see_ge` with the given piece values, agreeing with `see_with`.
+    pub fn see_ge_with(&self, m: Move, threshold: i32, values: &PieceValues) -> bool {
+        let (from, to) = (m.from(), m.to());
+        let mut occupancy = self.occupied_squares() ^ from.bb();
+        let victim = if m.is_enpassant() {
+            occupancy ^= if self.side_to_move == Color::White { to.south() } else { to.north() }.bb();
+            PieceType::Pawn
         } else {
-            let bytes = en_passant_part.as_bytes();
-            let file = bytes[0].wrapping_sub(b'a');
-            let rank = bytes[1].wrapping_sub(b'1');
-            if file > 7 || rank > 7 {
-                return Err("Invalid en passant square");
+            match self.mailbox[to] {
+                Some(piece) => piece.get_type(),
+                None => return threshold <= 0,
             }
-            Some(Square::new((rank as u8) * 8 + (file as u8)))
         };
 
-        // ===== Set initial state =====
-        self.state_stack[0] = State {
-            castling,
-            en_passant,
-            halfmove: halfmove_part.parse().unwrap_or_default(),
-            captured: Option::None,
-            zobrist: Bitboard(0),
-        };
-        self.state_idx = 0;
+        let mut on_square = self.piece_on_unchecked(from).get_type();
+        let mut balance = see_value(values, victim) - threshold;
+        if m.is_promotion() {
+            on_square = m.promotion_piece();
+            balance += see_value(values, on_square) - see_value(values, PieceType::Pawn);
+        }
+        if balance < 0 {
+            return false; // Short of the threshold even if nothing recaptures
+        }
+        balance = see_value(values, on_square) - balance;
+        if balance <= 0 {
+            return true; // Still at the threshold after losing the capturing piece
+        }
 
-        Ok(())
+        // `balance` is what the side about to recapture must win back, `result` whether the mover is doing well
+        let (mut side, mut result) = (self.side_to_move, true);
+        loop {
+            side = !side;
+            let attackers = self.attackers_to(to, occupancy) & occupancy;
+            let Some(attacker) = self.least_valuable_attacker(attackers & self.colors[side]) else {
+                break;
+            };
+            result = !result;
+            if attacker == PieceType::King {
+                // The king can only take if nothing recaptures it
+                return if attackers & self.colors[!side] != Bitboard(0) { !result } else { result };
+            }
+            balance = see_value(values, attacker) - balance;
+            if balance < result as i32 {
+                break;
+            }
+            occupancy ^= (attackers & self.colors[side] & self.pieces[attacker]).square().bb();
+        }
+        result
     }
 
-    /// Prints the board to console terminal for debug.
-    pub fn print(&self) {
-        println!("Side to move: {:?}", self.side_to_move);
-        println!("  +------------------------+");
+    /// Returns the type of the least valuable piece among `attackers`, if any.
+    #[inline(always)]
+    fn least_valuable_attacker(&self, attackers: Bitboard) -> Option<PieceType> {
+        let by_value = [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King];
+        by_value.into_iter().find(|&pt| attackers & self.pieces[pt] != Bitboard(0))
+    }
 
-        for rank in (0..8).rev() {
-            // 8 = ranks, print rank 8..1
-            print!("{} |", rank + 1);
-            for file in 0..8 {
-                let sq = rank * 8 + file;
-                let ch = self.mailbox[sq].map_or('.', |p| p.to_char());
-                print!(" {} ", ch);
-            }
-            println!("|");
-        }
+    /// Classifies a capture by its static exchange: `Greater` wins material, `Equal` trades evenly, `Less` loses
+    /// material. Quiet moves are `Equal`.
+    pub fn capture_class(&self, m: Move) -> Ordering {
+        self.see(m).cmp(&0)
+    }
 
-        println!("  +------------------------+");
-        println!("    a  b  c  d  e  f  g  h");
+    /// Returns the legal moves of the side to move in a new list.
+    ///
+    /// Convenient for one-shot use; hot paths fill a reused list with `generate_legal_moves` instead.
+    pub fn legal_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        generate_legal_moves(self, &mut moves);
+        moves
     }
-}
 
-impl Default for Board {
-    fn default() -> Self {
-        Self {
-            mailbox: [Option::None; 64],
-            pieces: [Bitboard(0); PieceType::NUM],
-            colors: [Bitboard(0); 2],
-            side_to_move: Color::White,
+    /// Returns the pseudo-legal moves of the side to move in a new list, some may leave the king in check.
+    pub fn pseudo_legal_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        generate_all_moves(self, &mut moves);
+        moves
+    }
 
-            state_stack: [State::default(); MAX_PLY],
-            state_idx: 0,
+    /// Returns true if the side to move has at least one legal move.
+    ///
+    /// Stops at the first pseudo-legal move that does not leave the king in check, without materializing the legal list.
+    pub fn has_legal_move(&self) -> bool {
+        let mut moves = MoveList::new();
+        generate_all_moves(self, &mut moves);
+        moves.iter().any(|m| self.leaves_king_safe(m))
+    }
 
-            eval: 0,
+    /// Returns true if the side to move is checkmated.
+    pub fn is_checkmate(&self) -> bool {
+        self.king_in_check(self.side_to_move) && !self.has_legal_move()
+    }
 
-            attack_tables: AttackTables::new(),
+    /// Returns true if the legal move `m` checkmates the opponent.
+    ///
+    /// Moves that do not give check are rejected without making them; checking moves are made, tested for a legal
+    /// reply and unmade, hence `&mut self`.
+    pub fn gives_checkmate(&mut self, m: Move) -> bool {
+        if !self.gives_check(m) {
+            return false;
         }
+        self.make_move(m);
+        let mate = !self.has_legal_move();
+        self.unmake_move(m);
+        mate
     }
-}
 
-impl Default for State {
-    fn default() -> Self {
-        Self {
-            castling: 0,
-            en_passant: None,
-            halfmove: 0,
-            captured: Option::None,
-            zobrist: Bitboard(0),
+    /// Returns true if the side to move is stalemated.
+    pub fn is_stalemate(&self) -> bool {
+        !self.king_in_check(self.side_to_move) && !self.has_legal_move()
+    }
+
+    /// Returns the squares the piece on `from` can legally move to, empty if there is none or it cannot move.
+    ///
+    /// Meant for move highlighting in a UI. Castling is reported as the king destination square.
+    pub fn legal_targets(&self, from: Square) -> Bitboard {
+        let mut moves = MoveList::new();
+        generate_legal_moves(self, &mut moves);
+        moves.iter().filter(|m| m.from() == from).fold(Bitboard(0), |targets, m| targets | m.to().bb())
+    }
+
+    /// Returns true if neither side can possibly checkmate: no pawns, rooks or queens, and either at most one minor
+    /// piece or only bishops all standing on squares of the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        if self.pieces[PieceType::Pawn] | self.pieces[PieceType::Rook] | self.pieces[PieceType::Queen] != Bitboard(0) {
+            return false;
+        }
+
+        let (knights, bishops) = (self.pieces[PieceType::Knight], self.pieces[PieceType::Bishop]);
+        if (knights | bishops).popcnt() <= 1 {
+            return true;
+        }
+        knights == Bitboard(0) && (bishops & Bitboard::light_squares() == Bitboard(0) || bishops & !Bitboard::light_squares() == Bitboard(0))
+    }
+
+    /// Returns true if neither side can force checkmate, though one may still be possible if the loser helps.
+    ///
+    /// Covers `is_insufficient_material`, plus the pawnless endings where each side keeps at most one minor piece,
+    /// and two knights against a bare king. Unlike `is_insufficient_material`, these positions are not dead in the
+    /// FIDE sense, so the game must not be ended on them: this is for adjudication and contempt decisions only.
+    pub fn is_unwinnable_material(&self) -> bool {
+        if self.is_insufficient_material() {
+            return true;
+        }
+        if self.pieces[PieceType::Pawn] | self.pieces[PieceType::Rook] | self.pieces[PieceType::Queen] != Bitboard(0) {
+            return false;
+        }
+
+        let minors = |color: Color| self.pieces_of(color, PieceType::Knight) | self.pieces_of(color, PieceType::Bishop);
+        let (white, black) = (minors(Color::White), minors(Color::Black));
+        let two_knights = |own: Bitboard, color: Color| own == self.pieces_of(color, PieceType::Knight) && own.popcnt() == 2;
+        (white.popcnt() <= 1 && black.popcnt() <= 1)
+            || (black == Bitboard(0) && two_knights(white, Color::White))
+            || (white == Bitboard(0) && two_knights(black, Color::Black))
+    }
+
+    /// Heuristic for blocked pawn fortresses, for analysis display only: never use it to adjudicate a game.
+    ///
+    /// Returns true if only kings and pawns are left, every pawn is blocked by an enemy pawn and has nothing to capture,
+    /// and neither king can walk to an enemy pawn that is not defended by another pawn. Kings are then unable to make
+    /// any progress. The check is conservative: the enemy king is ignored, so the regions walked are overestimated.
+    pub fn is_likely_dead_draw(&self) -> bool {
+        let pawns = self.pieces[PieceType::Pawn];
+        if pawns | self.pieces[PieceType::King] != self.occupied_squares() || pawns == Bitboard(0) {
+            return false;
+        }
+
+        let white_pawns = self.pieces_of(Color::White, PieceType::Pawn);
+        let black_pawns = self.pieces_of(Color::Black, PieceType::Pawn);
+        if Bitboard(white_pawns.0 << 8) != black_pawns {
+            return false; // Some pawn is not blocked head-on by an enemy pawn
+        }
+
+        let pawn_attacks = |mut bb: Bitboard, color: Color| {
+            let mut attacks = Bitboard(0);
+            while bb != Bitboard(0) {
+                attacks |= self.attack_tables.pawn_capture[color][bb.pop_lsb()];
+            }
+            attacks
+        };
+        let white_attacks = pawn_attacks(white_pawns, Color::White);
+        let black_attacks = pawn_attacks(black_pawns, Color::Black);
+        if white_attacks & black_pawns != Bitboard(0) || black_attacks & white_pawns != Bitboard(0) {
+            return false; // A pawn capture can open the position
+        }
+
+        for (color, their_pawns, their_attacks) in [(Color::White, black_pawns, black_attacks), (Color::Black, white_pawns, white_attacks)] {
+            // Flood fill the squares the king can walk to, without stepping on pawns or enemy pawn attacks
+            let walkable = !(pawns | their_attacks);
+            let mut region = self.king_square(color).bb();
+            loop {
+                let mut grown = region;
+                let mut frontier = region;
+                while frontier != Bitboard(0) {
+                    grown |= self.attack_tables.king[frontier.pop_lsb()] & walkable;
+                }
+                if grown == region {
+                    break;
+                }
+                region = grown;
+            }
+
+            // Undefended enemy pawns next to the region can be captured
+            let mut reach = Bitboard(0);
+            while region != Bitboard(0) {
+                reach |= self.attack_tables.king[region.pop_lsb()];
+            }
+            if reach & their_pawns & !their_attacks != Bitboard(0) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns true if `m` is one of the legal moves of the current position.
+    ///
+    /// Meant for moves coming from outside the engine (UCI, PGN, UI): since the whole encoding is compared, a move with
+    /// the right squares but wrong flags is rejected too. Slower than `is_legal_fast`, do not use it in search.
+    pub fn is_legal(&self, m: Move) -> bool {
+        let mut moves = MoveList::new();
+        generate_legal_moves(self, &mut moves);
+        moves.iter().any(|legal| legal == m)
+    }
+
+    /// Makes `m` only if it is legal, for moves coming from outside the engine such as a book move or a UCI `moves`
+    /// list that may belong to another position. The board is left untouched on error.
+    pub fn try_make_move(&mut self, m: Move) -> Result<(), &'static str> {
+        match self.mailbox[m.from()] {
+            None => return Err("No piece on the origin square"),
+            Some(piece) if piece.get_color() != self.side_to_move => return Err("Move for the side not to move"),
+            _ if !self.is_legal(m) => return Err("Illegal move"),
+            _ => {}
+        }
+        self.make_move(m);
+        Ok(())
+    }
+
+    /// Resolves a move in UCI notation (`e2e4`, `e7e8q`) against the legal moves of the current position.
+    pub fn parse_uci_move(&self, uci: &str) -> Result<Move, &'static str> {
+        let bytes = uci.as_bytes();
+        let is_square = |file: u8, rank: u8| (b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank);
+        let well_formed =
+            matches!(bytes.len(), 4 | 5) && is_square(bytes[0], bytes[1]) && is_square(bytes[2], bytes[3]) && (bytes.len() == 4 || b"nbrq".contains(&bytes[4]));
+        if !well_formed {
+            return Err("Unparseable UCI move");
+        }
+
+        let mut moves = MoveList::new();
+        generate_legal_moves(self, &mut moves);
+        moves.iter().find(|m| m.to_string() == uci).ok_or("Illegal UCI move")
+    }
+
+    /// Makes the UCI moves in sequence, as in `position ... moves ...`, without going through a FEN.
+    ///
+    /// Stops at the first unparseable or illegal move and returns its index in `moves` with the reason, the moves
+    /// before it stay made.
+    pub fn apply_uci_moves(&mut self, moves: &[&str]) -> Result<(), (usize, &'static str)> {
+        for (i, uci) in moves.iter().enumerate() {
+            let m = self.parse_uci_move(uci).map_err(|e| (i, e))?;
+            self.make_move(m);
+        }
+        Ok(())
+    }
+
+    /// Formats the legal move `m` in standard algebraic notation (`Nbd7`, `exd6`, `e8=Q`, `O-O`), with the `+` or `#`
+    /// suffix.
+    ///
+    /// Finding the suffix may require making the move, hence `&mut self`, see `gives_checkmate`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_san(&mut self, m: Move) -> String {
+        let mut moves = MoveList::new();
+        generate_legal_moves(self, &mut moves);
+        let mut san = self.san_body(m, &moves);
+        if self.gives_check(m) {
+            san.push(if self.gives_checkmate(m) { '#' } else { '+' });
+        }
+        san
+    }
+
+    /// Returns every legal move in SAN, in generation order, each disambiguated against the whole legal move set.
+    ///
+    /// The board is left unchanged; `&mut self` is only needed for the mate suffix, as in `to_san`.
+    pub fn legal_moves_san(&mut self) -> Vec<String> {
+        let moves = self.legal_moves();
+        let mut sans = Vec::with_capacity(moves.count());
+        for m in moves.iter() {
+            let mut san = self.san_body(m, &moves);
+            if self.gives_check(m) {
+                san.push(if self.gives_checkmate(m) { '#' } else { '+' });
+            }
+            sans.push(san);
+        }
+        sans
+    }
+
+    /// Resolves a move in standard algebraic notation against the legal moves of the current position.
+    ///
+    /// Check and annotation suffixes (`+`, `#`, `!`, `?`) are ignored, castling may be written with zeros and the `=`
+    /// of promotions may be left out. Disambiguation must be the minimal one, as written by `to_san`.
+    pub fn parse_san(&self, san: &str) -> Result<Move, &'static str> {
+        let normalize = |san: &str| san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O").replace('=', "");
+        let wanted = normalize(san);
+        if wanted.is_empty() {
+            return Err("Empty SAN move");
+        }
+
+        let mut moves = MoveList::new();
+        generate_legal_moves(self, &mut moves);
+        moves.iter().find(|&m| normalize(&self.san_body(m, &moves)) == wanted).ok_or("Illegal SAN move")
+    }
+
+    /// Formats `m` in SAN without the check suffix, disambiguating against the other `legal` moves.
+    fn san_body(&self, m: Move, legal: &MoveList) -> String {
+        if m.is_castling() {
+            return if m.to().file() == 6 { "O-O" } else { "O-O-O" }.to_string();
+        }
+
+        let (from, to) = (m.from(), m.to());
+        let piece_type = self.piece_on_unchecked(from).get_type();
+        let mut san = String::new();
+        if piece_type == PieceType::Pawn {
+            if m.is_capture() {
+                san.push(char::from(b'a' + from.file()));
+            }
+        } else {
+            san.push(Piece::new(Color::White, piece_type).to_char());
+
+            // Another piece of the same type reaching `to`: the file if it tells them apart, else the rank, else both
+            let rivals = legal.iter().filter(|o| o.to() == to && o.from() != from && self.piece_on_unchecked(o.from()).get_type() == piece_type);
+            let (mut ambiguous, mut same_file, mut same_rank) = (false, false, false);
+            for rival in rivals {
+                ambiguous = true;
+                same_file |= rival.from().file() == from.file();
+                same_rank |= rival.from().rank() == from.rank();
+            }
+            if ambiguous && (!same_file || same_rank) {
+                san.push(char::from(b'a' + from.file()));
+            }
+            if same_file {
+                san.push(char::from(b'1' + from.rank()));
+            }
+        }
+
+        if m.is_capture() {
+            san.push('x');
+        }
+        san += &to.to_string();
+        if m.is_promotion() {
+            san.push('=');
+            san.push(Piece::new(Color::White, m.promotion_piece()).to_char());
+        }
+        san
+    }
+
+    /// Returns a specific bitboard from `self.pieces`.
+    #[inline(always)]
+    pub fn piece(&self, piece_type: PieceType) -> Bitboard {
+        self.pieces[piece_type as usize]
+    }
+
+    /// Returns the pieces of type `piece_type` and color `color`.
+    #[inline(always)]
+    pub fn pieces_of(&self, color: Color, piece_type: PieceType) -> Bitboard {
+        self.pieces[piece_type] & self.colors[color]
+    }
+
+    /// Returns the number of pieces of type `piece_type` and color `color`.
+    #[inline(always)]
+    pub fn piece_count(&self, piece_type: PieceType, color: Color) -> u32 {
+        self.pieces_of(color, piece_type).popcnt() as u32
+    }
+
+    /// Returns the number of pieces of `color`, king and pawns included.
+    #[inline(always)]
+    pub fn total_piece_count(&self, color: Color) -> u32 {
+        self.colors[color].popcnt() as u32
+    }
+
+    /// Returns the square of `color`'s king.
+    #[inline(always)]
+    pub fn king_square(&self, color: Color) -> Square {
+        debug_assert!(self.pieces_of(color, PieceType::King) == self.king_sq[color].bb());
+        self.king_sq[color]
+    }
+
+    /// Returns the piece on a specific square, if any.
+    #[inline(always)]
+    pub fn piece_on(&self, sq: Square) -> Option<Piece> {
+        self.mailbox[sq]
+    }
+
+    /// Returns the piece on a specific square. Does not check if a piece is actually present.
+    #[inline(always)]
+    pub fn piece_on_unchecked(&self, sq: Square) -> Piece {
+        debug_assert!(self.mailbox[sq].is_some()); // There must be a piece in the square
+        unsafe { self.mailbox[sq].unwrap_unchecked() }
+    }
+
+    /// Returns a specific bitboard from `self.colors`.
+    #[inline(always)]
+    pub fn color(&self, color: Color) -> Bitboard {
+        self.colors[color as usize]
+    }
+
+    /// Returns black or white.
+    #[inline(always)]
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    /// Returns which squares are occupied by a piece of any color.
+    #[inline(always)]
+    pub fn occupied_squares(&self) -> Bitboard {
+        self.colors[Color::White] | self.colors[Color::Black]
+    }
+
+    /// Returns empty squares.
+    #[inline(always)]
+    pub fn empty_squares(&self) -> Bitboard {
+        !(self.colors[Color::White] | self.colors[Color::Black])
+    }
+
+    /// Returns the en-passant capture square, if existing.
+    #[inline(always)]
+    pub fn en_passant_square(&self) -> Option<Square> {
+        self.state_stack[self.state_idx].en_passant
+    }
+
+    /// Returns true if both boards hold the same position: pieces, side to move, castling rights, and en-passant square
+    /// when a capture on it is possible, as for the Zobrist key. Move counters and history are not compared.
+    pub fn position_eq(&self, other: &Board) -> bool {
+        let en_passant = |board: &Board| if board.can_capture_en_passant() { board.en_passant_square() } else { None };
+        self.mailbox == other.mailbox
+            && self.side_to_move == other.side_to_move
+            && self.castling_rights() == other.castling_rights()
+            && en_passant(self) == en_passant(other)
+    }
+
+    /// Returns the Zobrist key of the current position.
+    #[inline(always)]
+    pub fn zobrist_key(&self) -> u64 {
+        self.key_history[self.state_idx]
+    }
+
+    /// Returns the Zobrist keys of all positions since the last FEN was loaded, current position last.
+    ///
+    /// Null moves made during search push a key too, so this is only meaningful outside of search.
+    #[inline(always)]
+    pub fn history_keys(&self) -> &[u64] {
+        &self.key_history[..=self.state_idx]
+    }
+
+    /// Returns true if the current position already occurred earlier in the history.
+    ///
+    /// Cheap enough for the search hot path, see `repetition_candidates`.
+    pub fn is_repetition(&self) -> bool {
+        let key = self.zobrist_key();
+        self.repetition_candidates().any(|&k| k == key)
+    }
+
+    /// Returns how many times the current position occurred in the history, itself included.
+    pub fn repetition_count(&self) -> usize {
+        let key = self.zobrist_key();
+        1 + self.repetition_candidates().filter(|&&k| k == key).count()
+    }
+
+    /// Returns the keys of the earlier positions that can be equal to the current one, most recent first.
+    ///
+    /// Positions before the last capture or pawn move cannot repeat, so the scan is bounded by the halfmove clock, and
+    /// a repetition needs at least 4 plies. Only positions with the same side to move can repeat, so every other key
+    /// is skipped.
+    #[inline(always)]
+    fn repetition_candidates(&self) -> impl Iterator<Item = &u64> {
+        let halfmove = self.halfmove_clock();
+        let window = if halfmove < 4 { &[][..] } else { &self.key_history[self.state_idx.saturating_sub(halfmove)..self.state_idx] };
+        window.iter().rev().skip(1).step_by(2)
+    }
+
+    /// Computes the Zobrist key of the current position from scratch.
+    ///
+    /// Used to initialize the key on FEN loading, and to verify the incremental key in debug.
+    pub fn compute_zobrist_key(&self) -> u64 {
+        let mut key = 0u64;
+        for sq in Square::ALL {
+            if let Some(piece) = self.mailbox[sq] {
+                key ^= ZOBRIST.pieces[piece as usize][sq];
+            }
+        }
+        if self.side_to_move == Color::Black {
+            key ^= ZOBRIST.side;
+        }
+        let state = &self.state_stack[self.state_idx];
+        key ^= ZOBRIST.castling[state.castling.bits() as usize];
+        key ^ self.en_passant_hash(state.en_passant, self.side_to_move)
+    }
+
+    /// Returns true if a pawn of the side to move stands next to the double-pushed pawn and can capture en passant.
+    ///
+    /// Only pseudo-legality is checked: a pinned pawn still counts, as in the Polyglot key.
+    #[inline(always)]
+    pub fn can_capture_en_passant(&self) -> bool {
+        self.en_passant_hash(self.en_passant_square(), self.side_to_move) != 0
+    }
+
+    /// Returns the Zobrist contribution of the en-passant square `ep`, with `capturer` to move.
+    ///
+    /// The square is kept in the state for FEN fidelity, but its file is hashed only when a pawn can capture on it, so
+    /// that a position has the same key whether it was reached with a double push, a single push, or loaded from FEN.
+    #[inline(always)]
+    fn en_passant_hash(&self, ep: Option<Square>, capturer: Color) -> u64 {
+        match ep {
+            Some(ep) if self.attack_tables.pawn_capture[!capturer][ep] & self.pieces_of(capturer, PieceType::Pawn) != Bitboard(0) => ZOBRIST.en_passant_key(ep),
+            _ => 0,
+        }
+    }
+
+    /// Returns the castling rights.
+    #[inline(always)]
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.state_stack[self.state_idx].castling
+    }
+
+    /// Returns true if `color` still has the right to castle kingside.
+    ///
+    /// Only the right is checked: the path may currently be blocked or attacked, see `generate_castling`.
+    #[inline(always)]
+    pub fn can_castle_kingside(&self, color: Color) -> bool {
+        self.castling_rights().has(color, CastlingSide::King)
+    }
+
+    /// Returns true if `color` still has the right to castle queenside, see `can_castle_kingside`.
+    #[inline(always)]
+    pub fn can_castle_queenside(&self, color: Color) -> bool {
+        self.castling_rights().has(color, CastlingSide::Queen)
+    }
+
+    /// Returns the number of halfmoves since the last capture or pawn move.
+    #[inline(always)]
+    pub fn halfmove_clock(&self) -> usize {
+        self.state_stack[self.state_idx].halfmove
+    }
+
+    /// Returns true if the fifty-move rule applies: 100 halfmoves without a capture or pawn move.
+    ///
+    /// Does not look at checkmate, which takes precedence when it happens on the 100th halfmove.
+    #[inline(always)]
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock() >= 100
+    }
+
+    /// Returns true if a player may claim a draw: threefold repetition or fifty-move rule.
+    ///
+    /// The game goes on unless the draw is claimed, see `is_forced_draw` for the draws the arbiter applies by itself.
+    pub fn can_claim_draw(&self) -> bool {
+        (self.is_fifty_move_draw() && !self.is_checkmate()) || self.repetition_count() >= 3
+    }
+
+    /// Returns true if the game is drawn without any claim: fivefold repetition, seventy-five-move rule, dead
+    /// position by insufficient material, or stalemate.
+    pub fn is_forced_draw(&self) -> bool {
+        self.repetition_count() >= 5 || (self.halfmove_clock() >= 150 && !self.is_checkmate()) || self.is_insufficient_material() || self.is_stalemate()
+    }
+
+    /// Returns the fullmove number, as in FEN.
+    #[inline(always)]
+    pub fn fullmove_number(&self) -> usize {
+        self.state_stack[self.state_idx].fullmove
+    }
+
+    /// Sets board to the starting position.
+    /// # Panics
+    /// Panics if the internal FEN parser fails.
+    pub fn set_startpos(&mut self) {
+        self.from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    }
+
+    /// Sets board state from a FEN string, see `set_fen`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_fen(&mut self, fen: &str) -> Result<(), &'static str> {
+        self.set_fen(fen)
+    }
+
+    /// Loads a FEN into this board, clearing it in place.
+    ///
+    /// Only the position is rewritten, the board keeps pointing to the same attack tables, so suite runners can create
+    /// one board and call `set_fen` on it for every position. The result does not depend on what was on the board
+    /// before.
+    ///
+    /// Both 6-field FENs and 4-field EPD positions are accepted, and whatever follows the fields is ignored, such as EPD
+    /// operations or move text.
+    pub fn set_fen(&mut self, fen: &str) -> Result<(), &'static str> {
+        let mut parts = fen.split_whitespace();
+        let board_part = parts.next().ok_or("FEN missing board part")?;
+        let side_part = parts.next().ok_or("FEN missing side to move")?;
+        let castling_part = parts.next().unwrap_or("-");
+        let en_passant_part = parts.next().unwrap_or("-");
+        // Move counters may be missing from truncated FENs and EPD lines. A token starting with a digit is a counter and
+        // must be numeric, and so is the halfmove slot when a fullmove number follows it. Anything else is trailing text
+        let is_counter = |part: &&str| part.starts_with(|c: char| c.is_ascii_digit());
+        let (halfmove_part, fullmove_part) = (parts.next(), parts.next());
+        let fullmove_follows = fullmove_part.is_some_and(|part| part.bytes().all(|b| b.is_ascii_digit()));
+        let halfmove_part = halfmove_part.filter(|part| is_counter(part) || fullmove_follows);
+        let fullmove_part = fullmove_part.filter(|part| halfmove_part.is_some() && is_counter(part));
+        let halfmove = halfmove_part.map_or(Ok(0), |part| part.parse().map_err(|_| "Invalid halfmove clock"))?;
+        let fullmove = fullmove_part.map_or(Ok(1), |part| part.parse().map_err(|_| "Invalid fullmove number"))?;
+
+        self.clear_pieces();
+
+        // ===== Parse board squares =====
+        for (rank_idx, rank) in board_part.split('/').enumerate() {
+            let rank_num = 7 - rank_idx; //FEN top rank = 7
+            let mut file = 0;
+
+            for ch in rank.chars() {
+                if ch.is_ascii_digit() {
+                    let skip = ch.to_digit(10).unwrap();
+                    file += skip;
+                } else {
+                    let sq = rank_num * 8 + file as usize;
+                    self.put_piece(Square::new(sq as u8), Piece::from_char(ch));
+                    file += 1;
+                }
+            }
+            if file != 8 {
+                return Err("Invalid FEN rank length");
+            }
+        }
+
+        // ===== Parse side to move =====
+        self.side_to_move = match side_part {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err("Invalid side to move"),
+        };
+
+        // ===== Parse castling rights =====
+        let mut castling = CastlingRights::NONE;
+        for ch in castling_part.chars() {
+            match ch {
+                'K' => castling.set(Color::White, CastlingSide::King),
+                'Q' => castling.set(Color::White, CastlingSide::Queen),
+                'k' => castling.set(Color::Black, CastlingSide::King),
+                'q' => castling.set(Color::Black, CastlingSide::Queen),
+                '-' => {}
+                _ => return Err("Invalid castling"),
+            }
+        }
+
+        // ===== Parse en passant square =====
+        let en_passant = if en_passant_part == "-" {
+            None
+        } else {
+            let bytes = en_passant_part.as_bytes();
+            let file = bytes[0].wrapping_sub(b'a');
+            let rank = bytes[1].wrapping_sub(b'1');
+            if file > 7 || rank > 7 {
+                return Err("Invalid en passant square");
+            }
+            Some(Square::new(rank * 8 + file))
+        };
+
+        // ===== Set initial state =====
+        self.set_root_state(castling, en_passant, halfmove, fullmove);
+
+        Ok(())
+    }
+
+    /// Sets board state from a FEN string, rejecting positions that cannot arise in a game. See `validate`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_fen_validated(&mut self, fen: &str) -> Result<(), &'static str> {
+        self.from_fen(fen)?;
+        self.validate()
+    }
+
+    /// Checks that the position is legal: exactly one king per color, at most 8 pawns per color, no pawns on the first
+    /// or last rank, and the side that just moved is not in check.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for color in [Color::White, Color::Black] {
+            if self.piece_count(PieceType::King, color) != 1 {
+                return Err("Each side must have exactly one king");
+            }
+            if self.piece_count(PieceType::Pawn, color) > 8 {
+                return Err("A side cannot have more than 8 pawns");
+            }
+        }
+        if self.pieces[PieceType::Pawn] & (Bitboard::rank_1() | Bitboard::rank_8()) != Bitboard(0) {
+            return Err("Pawns cannot stand on the first or last rank");
+        }
+        if self.king_in_check(!self.side_to_move) {
+            return Err("The side not to move is in check");
+        }
+        Ok(())
+    }
+
+    /// Returns the color-flipped position: colors are swapped and ranks mirrored, along with side to move, castling
+    /// rights and en-passant square. Any symmetric evaluation must score it as the negation of this position.
+    pub fn mirror(&self) -> Board {
+        let mut board = Board::with_tables(self.attack_tables);
+        for sq in Square::ALL {
+            if let Some(piece) = self.mailbox[sq] {
+                board.put_piece(sq.flipped(), Piece::new(!piece.get_color(), piece.get_type()));
+            }
+        }
+        board.side_to_move = !self.side_to_move;
+
+        let state = &self.state_stack[self.state_idx];
+        board.set_root_state(state.castling.flipped(), state.en_passant.map(Square::flipped), state.halfmove, state.fullmove);
+        board
+    }
+
+    /// Places `piece` on the empty square `sq`, updating bitboards and material.
+    ///
+    /// Meant for position setup: the Zobrist key is not updated, it is recomputed once the position is complete.
+    pub fn put_piece(&mut self, sq: Square, piece: Piece) {
+        debug_assert!(self.mailbox[sq].is_none());
+        self.mailbox[sq] = Some(piece);
+
+        let (color, ptype) = (piece.get_color(), piece.get_type());
+        self.pieces[ptype] |= sq.bb();
+        self.colors[color] |= sq.bb();
+        if ptype == PieceType::King {
+            self.king_sq[color] = sq;
+        }
+
+        self.apply_material_delta(color, ptype, 1);
+        self.apply_psqt_delta(piece, sq, 1);
+    }
+
+    /// Makes the current piece placement the root of the game, with the given state, and hashes it.
+    fn set_root_state(&mut self, castling: CastlingRights, en_passant: Option<Square>, halfmove: usize, fullmove: usize) {
+        self.state_stack.truncate(1);
+        self.key_history.truncate(1);
+        self.state_stack[0] = State {
+            castling,
+            en_passant,
+            halfmove,
+            fullmove,
+            captured: Option::None,
+        };
+        self.state_idx = 0;
+        self.key_history[0] = self.compute_zobrist_key();
+    }
+
+    /// Empties the board and the incremental eval terms, before placing the pieces of a new position.
+    fn clear_pieces(&mut self) {
+        self.mailbox.fill(Option::None);
+        self.king_sq = [Square::E1, Square::E8];
+        self.pieces.fill(Bitboard(0));
+        self.colors = [Bitboard(0); 2];
+        self.state_stack.truncate(1);
+        self.key_history.truncate(1);
+        self.state_idx = 0;
+        self.eval = 0;
+        self.psqt_mg = 0;
+        self.psqt_eg = 0;
+    }
+
+    /// Encodes the position in `POSITION_BYTES` bytes, about half the size of a typical FEN.
+    ///
+    /// Layout: occupancy as a little-endian u64, then one nibble per occupied square in square order holding the
+    /// `Piece` code (two per byte, low nibble first, room for 32 pieces), a byte with the side to move in bit 0 and the
+    /// castling rights above, the en-passant square or 0xFF, and the halfmove clock and fullmove number as
+    /// little-endian u16. Only the position is encoded, not the move history.
+    ///
+    /// Fails on positions with more than 32 pieces, which `from_fen` accepts but the format has no room for.
+    pub fn to_bytes(&self) -> Result<[u8; POSITION_BYTES], &'static str> {
+        let mut bytes = [0u8; POSITION_BYTES];
+        let occupancy = self.occupied_squares();
+        if occupancy.popcnt() > 32 {
+            return Err("Too many pieces for the binary position format");
+        }
+        bytes[0..8].copy_from_slice(&occupancy.0.to_le_bytes());
+        for (i, sq) in occupancy.squares().enumerate() {
+            bytes[8 + i / 2] |= (self.piece_on_unchecked(sq) as u8) << (4 * (i % 2));
+        }
+
+        bytes[24] = self.side_to_move as u8 | (self.castling_rights().bits() << 1);
+        bytes[25] = self.en_passant_square().map_or(0xFF, |sq| sq as u8);
+        bytes[26..28].copy_from_slice(&(self.halfmove_clock().min(u16::MAX as usize) as u16).to_le_bytes());
+        bytes[28..30].copy_from_slice(&(self.fullmove_number().min(u16::MAX as usize) as u16).to_le_bytes());
+        Ok(bytes)
+    }
+
+    /// Sets board state from the binary format of `to_bytes`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_bytes(&mut self, bytes: &[u8; POSITION_BYTES]) -> Result<(), &'static str> {
+        let occupancy = Bitboard(u64::from_le_bytes(bytes[0..8].try_into().unwrap()));
+        if occupancy.popcnt() > 32 {
+            return Err("Too many pieces in binary position");
+        }
+
+        self.clear_pieces();
+        for (i, sq) in occupancy.squares().enumerate() {
+            let code = (bytes[8 + i / 2] >> (4 * (i % 2))) & 0xF;
+            if code >= 12 {
+                return Err("Invalid piece code in binary position");
+            }
+            let color = if code < 6 { Color::White } else { Color::Black };
+            self.put_piece(sq, Piece::new(color, PieceType::new(code % 6)));
+        }
+
+        self.side_to_move = if bytes[24] & 1 == 0 { Color::White } else { Color::Black };
+        let en_passant = match bytes[25] {
+            0xFF => None,
+            sq if sq < 64 => Some(Square::new(sq)),
+            _ => return Err("Invalid en passant square in binary position"),
+        };
+        let halfmove = u16::from_le_bytes([bytes[26], bytes[27]]) as usize;
+        let fullmove = u16::from_le_bytes([bytes[28], bytes[29]]) as usize;
+        self.set_root_state(CastlingRights::from_bits(bytes[24] >> 1), en_passant, halfmove, fullmove);
+        Ok(())
+    }
+
+    /// Returns the FEN string of the position.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.mailbox[rank * 8 + file] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push(char::from(b'0' + empty));
+                            empty = 0;
+                        }
+                        fen.push(piece.to_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push(char::from(b'0' + empty));
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        let state = &self.state_stack[self.state_idx];
+        let side = if self.side_to_move == Color::White { 'w' } else { 'b' };
+        let en_passant = state.en_passant.map_or("-".to_string(), |sq| sq.to_string());
+        format!("{} {} {} {} {} {}", fen, side, state.castling, en_passant, state.halfmove, state.fullmove)
+    }
+
+    /// Prints the board to console terminal for debug.
+    pub fn print(&self) {
+        println!("Side to move: {:?}", self.side_to_move);
+        println!("  +------------------------+");
+
+        for rank in (0..8).rev() {
+            // 8 = ranks, print rank 8..1
+            print!("{} |", rank + 1);
+            for file in 0..8 {
+                let sq = rank * 8 + file;
+                let ch = self.mailbox[sq].map_or('.', |p| p.to_char());
+                print!(" {} ", ch);
+            }
+            println!("|");
+        }
+
+        println!("  +------------------------+");
+        println!("    a  b  c  d  e  f  g  h");
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::with_tables(attack_tables())
+    }
+}
+
+/// Boards are equal when their positions are, see `position_eq`.
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.position_eq(other)
+    }
+}
+
+impl Eq for Board {}
+
+/// Hashes the Zobrist key, so that equal positions hash equally whatever the move order that reached them.
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.zobrist_key().hash(state);
+    }
+}
+
+/// Fluent construction of a position, as an alternative to writing a FEN by hand.
+///
+/// Starts from an empty board with white to move and no castling rights. `build` rejects illegal positions, see
+/// `Board::validate`.
+pub struct PositionBuilder {
+    pieces: Vec<(Square, Piece)>,
+    side: Color,
+    castling: CastlingRights,
+    en_passant: Option<Square>,
+}
+
+impl PositionBuilder {
+    pub fn new() -> Self {
+        Self {
+            pieces: Vec::new(),
+            side: Color::White,
+            castling: CastlingRights::NONE,
+            en_passant: None,
+        }
+    }
+
+    /// Places `piece` on `sq`, replacing whatever was placed there before.
+    pub fn piece(mut self, sq: Square, piece: Piece) -> Self {
+        self.pieces.retain(|&(s, _)| s != sq);
+        self.pieces.push((sq, piece));
+        self
+    }
+
+    pub fn side(mut self, color: Color) -> Self {
+        self.side = color;
+        self
+    }
+
+    pub fn castling(mut self, rights: CastlingRights) -> Self {
+        self.castling = rights;
+        self
+    }
+
+    pub fn en_passant(mut self, sq: Square) -> Self {
+        self.en_passant = Some(sq);
+        self
+    }
+
+    /// Validates the position and builds the board.
+    pub fn build(self) -> Result<Board, &'static str> {
+        let mut board = Board::new();
+        for (sq, piece) in self.pieces {
+            board.put_piece(sq, piece);
+        }
+        board.side_to_move = self.side;
+        board.set_root_state(self.castling, self.en_passant, 0, 1);
+        board.validate()?;
+        Ok(board)
+    }
+}
+
+impl Default for PositionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::MoveKind;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn has_legal_move() {
+        let mut board = Board::new();
+        board.from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert!(!board.has_legal_move() && board.is_checkmate());
+        board.from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(!board.has_legal_move() && board.is_stalemate());
+        board.set_startpos();
+        assert!(board.has_legal_move());
+    }
+
+    /// Times `has_legal_move` against counting the legal moves, run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn has_legal_move_speed() {
+        const RUNS: usize = 1_000_000;
+        let mut board = Board::new();
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+
+        let start = std::time::Instant::now();
+        let found = (0..RUNS).filter(|_| std::hint::black_box(&board).has_legal_move()).count();
+        let fast = start.elapsed();
+        let start = std::time::Instant::now();
+        let counted = (0..RUNS).filter(|_| crate::perft::perft(std::hint::black_box(&mut board), 1) > 0).count();
+        let full = start.elapsed();
+
+        println!("has_legal_move: {fast:?}, counting legal moves: {full:?}");
+        assert_eq!(found, counted);
+        assert!(fast < full);
+    }
+
+    #[test]
+    fn queen_attacks_are_rook_and_bishop_attacks() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/2p5/8/4Q1P1/8/8/4K3 w - - 0 1").unwrap();
+        let queen = board.attacks_from(PieceType::Queen, Square::E4, Color::White);
+        let rook = board.attacks_from(PieceType::Rook, Square::E4, Color::White);
+        let bishop = board.attacks_from(PieceType::Bishop, Square::E4, Color::White);
+        assert_eq!(queen, rook | bishop);
+
+        // Blocked by the c6 pawn and the g4 pawn, both attacked
+        assert_eq!(queen & (Square::C6.bb() | Square::B7.bb()), Square::C6.bb());
+        assert_eq!(queen & (Square::G4.bb() | Square::H4.bb()), Square::G4.bb());
+        assert_eq!(board.attacks_from(PieceType::Pawn, Square::G4, Color::White), Square::F5.bb() | Square::H5.bb());
+    }
+
+    #[test]
+    fn history_keys_show_repetitions() {
+        let mut board = Board::new();
+        board.set_startpos();
+        let start = board.zobrist_key();
+        assert!(!board.is_repetition());
+
+        for (from, to) in [(Square::G1, Square::F3), (Square::G8, Square::F6), (Square::F3, Square::G1), (Square::F6, Square::G8)] {
+            board.make_move(Move::new_normal(from, to));
+        }
+        assert!(board.is_repetition());
+        assert_eq!(board.history_keys().len(), 5);
+        assert_eq!(board.history_keys().iter().filter(|&&key| key == start).count(), 2);
+    }
+
+    #[test]
+    fn is_legal_rejects_illegal_and_malformed_moves() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1").unwrap();
+        assert!(board.is_legal(Move::new_normal(Square::E1, Square::F1)));
+        assert!(board.is_legal(Move::new_special(Square::E1, Square::D2, MoveKind::Capture)));
+        assert!(!board.is_legal(Move::new_normal(Square::E1, Square::E2)), "Moving into check");
+        assert!(!board.is_legal(Move::new_normal(Square::E1, Square::D2)), "Capture without its kind flag");
+    }
+
+    #[test]
+    fn san_formatting() {
+        #[rustfmt::skip]
+        const CASES: [(&str, &str, &str); 9] = [
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1", "O-O-O"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "a1a8", "Rxa8+"),
+            ("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1d2", "Nbd2"), // Both knights reach d2, the files differ
+            ("4k3/8/8/N7/8/8/8/N3K3 w - - 0 1", "a1b3", "N1b3"),  // Both knights reach b3 from the a file
+            ("4k3/8/8/N7/8/8/8/N3K3 w - - 0 1", "a1c2", "Nc2"),
+            ("3r1k2/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7d8n", "exd8=N"),
+            ("3r1k2/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q", "e8=Q+"),
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", "exd6"),
+            ("6k1/5ppp/8/8/8/8/8/3R2K1 w - - 0 1", "d1d8", "Rd8#"),
+        ];
+        let mut board = Board::new();
+        for (fen, uci, san) in CASES {
+            board.from_fen(fen).unwrap();
+            let m = board.parse_uci_move(uci).unwrap();
+            assert_eq!(board.to_san(m), san, "{fen}");
+            assert_eq!(board.parse_san(san), Ok(m), "{fen}");
+        }
+    }
+
+    #[test]
+    fn san_parsing_ignores_suffixes_and_variants() {
+        let mut board = Board::new();
+        board.from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.parse_san("0-0-0"), board.parse_uci_move("e1c1"));
+        assert_eq!(board.parse_san("Rxa8+!?"), board.parse_uci_move("a1a8"));
+        board.from_fen("3r1k2/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.parse_san("e8Q+"), board.parse_uci_move("e7e8q"));
+
+        board.from_fen("4k3/8/8/N7/8/8/8/N3K3 w - - 0 1").unwrap();
+        assert!(board.parse_san("Nb3").is_err(), "Ambiguous");
+        assert!(board.parse_san("Nb4").is_err(), "Illegal");
+        assert!(board.parse_san("+").is_err(), "Empty");
+    }
+
+    #[test]
+    fn fen_move_counters() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/8/8/8/8/8/4K3 b - - 7 42").unwrap();
+        assert_eq!((board.halfmove_clock(), board.fullmove_number()), (7, 42));
+
+        // Truncated FENs default the counters, present but malformed counters are rejected
+        board.from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+        assert_eq!((board.halfmove_clock(), board.fullmove_number()), (0, 1));
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(board.from_fen("4k3/8/8/8/8/8/8/4K3 w - - xx 1"), Err("Invalid halfmove clock"));
+        assert_eq!(board.from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1x"), Err("Invalid fullmove number"));
+    }
+
+    #[test]
+    fn position_builder() {
+        let board = PositionBuilder::new()
+            .piece(Square::E1, Piece::from_char('K'))
+            .piece(Square::D1, Piece::from_char('Q'))
+            .piece(Square::E8, Piece::from_char('k'))
+            .side(Color::Black)
+            .build()
+            .unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/3QK3 b - - 0 1");
+
+        let two_kings = PositionBuilder::new()
+            .piece(Square::E1, Piece::from_char('K'))
+            .piece(Square::D1, Piece::from_char('K'))
+            .piece(Square::E8, Piece::from_char('k'))
+            .build();
+        assert_eq!(two_kings.err(), Some("Each side must have exactly one king"));
+    }
+
+    #[test]
+    fn validate_rejects_illegal_positions() {
+        #[rustfmt::skip]
+        const CASES: [(&str, &str); 6] = [
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", "Each side must have exactly one king"),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", "Each side must have exactly one king"),
+            ("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1", "A side cannot have more than 8 pawns"),
+            ("P3k3/8/8/8/8/8/8/4K3 w - - 0 1", "Pawns cannot stand on the first or last rank"),
+            ("4k3/8/8/8/8/8/8/p3K3 w - - 0 1", "Pawns cannot stand on the first or last rank"),
+            ("4k3/8/8/8/8/8/8/4K2r b - - 0 1", "The side not to move is in check"),
+        ];
+        let mut board = Board::new();
+        for (fen, error) in CASES {
+            assert_eq!(board.from_fen_validated(fen), Err(error), "{fen}");
+        }
+        assert_eq!(board.from_fen_validated("4k3/8/8/8/8/8/8/4K2r w - - 0 1"), Ok(()));
+    }
+
+    #[test]
+    fn mirror_is_an_involution_negating_the_eval() {
+        const FENS: [&str; 3] = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 3 12",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ];
+        let mut board = Board::new();
+        for fen in FENS {
+            board.from_fen(fen).unwrap();
+            let mirrored = board.mirror();
+            assert_ne!(mirrored.to_fen(), fen);
+            assert_eq!(mirrored.mirror().to_fen(), fen);
+            assert_eq!(eval_position(&mirrored), -eval_position(&board), "{fen}");
+        }
+
+        board.from_fen(FENS[2]).unwrap();
+        assert_eq!(board.mirror().to_fen(), "rnbqkbnr/pppp1ppp/8/8/3PpP2/8/PPP1P1PP/RNBQKBNR b KQkq f3 0 3");
+
+        // The mirrored board reads the same tables as its source, not the global ones
+        let tables: &'static AttackTables = Box::leak(Box::new(AttackTables::new()));
+        let mut board = Board::with_tables(tables);
+        board.set_startpos();
+        assert!(std::ptr::eq(board.mirror().attack_tables, tables));
+    }
+
+    #[test]
+    fn halfmove_clock_counts_reversible_moves() {
+        let mut board = Board::new();
+        board.from_fen("r3k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        for _ in 0..25 {
+            board.apply_uci_moves(&["e1f1", "e8f8", "f1e1", "f8e8"]).unwrap();
+        }
+        assert_eq!(board.halfmove_clock(), 100);
+        assert!(board.is_fifty_move_draw());
+
+        let capture = board.parse_uci_move("a1a8").unwrap();
+        board.make_move(capture);
+        assert_eq!(board.halfmove_clock(), 0);
+        board.unmake_move(capture);
+        assert_eq!(board.halfmove_clock(), 100);
+    }
+
+    #[test]
+    fn pieces_of_and_king_square() {
+        let mut board = Board::new();
+        board.set_startpos();
+        assert_eq!(board.king_square(Color::White), Square::E1);
+        assert_eq!(board.king_square(Color::Black), Square::E8);
+        assert_eq!(board.pieces_of(Color::White, PieceType::Knight), Square::B1.bb() | Square::G1.bb());
+        assert_eq!(board.pieces_of(Color::Black, PieceType::Pawn), Bitboard(0x00FF_0000_0000_0000));
+    }
+
+    #[test]
+    fn legal_targets_of_a_pinned_bishop() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/8/b7/8/8/3B4/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.legal_targets(Square::D2), Square::C3.bb() | Square::B4.bb() | Square::A5.bb());
+        assert_eq!(board.legal_targets(Square::E1), Bitboard::from_squares(&[Square::D1, Square::F1, Square::E2, Square::F2]));
+        assert_eq!(board.legal_targets(Square::E4), Bitboard(0), "Empty square");
+        assert_eq!(board.legal_targets(Square::A5), Bitboard(0), "Piece of the side not to move");
+    }
+
+    #[test]
+    fn blocked_pawn_wall_is_likely_dead() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/8/1p1p1p1p/pPpPpPpP/P1P1P1P1/8/4K3 w - - 0 1").unwrap();
+        assert!(board.is_likely_dead_draw());
+        assert!(!board.is_insufficient_material());
+
+        board.from_fen("4k3/8/8/1p1p1p1p/pPpPpPpP/P1P1P1P1/8/4KN2 w - - 0 1").unwrap();
+        assert!(!board.is_likely_dead_draw(), "A knight can still make progress");
+        board.from_fen("4k3/8/8/1pKp1p1p/pPpPpPpP/P1P1P1P1/8/8 w - - 0 1").unwrap();
+        assert!(!board.is_likely_dead_draw(), "The white king can take the b5 pawn");
+    }
+
+    #[test]
+    fn incremental_psqt_matches_recomputation() {
+        const FENS: [&str; 2] =
+            ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1"];
+        let mut rng = SmallRng::seed_from_u64(926);
+        let (mut castles, mut promotions) = (0, 0);
+        let mut board = Board::new();
+        for fen in FENS {
+            for _ in 0..50 {
+                board.from_fen(fen).unwrap();
+                let mut played = Vec::new();
+                for _ in 0..40 {
+                    let mut moves = MoveList::new();
+                    generate_legal_moves(&board, &mut moves);
+                    if moves.count() == 0 {
+                        break;
+                    }
+                    let m = moves.get(rng.random_range(0..moves.count()));
+                    castles += m.is_castling() as usize;
+                    promotions += m.is_promotion() as usize;
+                    board.make_move(m);
+                    played.push(m);
+                    assert_eq!((board.psqt_mg(), board.psqt_eg()), board.compute_psqt(), "{}", board.to_fen());
+                }
+                for m in played.into_iter().rev() {
+                    board.unmake_move(m);
+                    assert_eq!((board.psqt_mg(), board.psqt_eg()), board.compute_psqt(), "{}", board.to_fen());
+                }
+            }
+        }
+        assert!(castles > 0 && promotions > 0);
+    }
+
+    /// Checks that the tracked king squares follow castling and king moves, and are restored by unmake.
+    ///
+    /// Plays a fixed line from a castling position and compares `king_square` with the king bitboards after each move.
+    #[test]
+    fn king_squares_follow_the_moves() {
+        const LINE: [&str; 6] = ["e1g1", "e8c8", "g1h1", "c8b8", "h1g1", "b8a8"];
+        let mut board = Board::new();
+        board.from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1").unwrap();
+
+        let assert_tracked = |board: &Board| {
+            for color in [Color::White, Color::Black] {
+                assert_eq!(board.king_square(color), board.pieces_of(color, PieceType::King).square(), "{}", board.to_fen());
+            }
+        };
+        let mut played = Vec::new();
+        for uci in LINE {
+            let m = board.parse_uci_move(uci).unwrap();
+            board.make_move(m);
+            played.push(m);
+            assert_tracked(&board);
+        }
+        while let Some(m) = played.pop() {
+            board.unmake_move(m);
+            assert_tracked(&board);
+        }
+    }
+
+    /// Checks `attack_count_on` on a king zone hit by a queen and a knight, the rook and bishop being out of play.
+    #[test]
+    fn attack_count_on_the_king_zone() {
+        let mut board = Board::new();
+        board.from_fen("6k1/5ppp/8/6NQ/8/8/PP6/R1B1K3 w - - 0 1").unwrap();
+        let king_sq = board.king_square(Color::Black);
+        let zone = board.attack_tables.king[king_sq] | king_sq.bb();
+
+        let expected = (2, KING_ATTACK_WEIGHTS[PieceType::Queen as usize] + KING_ATTACK_WEIGHTS[PieceType::Knight as usize]);
+        assert_eq!(board.attack_count_on(zone, Color::White), expected);
+    }
+
+    /// Checks `apply_uci_moves` on the Scholar's mate, and that it reports the index of an illegal move.
+    #[test]
+    fn apply_uci_moves_plays_and_reports() {
+        const SCHOLARS_MATE: [&str; 7] = ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"];
+        let mut board = Board::new();
+        board.set_startpos();
+        board.apply_uci_moves(&SCHOLARS_MATE).unwrap();
+        assert_eq!(board.to_fen(), "r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4");
+        assert!(board.is_checkmate());
+
+        board.set_startpos();
+        assert_eq!(board.apply_uci_moves(&["e2e4", "e7e5", "e4e5"]), Err((2, "Illegal UCI move")));
+        board.set_startpos();
+        assert_eq!(board.apply_uci_moves(&["e2e4", "e7e9"]), Err((1, "Unparseable UCI move")));
+    }
+
+    /// Checks the claimable and forced draw rules: threefold repetition and the fifty-move rule can only be claimed,
+    /// fivefold repetition and the seventy-five-move rule end the game.
+    #[test]
+    fn draw_rules_claimable_and_forced() {
+        const SHUFFLE: [&str; 4] = ["g1f3", "g8f6", "f3g1", "f6g8"]; // Back to the starting position
+        let mut board = Board::new();
+        board.set_startpos();
+        for _ in 0..2 {
+            board.apply_uci_moves(&SHUFFLE).unwrap();
+        }
+        assert_eq!(board.repetition_count(), 3);
+        assert!(board.can_claim_draw() && !board.is_forced_draw());
+        for _ in 0..2 {
+            board.apply_uci_moves(&SHUFFLE).unwrap();
+        }
+        assert_eq!(board.repetition_count(), 5);
+        assert!(board.is_forced_draw());
+
+        board.from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 100 80").unwrap();
+        assert!(board.can_claim_draw() && !board.is_forced_draw());
+        board.from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 150 105").unwrap();
+        assert!(board.is_forced_draw());
+    }
+
+    /// Lines checked by `repetition_window_matches_full_scan`, with captures and pawn moves between knight shuffles.
+    const REPETITION_LINES: [&[&str]; 3] = [
+        &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8", "g1f3"],
+        &["g1f3", "g8f6", "f3g1", "f6g8", "e2e4", "e7e5", "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6"],
+        &["e2e4", "d7d5", "e4d5", "d8d5", "b1c3", "d5d8", "c3b1", "d8d5", "b1c3", "d5d8", "c3b1", "d8d5", "b1c3"],
+    ];
+
+    /// Checks that the halfmove-bounded repetition scan agrees with a naive scan of the whole history at every ply.
+    #[test]
+    fn repetition_window_matches_full_scan() {
+        let mut board = Board::new();
+        for line in REPETITION_LINES {
+            board.set_startpos();
+            for uci in line {
+                board.apply_uci_moves(&[uci]).unwrap();
+
+                let keys = board.history_keys();
+                let (key, earlier) = keys.split_last().unwrap();
+                let naive = earlier.iter().rev().skip(1).step_by(2).filter(|&k| k == key).count();
+                assert_eq!(board.is_repetition(), naive > 0, "{line:?}");
+                assert_eq!(board.repetition_count(), naive + 1, "{line:?}");
+            }
+        }
+    }
+
+    /// Checks the per-side castling queries on partial rights: only white kingside and black queenside are left.
+    #[test]
+    fn castling_queries_on_partial_rights() {
+        let mut board = Board::new();
+        board.from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+        let sides = [
+            board.can_castle_kingside(Color::White),
+            board.can_castle_queenside(Color::White),
+            board.can_castle_kingside(Color::Black),
+            board.can_castle_queenside(Color::Black),
+        ];
+        assert_eq!(sides, [true, false, false, true]);
+    }
+
+    /// Checks the piece counts of the starting position.
+    #[test]
+    fn startpos_piece_counts() {
+        let mut board = Board::new();
+        board.set_startpos();
+        assert_eq!(board.piece_count(PieceType::Pawn, Color::White), 8);
+        assert_eq!(board.piece_count(PieceType::Rook, Color::Black), 2);
+        assert_eq!(board.total_piece_count(Color::White), 16);
+        assert_eq!(board.total_piece_count(Color::Black), 16);
+    }
+
+    /// Checks that unmaking a promotion capture, exd8=Q taking a rook, restores the pawn on e7, the rook on d8 and every
+    /// bitboard, table and key bit-identically.
+    #[test]
+    fn promotion_capture_unmakes_exactly() {
+        let mut board = Board::new();
+        board.from_fen("3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let snapshot = |b: &Board| (b.mailbox, b.pieces, b.colors, b.side_to_move, b.king_sq, b.eval, b.psqt_mg, b.psqt_eg, b.zobrist_key(), b.state_idx);
+        let before = snapshot(&board);
+
+        let m = board.parse_uci_move("e7d8q").unwrap();
+        assert!(m.is_capture() && m.is_promotion());
+        board.make_move(m);
+        assert_eq!(board.pieces_of(Color::White, PieceType::Queen), Square::D8.bb());
+        assert_eq!(board.piece(PieceType::Rook), Bitboard(0));
+        board.unmake_move(m);
+
+        assert_eq!(board.piece_on(Square::E7), Some(Piece::new(Color::White, PieceType::Pawn)));
+        assert_eq!(board.piece_on(Square::D8), Some(Piece::new(Color::Black, PieceType::Rook)));
+        assert_eq!(snapshot(&board), before);
+    }
+
+    /// Checks that the validated loader rejects a position where the side that just moved left its own king in check,
+    /// while the plain loader still accepts it.
+    #[test]
+    fn validated_loader_rejects_moved_side_in_check() {
+        const FEN: &str = "4k3/8/8/8/8/8/4r3/4K3 b - - 0 1"; // White to have moved, its king on e1 is attacked by the rook
+        let mut board = Board::new();
+        assert_eq!(board.from_fen_validated(FEN), Err("The side not to move is in check"));
+        assert_eq!(board.from_fen(FEN), Ok(()));
+        assert_eq!(board.from_fen_validated("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1"), Ok(()));
+    }
+
+    /// Checks that the binary format round-trips positions exactly, compared by FEN, including castling rights, en
+    /// passant and move counters.
+    #[test]
+    fn binary_format_round_trips() {
+        const FENS: [&str; 5] = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 37 112",
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        ];
+        let (mut board, mut decoded) = (Board::new(), Board::new());
+        for fen in FENS {
+            board.from_fen(fen).unwrap();
+            decoded.from_bytes(&board.to_bytes().unwrap()).unwrap();
+            assert_eq!(decoded.to_fen(), fen);
+            assert_eq!(decoded.zobrist_key(), board.zobrist_key());
+        }
+    }
+
+    /// Checks that a position with 33 pieces, accepted by `from_fen`, is refused by the binary format instead of
+    /// overflowing the piece nibbles.
+    #[test]
+    fn binary_format_refuses_more_than_32_pieces() {
+        let mut board = Board::new();
+        board.from_fen("rnbqkbnr/pppppppp/8/8/4Q3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(board.to_bytes(), Err("Too many pieces for the binary position format"));
+    }
+
+    /// Checks the capture classification: exd5 wins the queen for a pawn, Qxc6 loses the queen to bxc6.
+    #[test]
+    fn captures_classified_by_see() {
+        let mut board = Board::new();
+        board.from_fen("4k3/1p6/2p5/3q4/4P3/8/2Q5/4K3 w - - 0 1").unwrap();
+        let pawn_takes_queen = board.parse_uci_move("e4d5").unwrap();
+        assert_eq!(board.capture_class(pawn_takes_queen), Ordering::Greater);
+        assert_eq!(board.see(pawn_takes_queen), 800);
+        let queen_takes_pawn = board.parse_uci_move("c2c6").unwrap();
+        assert_eq!(board.capture_class(queen_takes_pawn), Ordering::Less);
+        assert_eq!(board.see(queen_takes_pawn), -800);
+        assert_eq!(board.capture_class(board.parse_uci_move("e1f1").unwrap()), Ordering::Equal);
+
+        // X-ray: after Qxd5 Rxd5 the rook behind the queen recaptures, the queen is lost for a pawn and a rook
+        board.from_fen("3rk3/8/8/3p4/8/8/3Q4/3RK3 w - - 0 1").unwrap();
+        assert_eq!(board.see(board.parse_uci_move("d2d5").unwrap()), -300);
+    }
+
+    /// Checks `gives_checkmate` on a back-rank position: Rd8 mates, Rd7 does not check, and Rd8 with an escape square
+    /// for the king is a mere check.
+    #[test]
+    fn gives_checkmate_on_the_back_rank() {
+        let mut board = Board::new();
+        board.from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let key = board.zobrist_key();
+        assert!(board.gives_checkmate(board.parse_uci_move("d1d8").unwrap()));
+        assert!(!board.gives_checkmate(board.parse_uci_move("d1d7").unwrap()));
+        assert_eq!(board.zobrist_key(), key);
+
+        board.from_fen("6k1/5pp1/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        assert!(!board.gives_checkmate(board.parse_uci_move("d1d8").unwrap()));
+    }
+
+    /// Checks that `set_fen` on a board dirtied by moves and another position gives the same state as a fresh board.
+    #[test]
+    fn set_fen_resets_a_dirty_board() {
+        const FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut dirty = Board::new();
+        dirty.set_startpos();
+        dirty.apply_uci_moves(&["e2e4", "d7d5", "e4d5", "d8d5", "e1e2"]).unwrap();
+        dirty.set_fen(FEN).unwrap();
+
+        let mut fresh = Board::new();
+        fresh.from_fen(FEN).unwrap();
+        let snapshot =
+            |b: &Board| (b.mailbox, b.pieces, b.colors, b.side_to_move, b.king_sq, b.eval, b.psqt_mg, b.psqt_eg, b.zobrist_key(), b.state_idx, b.to_fen());
+        assert_eq!(snapshot(&dirty), snapshot(&fresh));
+        assert_eq!(dirty.castling_rights(), fresh.castling_rights());
+        assert_eq!(dirty.en_passant_square(), fresh.en_passant_square());
+    }
+
+    /// Checks two boards built on the same tables: they share the tables, but moves made on one leave the other untouched.
+    #[test]
+    fn boards_share_tables_not_state() {
+        let tables: &'static AttackTables = Box::leak(Box::new(AttackTables::new()));
+        let (mut first, mut second) = (Board::with_tables(tables), Board::with_tables(tables));
+        assert!(std::ptr::eq(first.attack_tables, second.attack_tables));
+        assert!(!std::ptr::eq(first.attack_tables, attack_tables()));
+        first.set_startpos();
+        second.set_startpos();
+        let start = second.to_fen();
+
+        let m = first.parse_uci_move("g1f3").unwrap();
+        first.make_move(m);
+        assert_eq!(second.to_fen(), start);
+        assert_ne!(second.zobrist_key(), first.zobrist_key());
+        second.apply_uci_moves(&["e2e4", "e7e5"]).unwrap();
+        first.unmake_move(m);
+        assert_eq!(first.to_fen(), start);
+        assert_eq!(first.zobrist_key(), first.compute_zobrist_key());
+    }
+
+    /// Checks that `see_ge` agrees with the full `see` on every legal capture of a suite of tactical positions, with
+    /// promotions, en passant, x-rays and king recaptures, at thresholds around 0 and around the piece values.
+    #[test]
+    fn see_ge_agrees_with_see() {
+        const POSITIONS: [&str; 8] = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "4k3/1p6/2p5/3q4/4P3/8/2Q5/4K3 w - - 0 1",
+            "3rk3/8/8/3p4/8/8/3Q4/3RK3 w - - 0 1",
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "2r1r1k1/pp1bppbp/3p1np1/q3P3/2P2P2/1P2B3/P1N1B1PP/2RQ1RK1 b - - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ];
+        let mut board = Board::new();
+        for fen in POSITIONS {
+            board.from_fen(fen).unwrap();
+            let mut moves = MoveList::new();
+            generate_legal_moves(&board, &mut moves);
+            for m in moves.iter().filter(|m| m.is_capture()) {
+                let see = board.see(m);
+                for threshold in [-500, -100, -1, 0, 1, 100, 200, 500, 900] {
+                    assert_eq!(board.see_ge(m, threshold), see >= threshold, "{m} at {threshold} in {fen}");
+                }
+            }
+        }
+    }
+
+    /// Checks that `try_make_move` rejects a move for the wrong side without touching the board.
+    #[test]
+    fn try_make_move_rejects_the_wrong_side() {
+        let mut board = Board::new();
+        board.set_startpos();
+        let fen = board.to_fen();
+        let black_move = Move::new_special(Square::E7, Square::E5, MoveKind::DoublePush);
+        assert_eq!(board.try_make_move(black_move), Err("Move for the side not to move"));
+        assert_eq!(board.to_fen(), fen);
+        assert!(board.try_make_move(Move::new_normal(Square::E3, Square::E4)).is_err());
+        assert!(board.try_make_move(board.parse_uci_move("e2e4").unwrap()).is_ok());
+    }
+
+    /// Checks that `make_move` trips its debug assertion on a move for the wrong side.
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "for the side not to move")]
+    fn make_move_asserts_the_side_to_move() {
+        let mut board = Board::new();
+        board.set_startpos();
+        board.make_move(Move::new_special(Square::E7, Square::E5, MoveKind::DoublePush));
+    }
+
+    /// Checks `legal_moves_san`: the starting position must give 20 distinct moves including `Nf3` and `e4`, and a
+    /// position with two rooks and two knights reaching the same squares must disambiguate them.
+    #[test]
+    fn legal_moves_san_disambiguates() {
+        let mut board = Board::new();
+        board.set_startpos();
+        let sans = board.legal_moves_san();
+        let mut distinct = sans.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!((sans.len(), distinct.len()), (20, 20));
+        assert!(sans.iter().any(|san| san == "Nf3") && sans.iter().any(|san| san == "e4"));
+
+        board.from_fen("4k3/8/8/8/8/1N3N2/R6R/4K3 w - - 0 1").unwrap();
+        let sans = board.legal_moves_san();
+        for expected in ["Rad2", "Rhd2", "Nbd4", "Nfd4", "Rh8+", "Ra8+"] {
+            assert!(sans.iter().any(|san| san == expected), "{expected}");
+        }
+        let mut legal = MoveList::new();
+        generate_legal_moves(&board, &mut legal);
+        assert_eq!(sans.len(), legal.count());
+    }
+
+    /// Checks the castling rights lost by rook moves and captures, through the castling field of `to_fen`: the black a8
+    /// rook taking the white a1 rook removes both queenside rights, the white a1 rook moving away removes only White's, a
+    /// knight taking the h8 rook removes Black's kingside right, and unmaking restores all of them.
+    #[test]
+    fn rook_moves_and_captures_drop_castling_rights() {
+        const CASES: [(&str, &str, &str); 4] = [
+            ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "a8a1", "Kk"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "a1a2", "Kkq"),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "h1h8", "Qq"),
+            ("r3k2r/8/6N1/8/8/8/8/R3K2R w KQkq - 0 1", "g6h8", "KQq"),
+        ];
+        let mut board = Board::new();
+        for (fen, uci, expected) in CASES {
+            board.from_fen(fen).unwrap();
+            let m = board.parse_uci_move(uci).unwrap();
+            board.make_move(m);
+            assert_eq!(board.to_fen().split_whitespace().nth(2), Some(expected), "{uci} in {fen}");
+            board.unmake_move(m);
+            assert_eq!(board.to_fen(), fen);
+        }
+    }
+
+    /// Checks the list-returning generators: 20 legal moves in the starting position, and in a position with a pinned
+    /// knight the pseudo-legal moves are the legal ones plus the knight moves.
+    #[test]
+    fn move_lists_of_legal_and_pseudo_legal_moves() {
+        let mut board = Board::new();
+        board.set_startpos();
+        assert_eq!(board.legal_moves().count(), 20);
+        assert_eq!(board.pseudo_legal_moves().count(), 20);
+
+        board.from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        let (legal, pseudo) = (board.legal_moves(), board.pseudo_legal_moves());
+        let knight_moves = pseudo.iter().filter(|m| m.from() == Square::E2).count();
+        assert!(knight_moves > 0);
+        assert!(!legal.iter().any(|m| m.from() == Square::E2));
+        assert_eq!(pseudo.count(), legal.count() + knight_moves);
+    }
+
+    /// Checks `Hash` and `Eq` on transpositions: 1. e4 e5 2. Nf3 and 1. Nf3 e5 2. e4 must be a single `HashSet` element,
+    /// although only the second one ends with a double push setting an en-passant square, which no pawn can capture.
+    #[test]
+    fn transpositions_hash_equal() {
+        let mut positions = std::collections::HashSet::new();
+        for moves in [["e2e4", "e7e5", "g1f3"], ["g1f3", "e7e5", "e2e4"], ["e2e4", "e7e5", "b1c3"]] {
+            let mut board = Board::new();
+            board.set_startpos();
+            board.apply_uci_moves(&moves).unwrap();
+            positions.insert(board);
+        }
+        assert_eq!(positions.len(), 2);
+    }
+
+    /// Checks the FEN fields around the position: a 4-field EPD position with operations defaults the counters, trailing
+    /// text after a 6-field FEN is ignored, and a counter starting with a digit must still be a number.
+    #[test]
+    fn fen_trailing_tokens() {
+        const FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 3 17";
+        let mut board = Board::new();
+        board.from_fen("  r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - bm Qxf6; id \"kiwipete\";").unwrap();
+        assert_eq!(board.to_fen(), "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        board.from_fen(&format!("\t{}  {{game continues}} 17... Qxf6 18. exf6 *\n", FEN)).unwrap();
+        assert_eq!(board.to_fen(), FEN);
+        assert_eq!(board.from_fen("4k3/8/8/8/8/8/8/4K3 w - - 1x 1"), Err("Invalid halfmove clock"));
+    }
+
+    /// Checks `all_attacks` on the starting position, where White attacks its whole second and third ranks and every
+    /// first rank square but the corners, and Black the mirror image, then that it agrees with `is_square_attacked`
+    /// everywhere on a middlegame position.
+    #[test]
+    fn all_attacks_match_is_square_attacked() {
+        let mut board = Board::new();
+        board.from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let corners = Square::A1.bb() | Square::H1.bb() | Square::A8.bb() | Square::H8.bb();
+        assert_eq!(board.all_attacks(Color::White), Bitboard(0x0000_0000_00FF_FFFF) & !corners);
+        assert_eq!(board.all_attacks(Color::Black), Bitboard(0xFFFF_FF00_0000_0000) & !corners);
+
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for color in [Color::White, Color::Black] {
+            let attacks = board.all_attacks(color);
+            for sq in Square::ALL {
+                assert_eq!(attacks & sq.bb() != Bitboard(0), board.is_square_attacked(sq, color), "{sq}");
+            }
+        }
+    }
+
+    /// Checks the two material draw tests: a lone knight is dead and unwinnable, two knights or a minor piece each are
+    /// unwinnable but not dead, and a bishop pair or a pawn can still win.
+    #[test]
+    fn unwinnable_material() {
+        // (FEN, dead, unwinnable)
+        const CASES: [(&str, bool, bool); 6] = [
+            ("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1", true, true),
+            ("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", false, true),
+            ("1n2k1n1/8/8/8/8/8/8/4K3 w - - 0 1", false, true),
+            ("4k1b1/8/8/8/8/8/8/1N2K3 w - - 0 1", false, true),
+            ("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", false, false),
+            ("4k3/8/8/8/8/8/P7/1N2KN2 w - - 0 1", false, false),
+        ];
+        let mut board = Board::new();
+        for (fen, dead, unwinnable) in CASES {
+            board.from_fen(fen).unwrap();
+            assert_eq!(board.is_insufficient_material(), dead, "{fen}");
+            assert_eq!(board.is_unwinnable_material(), unwinnable, "{fen}");
+        }
+    }
+
+    /// Checks that the history grows with the game: thousands of knight moves can be made and unmade, back to the
+    /// starting key.
+    #[test]
+    fn history_grows_with_long_games() {
+        let mut board = Board::new();
+        board.set_startpos();
+        let start_key = board.zobrist_key();
+        let mut played = Vec::new();
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().cycle().take(3000) {
+            let m = board.parse_uci_move(uci).unwrap();
+            board.make_move(m);
+            played.push(m);
+        }
+        assert_eq!(board.history_keys().len(), 3001);
+        assert_eq!(board.zobrist_key(), start_key);
+        for &m in played.iter().rev() {
+            board.unmake_move(m);
+        }
+        assert_eq!(board.history_keys(), [start_key]);
+    }
+
+    /// Checks SEE on a board crowded with 38 pieces, where every line and knight square around d5 holds a queen or a
+    /// knight: the exchange on d5 runs to 34 captures, and every capture must be evaluated without running out of swap
+    /// slots and agree with `see_ge`.
+    #[test]
+    fn see_on_a_crowded_board() {
+        let mut board = Board::new();
+        board.from_fen("Q2Q2Qk/1qnqnq2/1nQQQn2/qQqrQqQq/1NqqqN2/1QNQNQ2/q2q2q1/K2Q3Q w - - 0 1").unwrap();
+        let captures: Vec<Move> = board.pseudo_legal_moves().iter().filter(|m| m.is_capture()).collect();
+        assert!(!captures.is_empty());
+        for m in captures {
+            let see = board.see(m);
+            assert!(board.see_ge(m, see) && !board.see_ge(m, see + 1), "{m}");
         }
     }
 }
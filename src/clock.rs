@@ -0,0 +1,132 @@
+//! Search limits.
+//!
+//! A `Clock` answers the one question the search asks at every node: must it stop now? It gathers everything that
+//! can end a search: the stop flag set by the thread reading UCI commands, a hard deadline after which the iteration in
+//! progress is abandoned, and a node limit. The soft deadline is only checked between iterations, so that a new
+//! iteration is not started when it is unlikely to complete.
+//!
+//! Reading the time and the atomic flag costs far more than a node of search, so they are only checked every
+//! `CHECK_INTERVAL` nodes. The node limit is a plain comparison and is exact.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Nodes between two reads of the time and of the stop flag.
+pub const CHECK_INTERVAL: u64 = 1024;
+
+pub struct Clock {
+    start: Instant,
+    soft_deadline: Option<Duration>, // No iteration is started past it
+    hard_deadline: Option<Duration>, // The search is interrupted past it
+    node_limit: Option<u64>,
+    stop: Arc<AtomicBool>, // Set by another thread to end the search
+    stopped: bool,         // A limit was hit, latched until `restart`
+}
+
+impl Clock {
+    /// Creates a clock with no limit, started now.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            soft_deadline: None,
+            hard_deadline: None,
+            node_limit: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+        }
+    }
+
+    /// Ends the search when `stop` is set.
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+
+    /// Sets the deadlines, measured from the last `restart`. `None` removes a deadline.
+    pub fn set_deadlines(&mut self, soft: Option<Duration>, hard: Option<Duration>) {
+        self.soft_deadline = soft;
+        self.hard_deadline = hard;
+    }
+
+    /// Stops the search once `nodes` have been searched. `None` removes the limit.
+    pub fn set_node_limit(&mut self, nodes: Option<u64>) {
+        self.node_limit = nodes;
+    }
+
+    /// Starts measuring time from now and clears a previous stop. The stop flag itself is left to its owner.
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+        self.stopped = false;
+    }
+
+    /// Returns the time since the last `restart`.
+    #[inline(always)]
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Returns true if the search must stop, given the nodes searched since the last `restart`.
+    ///
+    /// Once true, it stays true until `restart`, without reading anything again.
+    #[inline(always)]
+    pub fn should_stop(&mut self, nodes: u64) -> bool {
+        if !self.stopped {
+            self.stopped = self.node_limit.is_some_and(|limit| nodes >= limit)
+                || (nodes.is_multiple_of(CHECK_INTERVAL)
+                    && (self.stop.load(Ordering::Relaxed) || self.hard_deadline.is_some_and(|hard| self.elapsed() >= hard)));
+        }
+        self.stopped
+    }
+
+    /// Returns true if `should_stop` returned true since the last `restart`.
+    #[inline(always)]
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Returns true if the soft deadline has passed, or the search was stopped: no new iteration should be started.
+    pub fn soft_expired(&self) -> bool {
+        self.stopped || self.soft_deadline.is_some_and(|soft| self.elapsed() >= soft)
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks each limit of `should_stop`: false before the hard deadline and true past it, on a node count where the time
+    /// is read; the node cap exact to the node; and the stop flag.
+    #[test]
+    fn should_stop_limits() {
+        let mut clock = Clock::new();
+        clock.set_deadlines(Some(Duration::from_millis(10)), Some(Duration::from_millis(30)));
+        clock.restart();
+        assert!(!clock.should_stop(CHECK_INTERVAL));
+        assert!(!clock.soft_expired());
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(clock.soft_expired());
+        assert!(!clock.should_stop(CHECK_INTERVAL + 1)); // Time is only read on the check interval
+        assert!(clock.should_stop(2 * CHECK_INTERVAL));
+        assert!(clock.should_stop(2 * CHECK_INTERVAL + 1));
+
+        let mut clock = Clock::new();
+        clock.set_node_limit(Some(5000));
+        assert!(!clock.should_stop(4999));
+        assert!(clock.should_stop(5000));
+        clock.restart();
+        assert!(!clock.stopped());
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut clock = Clock::new();
+        clock.set_stop_flag(stop.clone());
+        stop.store(true, Ordering::Relaxed);
+        assert!(!clock.should_stop(CHECK_INTERVAL - 1));
+        assert!(clock.should_stop(CHECK_INTERVAL));
+    }
+}
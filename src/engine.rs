@@ -0,0 +1,274 @@
+//! Engine state between searches, as driven by a UCI front end.
+//!
+//! The engine owns the game position and the ponder state. Each `go` runs a fresh `Searcher` on the current position,
+//! so nothing found for one root position (best move, PV, killers) can leak into the search of another.
+//!
+//! Pondering follows the UCI protocol: `go ponder` searches the position after the predicted opponent move. On
+//! `ponderhit` the predicted move was played and the same position is searched for real. On a miss the GUI sends
+//! `stop` and then the actual `position`: the ponder search is discarded and the engine starts over on the new board.
+//!
+//! Analysis (`go infinite`) has no depth limit: iterative deepening goes on until the stop flag is set by the thread
+//! reading commands, or until a forced mate is proven. The best move is only reported once `stop` has arrived.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::eval::eval_position;
+use crate::moves::Move;
+use crate::search::{InfoSink, MAX_DEPTH, NullSink, Score, SearchResult, Searcher, StdoutSink};
+use crate::types::{Color, PieceValues};
+
+/// Sleep between two reads of the stop flag, when an infinite search ended by itself.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+pub struct Engine {
+    board: Board,
+    pondering: Option<Move>, // Predicted opponent move, made on `board` while pondering
+    quiet: bool,             // Discard info lines instead of printing them
+    piece_values: PieceValues,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        let mut board = Board::new();
+        board.set_startpos();
+        Self {
+            board,
+            pondering: None,
+            quiet: false,
+            piece_values: PieceValues::default(),
+        }
+    }
+
+    /// Returns the current position, with the predicted move made while pondering.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns the predicted move being pondered, if any.
+    pub fn pondering(&self) -> Option<Move> {
+        self.pondering
+    }
+
+    /// Discards info lines, for engines driven as a library rather than over UCI.
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    /// Sets the piece values of move ordering and SEE for the next searches.
+    pub fn set_piece_values(&mut self, values: PieceValues) {
+        self.piece_values = values;
+    }
+
+    /// UCI `position`: loads `fen`, or the starting position if `None`, then plays `moves`.
+    ///
+    /// The position is always rebuilt from scratch, so a pending ponder state is dropped along with its predicted
+    /// move.
+    pub fn set_position(&mut self, fen: Option<&str>, moves: &[&str]) -> Result<(), &'static str> {
+        self.pondering = None;
+        match fen {
+            Some(fen) => self.board.from_fen_validated(fen)?,
+            None => self.board.set_startpos(),
+        }
+        self.board.apply_uci_moves(moves).map_err(|(_, e)| e)
+    }
+
+    /// UCI `go`: searches the current position to `depth`.
+    pub fn go(&mut self, depth: usize) -> SearchResult {
+        debug_assert!(self.pondering.is_none(), "Use ponderhit to turn a ponder search into a normal one");
+        self.search(depth, None)
+    }
+
+    /// UCI `go infinite`: searches the current position until `stop` is set.
+    ///
+    /// A search that ends by itself, on a proven mate, still waits for `stop` before returning, since UCI forbids
+    /// sending `bestmove` earlier.
+    pub fn go_infinite(&mut self, stop: Arc<AtomicBool>) -> SearchResult {
+        debug_assert!(self.pondering.is_none(), "Use ponderhit to turn a ponder search into a normal one");
+        let result = self.search(MAX_DEPTH - 1, Some(stop.clone()));
+        while !stop.load(Ordering::Relaxed) {
+            thread::sleep(STOP_POLL_INTERVAL);
+        }
+        result
+    }
+
+    /// Static evaluation of the current position in centipawns, from White's point of view whoever is to move, as
+    /// shown by an evaluation bar.
+    pub fn static_eval_cp(&self) -> i32 {
+        eval_position(&self.board)
+    }
+
+    /// Searches the current position to `depth` and returns the score from White's point of view with the PV.
+    ///
+    /// Search scores are relative to the side to move, so they are negated when Black is to move.
+    pub fn best_continuation(&mut self, depth: usize) -> (Score, Vec<Move>) {
+        let result = self.go(depth);
+        let score = if self.board.side_to_move() == Color::White { result.score } else { Score(-result.score.0) };
+        (score, result.pv)
+    }
+
+    /// UCI `go ponder`: makes the predicted opponent move and searches the resulting position to `depth`, or until
+    /// `stop` is set by the thread reading commands on `ponderhit` or `stop`.
+    pub fn go_ponder(&mut self, predicted: Move, depth: usize, stop: Arc<AtomicBool>) -> SearchResult {
+        self.stop();
+        self.board.make_move(predicted);
+        self.pondering = Some(predicted);
+        self.search(depth, Some(stop))
+    }
+
+    /// UCI `ponderhit`: the predicted move was played, the pondered position becomes the real one.
+    pub fn ponderhit(&mut self, depth: usize) -> SearchResult {
+        self.pondering = None;
+        self.search(depth, None)
+    }
+
+    /// UCI `stop`: ends pondering by taking back the predicted move, so the board is the last real position again.
+    pub fn stop(&mut self) {
+        if let Some(predicted) = self.pondering.take() {
+            self.board.unmake_move(predicted);
+        }
+    }
+
+    /// Runs a fresh searcher on the current position, interrupted by `stop` if given.
+    fn search(&mut self, depth: usize, stop: Option<Arc<AtomicBool>>) -> SearchResult {
+        let sink: Box<dyn InfoSink> = if self.quiet { Box::new(NullSink) } else { Box::new(StdoutSink) };
+        let mut searcher = Searcher::new(&mut self.board);
+        searcher.set_info_sink(sink);
+        searcher.set_piece_values(self.piece_values);
+        if let Some(stop) = stop {
+            searcher.set_stop_flag(stop);
+        }
+        searcher.iterative_deepening(depth)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Checks a ponder miss: after pondering 1. e4 e5, the opponent plays 1... c5 instead. The engine must search the
+    /// actual position, with a best move and PV legal there, and no trace of the predicted move.
+    #[test]
+    fn ponder_miss_searches_the_actual_position() {
+        const DEPTH: usize = 4;
+        let mut engine = Engine::new();
+        engine.set_quiet(true);
+        engine.set_position(None, &["e2e4"]).unwrap();
+
+        let predicted = engine.board().parse_uci_move("e7e5").unwrap();
+        engine.go_ponder(predicted, DEPTH, Arc::new(AtomicBool::new(false)));
+        assert_eq!(engine.pondering(), Some(predicted));
+
+        engine.stop();
+        engine.set_position(None, &["e2e4", "c7c5"]).unwrap();
+        let mut actual = Board::new();
+        actual.set_startpos();
+        actual.apply_uci_moves(&["e2e4", "c7c5"]).unwrap();
+        assert_eq!(engine.pondering(), None);
+        assert_eq!(engine.board().to_fen(), actual.to_fen());
+        assert_eq!(engine.board().zobrist_key(), actual.zobrist_key());
+
+        let result = engine.go(DEPTH);
+        assert!(result.best_move.is_some());
+        assert_eq!(result.best_move, result.pv.first().copied());
+        for m in &result.pv {
+            let m = actual.parse_uci_move(&m.to_string()).unwrap();
+            actual.make_move(m);
+        }
+    }
+
+    /// Checks that `stop` ends a ponder search promptly: pondering with no depth limit on a search thread must keep
+    /// searching until the flag is set, then return within a second, still pondering the predicted move.
+    #[test]
+    fn stop_ends_pondering_promptly() {
+        let mut engine = Engine::new();
+        engine.set_quiet(true);
+        engine.set_position(None, &["e2e4"]).unwrap();
+        let predicted = engine.board().parse_uci_move("e7e5").unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let search = thread::spawn(move || {
+            let result = engine.go_ponder(predicted, MAX_DEPTH - 1, flag);
+            (engine, result)
+        });
+
+        thread::sleep(Duration::from_millis(200));
+        assert!(!search.is_finished());
+        let stopped_at = Instant::now();
+        stop.store(true, Ordering::Relaxed);
+        let (engine, result) = search.join().unwrap();
+        assert!(stopped_at.elapsed() < Duration::from_secs(1));
+        assert_eq!(engine.pondering(), Some(predicted));
+        assert!(result.best_move.is_some());
+    }
+
+    /// Checks `go infinite` on a search thread: once the stop flag is set, the search must return promptly, with at least
+    /// one completed iteration and the winning move. The black queen hangs to the rook, so even the shortest
+    /// iteration finds the capture.
+    #[test]
+    fn stop_ends_an_infinite_search() {
+        // Set up outside the search thread, so the time before stop is spent searching rather than building tables
+        let mut engine = Engine::new();
+        engine.set_quiet(true);
+        engine.set_position(Some("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1"), &[]).unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let search = thread::spawn(move || engine.go_infinite(flag));
+
+        thread::sleep(Duration::from_millis(200));
+        assert!(!search.is_finished());
+        let stopped_at = Instant::now();
+        stop.store(true, Ordering::Relaxed);
+        let result = search.join().unwrap();
+        assert!(stopped_at.elapsed() < Duration::from_secs(1));
+        assert!(result.depth >= 1);
+        assert_eq!(result.best_move.map(|m| m.to_string()).as_deref(), Some("d2d5"));
+    }
+
+    /// Checks that scores are reported from White's point of view: on the symmetric starting position, with either side to
+    /// move, the static eval and the searched score stay near zero instead of flipping sign with the side to move.
+    #[test]
+    fn scores_reported_from_white_perspective() {
+        const MARGIN: i32 = 50;
+        let mut engine = Engine::new();
+        engine.set_quiet(true);
+        for fen in ["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"] {
+            engine.set_position(Some(fen), &[]).unwrap();
+            assert!(engine.static_eval_cp().abs() <= MARGIN, "{fen}");
+            let (score, pv) = engine.best_continuation(4);
+            assert!(score.0.abs() <= MARGIN && !pv.is_empty(), "{fen}");
+        }
+
+        // A white material edge must be positive with both sides to move
+        for fen in ["rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"] {
+            engine.set_position(Some(fen), &[]).unwrap();
+            assert!(engine.static_eval_cp() >= 500, "{fen}");
+            assert!(engine.best_continuation(3).0.0 >= 500, "{fen}");
+        }
+    }
+
+    /// Checks that a `position ... moves` list longer than any fixed history is accepted: 1200 knight moves, past the
+    /// 1024 plies the history used to hold, then a search from the reached position.
+    #[test]
+    fn long_move_lists_are_accepted() {
+        const SHUFFLE: [&str; 4] = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let moves: Vec<&str> = SHUFFLE.iter().cycle().take(1200).copied().collect();
+        let mut engine = Engine::new();
+        engine.set_quiet(true);
+        engine.set_position(None, &moves).unwrap();
+        assert_eq!(engine.board().to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 1200 601");
+        assert!(engine.go(3).best_move.is_some());
+    }
+}
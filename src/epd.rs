@@ -0,0 +1,110 @@
+//! Extended Position Description (EPD).
+//!
+//! An EPD line is the first four FEN fields (placement, side, castling, en passant) followed by operations, each an
+//! opcode with optional operands and a terminating semicolon: `... w KQkq - bm Nf3; id "opening 1";`. Move counters
+//! are not part of the position, they travel in the `hmvc` and `fmvn` operations when known.
+
+use crate::board::Board;
+
+/// Position and operations of one EPD line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdRecord {
+    pub fen: String,                       // Full FEN, counters taken from `hmvc`/`fmvn` or defaulting to `0 1`
+    pub operations: Vec<(String, String)>, // Opcode and operands, quotes removed, in line order
+}
+
+impl EpdRecord {
+    /// Returns the operands of the first operation with `opcode`, if any.
+    pub fn operation(&self, opcode: &str) -> Option<&str> {
+        self.operations.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_str())
+    }
+}
+
+/// Formats the position of `board` as an EPD line, with its move counters as `hmvc` and `fmvn` after `operations`.
+///
+/// Operands are written as given: string operands must carry their own quotes.
+pub fn to_epd(board: &Board, operations: &[(&str, &str)]) -> String {
+    let fen = board.to_fen();
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let mut epd = fields[..4].join(" ");
+    for (opcode, operands) in operations {
+        epd += &format!(" {} {};", opcode, operands);
+    }
+    epd + &format!(" hmvc {}; fmvn {};", fields[4], fields[5])
+}
+
+/// Parses an EPD line into its FEN and operations.
+///
+/// Semicolons inside quoted operands do not end the operation. The FEN is checked by loading it into a board.
+pub fn parse_epd(line: &str) -> Result<EpdRecord, &'static str> {
+    let mut rest = line.trim();
+    let mut fields = Vec::with_capacity(4);
+    for _ in 0..4 {
+        let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if field.is_empty() {
+            return Err("EPD missing position fields");
+        }
+        fields.push(field);
+        rest = tail.trim_start();
+    }
+
+    // Split on the semicolons outside of quoted operands
+    let mut operations = Vec::new();
+    let (mut operation, mut quoted) = (String::new(), false);
+    for ch in rest.chars() {
+        match ch {
+            ';' if !quoted => {
+                let (opcode, operands) = operation.trim().split_once(char::is_whitespace).unwrap_or((operation.trim(), ""));
+                if opcode.is_empty() {
+                    return Err("EPD operation missing its opcode");
+                }
+                operations.push((opcode.to_string(), operands.trim().replace('"', "")));
+                operation.clear();
+            }
+            '"' => {
+                quoted = !quoted;
+                operation.push(ch);
+            }
+            _ => operation.push(ch),
+        }
+    }
+    if !operation.trim().is_empty() {
+        return Err("EPD operation missing its semicolon");
+    }
+
+    let counter = |opcode: &str, default: &str| operations.iter().find(|(op, _)| op == opcode).map_or(default.to_string(), |(_, n)| n.clone());
+    let fen = format!("{} {} {}", fields.join(" "), counter("hmvc", "0"), counter("fmvn", "1"));
+    Board::new().set_fen(&fen)?;
+    Ok(EpdRecord { fen, operations })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epd_round_trip() {
+        let mut board = Board::new();
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 3 12").unwrap();
+        let line = to_epd(&board, &[("bm", "Qxf6"), ("id", "\"kiwipete; middlegame\"")]);
+        assert_eq!(line, "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - bm Qxf6; id \"kiwipete; middlegame\"; hmvc 3; fmvn 12;");
+
+        let record = parse_epd(&line).unwrap();
+        assert_eq!(record.fen, board.to_fen());
+        assert_eq!(record.operation("bm"), Some("Qxf6"));
+        assert_eq!(record.operation("id"), Some("kiwipete; middlegame"));
+        assert_eq!(record.operation("c0"), None);
+    }
+
+    #[test]
+    fn epd_parsing() {
+        let record = parse_epd("4k3/8/8/8/8/8/8/4K3 b - - bm Kd7; c0;").unwrap();
+        assert_eq!(record.fen, "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(record.operations, [("bm".to_string(), "Kd7".to_string()), ("c0".to_string(), String::new())]);
+
+        assert!(parse_epd("4k3/8/8/8/8/8/8/4K3 b -").is_err(), "Missing field");
+        assert!(parse_epd("4k3/8/8/8/8/8/8/4K3 b - - bm Kd7").is_err(), "Missing semicolon");
+        assert!(parse_epd("4k3/8/8/8/8/8/8/4K3 b - - ; bm Kd7;").is_err(), "Missing opcode");
+        assert!(parse_epd("4k3/8/8/8/8/8/8/4K4 b - - bm Kd7;").is_err(), "Invalid position");
+    }
+}
@@ -0,0 +1,596 @@
+//! Static evaluation.
+//!
+//! This module contains the piece-square tables (PST) and the tapered evaluation built on top of them. Each table has
+//! a midgame and an endgame version; the board keeps both PST sums up to date in make/unmake, and `eval_position`
+//! blends them according to the game phase, which goes from 24 (all minor and major pieces on board) to 0. A threat
+//! term, computed from scratch, rewards pieces attacking more valuable or undefended enemy pieces, a mobility term
+//! rewards the squares pieces reach in their mobility area, and a king tropism term, faded out with the phase, rewards
+//! pieces standing close to the enemy king. In lone-king endings, a king-drive term rewards pushing the bare king
+//! toward a corner and bringing the winning king next to it, the plan of the basic mates.
+//!
+//! Evaluations are clamped to `±EVAL_LIMIT`, below the mate scores of the search, so a static eval can never be taken
+//! for a forced mate. `eval_position_scaled` scales the evaluation before the clamp, e.g. to flatten evals for
+//! self-play.
+//!
+//! Tables are written from white's point of view with rank 8 on top, as they are usually printed.
+
+use crate::attack::{distance, manhattan_distance};
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::movegen::{MoveList, generate_all_captures};
+use crate::search::SCORE_MATE_BOUND;
+use crate::types::{Color, Piece, PieceType, Square, piece_value};
+
+/// Phase weight of each piece type, summing to `PHASE_MAX` in the starting position.
+const PHASE_WEIGHTS: [i32; PieceType::NUM] = [0, 1, 1, 2, 4, 0];
+pub const PHASE_MAX: i32 = 24;
+
+/// Largest magnitude of an evaluation, strictly below the mate-score range of the search whatever the material,
+/// weights or scale.
+pub const EVAL_LIMIT: i32 = SCORE_MATE_BOUND - 1;
+
+/// Bonus for each enemy piece attacked by a less valuable piece, or attacked and undefended.
+const THREAT_BONUS: i32 = 25;
+
+/// Bonus for each square of the mobility area attacked by a knight, bishop, rook or queen.
+const MOBILITY_BONUS: i32 = 3;
+
+/// Midgame bonus per piece type for each step a piece is closer to the enemy king than the farthest possible square.
+const TROPISM_WEIGHTS: [i32; PieceType::NUM] = [0, 3, 2, 2, 4, 0];
+
+/// Bonus per step the bare king stands closer to its nearest corner than the center, in lone-king endings.
+const KING_DRIVE_CORNER: i32 = 10;
+
+/// Bonus per step the winning king stands closer to the bare king than the farthest possible square.
+const KING_DRIVE_PROXIMITY: i32 = 4;
+
+/// Weight of an attacker of the king zone by piece type, used to measure king danger.
+pub const KING_ATTACK_WEIGHTS: [i32; PieceType::NUM] = [1, 2, 2, 3, 5, 0];
+
+#[rustfmt::skip]
+const PST_MG: [[i32; 64]; PieceType::NUM] = [
+    // Pawn
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         10,  10,  20,  30,  30,  20,  10,  10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          0,   0,   0,  20,  20,   0,   0,   0,
+          5,  -5, -10,   0,   0, -10,  -5,   5,
+          5,  10,  10, -20, -20,  10,  10,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Knight
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    // Bishop
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // Rook
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+          5,  10,  10,  10,  10,  10,  10,   5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+          0,   0,   0,   5,   5,   0,   0,   0,
+    ],
+    // Queen
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+          0,   0,   5,   5,   5,   5,   0,  -5,
+        -10,   5,   5,   5,   5,   5,   0, -10,
+        -10,   0,   5,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    // King: stay sheltered behind the pawns
+    [
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -20, -30, -30, -40, -40, -30, -30, -20,
+        -10, -20, -20, -20, -20, -20, -20, -10,
+         20,  20,   0,   0,   0,   0,  20,  20,
+         20,  30,  10,   0,   0,  10,  30,  20,
+    ],
+];
+
+#[rustfmt::skip]
+const PST_EG: [[i32; 64]; PieceType::NUM] = [
+    // Pawn: advancing matters more than the file
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         80,  80,  80,  80,  80,  80,  80,  80,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         30,  30,  30,  30,  30,  30,  30,  30,
+         20,  20,  20,  20,  20,  20,  20,  20,
+         10,  10,  10,  10,  10,  10,  10,  10,
+         10,  10,  10,  10,  10,  10,  10,  10,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    PST_MG[PieceType::Knight as usize],
+    PST_MG[PieceType::Bishop as usize],
+    PST_MG[PieceType::Rook as usize],
+    PST_MG[PieceType::Queen as usize],
+    // King: centralize
+    [
+        -50, -40, -30, -20, -20, -30, -40, -50,
+        -30, -20, -10,   0,   0, -10, -20, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -30,   0,   0,   0,   0, -30, -30,
+        -50, -30, -30, -30, -30, -30, -30, -50,
+    ],
+];
+
+/// Returns the (midgame, endgame) PST values of `piece` on `sq`, positive for white and negative for black.
+#[inline(always)]
+pub fn psqt(piece: Piece, sq: Square) -> (i32, i32) {
+    let piece_type = piece.get_type() as usize;
+    match piece.get_color() {
+        // Tables have a8 at index 0, so white squares are flipped and black squares, seen from black, are not
+        Color::White => (PST_MG[piece_type][sq.flipped()], PST_EG[piece_type][sq.flipped()]),
+        Color::Black => (-PST_MG[piece_type][sq], -PST_EG[piece_type][sq]),
+    }
+}
+
+/// Returns the game phase, from `PHASE_MAX` in the opening down to 0 with only kings and pawns.
+pub fn game_phase(board: &Board) -> i32 {
+    let phase: i32 = [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen]
+        .into_iter()
+        .map(|pt| board.piece(pt).popcnt() as i32 * PHASE_WEIGHTS[pt as usize])
+        .sum();
+    phase.min(PHASE_MAX) // Promotions can push it above the starting value
+}
+
+/// Returns the static evaluation of the position from white's point of view.
+///
+/// Material plus the PST sums, blended between midgame and endgame by the game phase, plus threats, mobility, king
+/// tropism scaled by the phase, and the king drive of lone-king endings.
+pub fn eval_position(board: &Board) -> i32 {
+    eval_position_scaled(board, 100)
+}
+
+/// Returns the static evaluation of the position from white's point of view, scaled by `percent` before the clamp to
+/// `±EVAL_LIMIT`. With 100 it is `eval_position`.
+pub fn eval_position_scaled(board: &Board, percent: i32) -> i32 {
+    let phase = game_phase(board);
+    let psqt = (board.psqt_mg() * phase + board.psqt_eg() * (PHASE_MAX - phase)) / PHASE_MAX;
+    let threats = threat_count(board, Color::White, piece_value) - threat_count(board, Color::Black, piece_value);
+    let mobility = mobility_balance(board);
+    let tropism = tropism_balance(board, &TROPISM_WEIGHTS) * phase / PHASE_MAX;
+    scale_eval(board.material_balance() + psqt + threats * THREAT_BONUS + mobility * MOBILITY_BONUS + tropism + king_drive(board), percent)
+}
+
+/// Scales an evaluation by `percent`, then clamps it to `±EVAL_LIMIT`. Computed in 64 bits, so no scale can overflow.
+pub fn scale_eval(eval: i32, percent: i32) -> i32 {
+    (eval as i64 * percent as i64 / 100).clamp(-EVAL_LIMIT as i64, EVAL_LIMIT as i64) as i32
+}
+
+/// Returns the squares attacked by `color`'s pawns.
+fn pawn_attack_span(board: &Board, color: Color) -> Bitboard {
+    let pawns = board.pieces_of(color, PieceType::Pawn).0;
+    let (not_file_a, not_file_h) = (!Bitboard::file_a().0, !Bitboard::file_h().0);
+    match color {
+        Color::White => Bitboard(((pawns << 7) & not_file_h) | ((pawns << 9) & not_file_a)),
+        Color::Black => Bitboard(((pawns >> 9) & not_file_h) | ((pawns >> 7) & not_file_a)),
+    }
+}
+
+/// Returns the mobility area of `color`: every square except those holding its own pawns or king, and those attacked
+/// by enemy pawns. Moving there is either impossible or usually loses the piece to a pawn.
+pub fn mobility_area(board: &Board, color: Color) -> Bitboard {
+    let blocked = board.pieces_of(color, PieceType::Pawn) | board.pieces_of(color, PieceType::King);
+    !(blocked | pawn_attack_span(board, !color))
+}
+
+/// Counts the squares of `area` attacked by `color`'s knights, bishops, rooks and queens.
+pub fn mobility(board: &Board, color: Color, area: Bitboard) -> i32 {
+    let mut count = 0;
+    for piece_type in [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen] {
+        let mut pieces = board.pieces_of(color, piece_type);
+        while pieces != Bitboard(0) {
+            let sq = Square::new(pieces.pop_lsb() as u8);
+            count += (board.attacks_from(piece_type, sq, color) & area).popcnt() as i32;
+        }
+    }
+    count
+}
+
+/// Returns white's mobility minus black's, each counted in its own mobility area.
+fn mobility_balance(board: &Board) -> i32 {
+    mobility(board, Color::White, mobility_area(board, Color::White)) - mobility(board, Color::Black, mobility_area(board, Color::Black))
+}
+
+/// Sums, over `color`'s knights, bishops, rooks and queens, the piece type weight times how much closer the piece is
+/// to the enemy king than the largest possible distance.
+pub fn king_tropism(board: &Board, color: Color, weights: &[i32; PieceType::NUM]) -> i32 {
+    let their_king = board.king_square(!color);
+    let mut tropism = 0;
+    for piece_type in [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen] {
+        for sq in board.pieces_of(color, piece_type).squares() {
+            tropism += weights[piece_type as usize] * (7 - distance(sq, their_king) as i32);
+        }
+    }
+    tropism
+}
+
+/// Returns white's king tropism minus black's, at full midgame weight.
+fn tropism_balance(board: &Board, weights: &[i32; PieceType::NUM]) -> i32 {
+    king_tropism(board, Color::White, weights) - king_tropism(board, Color::Black, weights)
+}
+
+/// Returns the king-drive bonus from white's point of view, nonzero only when one side has a bare king and the other
+/// a knight, bishop, rook or queen, with enough material to force mate.
+///
+/// The PSTs alone leave the winning side shuffling in basic mates: the bare king must be driven to a corner, and the
+/// winning king must come up to help, so both a short corner distance and a short king distance are rewarded. Without
+/// mating material, as with a lone minor piece or two knights, driving the king would only inflate a drawn eval.
+pub fn king_drive(board: &Board) -> i32 {
+    for strong in [Color::White, Color::Black] {
+        let weak = !strong;
+        let bare = board.color(weak) == board.pieces_of(weak, PieceType::King);
+        let has_piece = (board.color(strong) & !board.piece(PieceType::Pawn) & !board.piece(PieceType::King)) != Bitboard(0);
+        if bare && has_piece && !board.is_unwinnable_material() {
+            let weak_king = board.king_square(weak);
+            let corner_distance =
+                [Square::A1, Square::H1, Square::A8, Square::H8].into_iter().map(|corner| manhattan_distance(weak_king, corner)).min().unwrap();
+            let king_distance = distance(board.king_square(strong), weak_king);
+            let drive = KING_DRIVE_CORNER * (6 - corner_distance as i32) + KING_DRIVE_PROXIMITY * (7 - king_distance as i32);
+            return if strong == Color::White { drive } else { -drive };
+        }
+    }
+    0
+}
+
+/// Counts the enemy pieces that `color` threatens: attacked by a less valuable piece, or attacked and undefended.
+///
+/// These are the simple tactics a static eval misses between quiescence nodes. Counting both colors makes the term
+/// penalize our own hanging pieces as much as it rewards attacking theirs. Kings are never counted as targets.
+fn threat_count(board: &Board, color: Color, value: impl Fn(PieceType) -> i32) -> i32 {
+    let occupancy = board.occupied_squares();
+    let mut targets = board.color(!color) & !board.piece(PieceType::King);
+    let mut threats = 0;
+    while targets != Bitboard(0) {
+        let sq = Square::new(targets.pop_lsb() as u8);
+        let attackers_to = board.attackers_to(sq, occupancy);
+        let mut attackers = attackers_to & board.color(color);
+        if attackers == Bitboard(0) {
+            continue;
+        }
+
+        let defended = attackers_to & board.color(!color) != Bitboard(0);
+        let target_value = value(board.piece_on_unchecked(sq).get_type());
+        let mut lesser_attacker = false;
+        while attackers != Bitboard(0) {
+            let attacker = board.piece_on_unchecked(Square::new(attackers.pop_lsb() as u8)).get_type();
+            // The king has no material value, but it can only take undefended pieces
+            lesser_attacker |= attacker != PieceType::King && value(attacker) < target_value;
+        }
+        threats += (lesser_attacker || !defended) as i32;
+    }
+    threats
+}
+
+/// Evaluation weights as plain data, so that a tuner can read, adjust and inject them.
+///
+/// `EvalParams::default()` holds the weights `eval_position` is built on, and `eval_with_params` with them returns the
+/// same score. The incremental evaluation used in search keeps its `const` tables for speed.
+#[derive(Clone, Debug)]
+pub struct EvalParams {
+    pub piece_values: [i32; PieceType::NUM],
+    pub pst_mg: [[i32; 64]; PieceType::NUM],
+    pub pst_eg: [[i32; 64]; PieceType::NUM],
+    pub threat_bonus: i32,
+    pub mobility_bonus: i32,
+    pub tropism_weights: [i32; PieceType::NUM],
+}
+
+const PIECE_NAMES: [&str; PieceType::NUM] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        let piece_types = [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King];
+        Self {
+            piece_values: piece_types.map(piece_value),
+            pst_mg: PST_MG,
+            pst_eg: PST_EG,
+            threat_bonus: THREAT_BONUS,
+            mobility_bonus: MOBILITY_BONUS,
+            tropism_weights: TROPISM_WEIGHTS,
+        }
+    }
+}
+
+impl EvalParams {
+    /// Number of parameters in the flattened vector.
+    pub const COUNT: usize = PieceType::NUM + 2 * 64 * PieceType::NUM + 2 + PieceType::NUM;
+
+    /// Returns the name of each parameter, in the order of `to_vec`.
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = PIECE_NAMES.iter().map(|piece| format!("{}_value", piece)).collect();
+        for stage in ["mg", "eg"] {
+            for piece in PIECE_NAMES {
+                names.extend(Square::ALL.iter().map(|sq| format!("pst_{}_{}_{}", stage, piece, sq)));
+            }
+        }
+        names.push("threat_bonus".to_string());
+        names.push("mobility_bonus".to_string());
+        names.extend(PIECE_NAMES.iter().map(|piece| format!("{}_tropism", piece)));
+        names
+    }
+
+    /// Flattens the parameters: piece values, midgame and endgame PSTs, each indexed by square (a1 first), then threats,
+    /// mobility and the tropism weights.
+    pub fn to_vec(&self) -> Vec<i32> {
+        let mut params = self.piece_values.to_vec();
+        for table in [&self.pst_mg, &self.pst_eg] {
+            for piece_table in table {
+                params.extend(Square::ALL.iter().map(|&sq| piece_table[sq.flipped()]));
+            }
+        }
+        params.push(self.threat_bonus);
+        params.push(self.mobility_bonus);
+        params.extend_from_slice(&self.tropism_weights);
+        params
+    }
+
+    /// Builds parameters from a vector laid out as in `to_vec`.
+    pub fn from_slice(params: &[i32]) -> Result<Self, &'static str> {
+        if params.len() != Self::COUNT {
+            return Err("Wrong number of evaluation parameters");
+        }
+        let mut result = Self::default();
+        result.piece_values.copy_from_slice(&params[..PieceType::NUM]);
+        let mut values = params[PieceType::NUM..].iter();
+        for table in [&mut result.pst_mg, &mut result.pst_eg] {
+            for piece_table in table.iter_mut() {
+                for sq in Square::ALL {
+                    piece_table[sq.flipped()] = *values.next().unwrap();
+                }
+            }
+        }
+        result.threat_bonus = *values.next().unwrap();
+        result.mobility_bonus = *values.next().unwrap();
+        for weight in result.tropism_weights.iter_mut() {
+            *weight = *values.next().unwrap();
+        }
+        Ok(result)
+    }
+}
+
+/// Evaluates the position from scratch with the given weights, from white's point of view.
+pub fn eval_with_params(board: &Board, params: &EvalParams) -> i32 {
+    let (mut material, mut mg, mut eg) = (0, 0, 0);
+    for sq in Square::ALL {
+        if let Some(piece) = board.piece_on(sq) {
+            let piece_type = piece.get_type() as usize;
+            let (sign, index) = if piece.get_color() == Color::White { (1, sq.flipped()) } else { (-1, sq) };
+            material += sign * params.piece_values[piece_type];
+            mg += sign * params.pst_mg[piece_type][index];
+            eg += sign * params.pst_eg[piece_type][index];
+        }
+    }
+
+    let value = |piece_type: PieceType| params.piece_values[piece_type as usize];
+    let threats = threat_count(board, Color::White, value) - threat_count(board, Color::Black, value);
+
+    let phase = game_phase(board);
+    let psqt = (mg * phase + eg * (PHASE_MAX - phase)) / PHASE_MAX;
+    let tropism = tropism_balance(board, &params.tropism_weights) * phase / PHASE_MAX;
+    let positional = threats * params.threat_bonus + mobility_balance(board) * params.mobility_bonus + tropism + king_drive(board);
+    (material + psqt + positional).clamp(-EVAL_LIMIT, EVAL_LIMIT)
+}
+
+/// Quiet evaluation for tuning, from white's point of view: a capture-only quiescence search on top of
+/// `eval_with_params`, so that tuning positions with pending captures are scored after the exchanges.
+pub fn eval_for_tuning(board: &mut Board, params: &EvalParams) -> i32 {
+    let sign = if board.side_to_move() == Color::White { 1 } else { -1 };
+    sign * tuning_quiescence(board, params, -i32::MAX, i32::MAX)
+}
+
+fn tuning_quiescence(board: &mut Board, params: &EvalParams, mut alpha: i32, beta: i32) -> i32 {
+    let sign = if board.side_to_move() == Color::White { 1 } else { -1 };
+    let stand_pat = sign * eval_with_params(board, params);
+    if stand_pat >= beta {
+        return stand_pat;
+    }
+    alpha = alpha.max(stand_pat);
+
+    let mut moves = MoveList::new();
+    generate_all_captures(board, &mut moves);
+    let (pinned, in_check) = (board.pinned(), board.king_in_check(board.side_to_move()));
+
+    let mut best = stand_pat;
+    for m in moves.iter() {
+        if !board.is_legal_fast(m, pinned, in_check) {
+            continue;
+        }
+        board.make_move(m);
+        let score = -tuning_quiescence(board, params, -beta, -alpha);
+        board.unmake_move(m);
+
+        best = best.max(score);
+        if score >= beta {
+            break;
+        }
+        alpha = alpha.max(score);
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the tuning interface: default parameters must agree with `eval_position`, and raising the pawn value must
+    /// move the eval of the starting position minus a white pawn down by exactly the same amount.
+    #[test]
+    fn default_params_match_eval_position() {
+        const FENS: [&str; 3] = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        let mut board = Board::new();
+        let params = EvalParams::default();
+        for fen in FENS {
+            board.from_fen(fen).unwrap();
+            assert_eq!(eval_with_params(&board, &params), eval_position(&board), "{fen}");
+        }
+        assert_eq!(EvalParams::from_slice(&params.to_vec()).unwrap().to_vec(), params.to_vec());
+        assert_eq!(EvalParams::names().len(), EvalParams::COUNT);
+
+        board.from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let mut heavier_pawns = params.clone();
+        heavier_pawns.piece_values[PieceType::Pawn as usize] += 50;
+        assert_eq!(eval_for_tuning(&mut board, &heavier_pawns), eval_for_tuning(&mut board, &params) - 50);
+    }
+
+    /// Checks the threat term: a knight forking two rooks must score exactly two threat bonuses more than the same
+    /// material with the knight on a square of equal PST value that attacks nothing. Tropism is left out, as the two
+    /// knight squares are not as far from the black king.
+    #[test]
+    fn knight_fork_scores_two_threats() {
+        let params = EvalParams {
+            tropism_weights: [0; PieceType::NUM],
+            ..EvalParams::default()
+        };
+        let mut board = Board::new();
+        board.from_fen("k7/2r3r1/4N3/8/8/8/8/7K w - - 0 1").unwrap();
+        let fork = eval_with_params(&board, &params);
+        board.from_fen("k7/2r3r1/8/8/8/4N3/8/7K w - - 0 1").unwrap();
+        let quiet = eval_with_params(&board, &params);
+
+        assert_eq!(fork - quiet, 2 * THREAT_BONUS);
+    }
+
+    /// Checks that a position with absurd material still evaluates strictly below the mate threshold, and that absurd
+    /// weights are clamped to `±EVAL_LIMIT` for either side.
+    #[test]
+    fn huge_material_stays_below_mate() {
+        let mut board = Board::new();
+        board.from_fen("QQQQQQQQ/QQQQQQQQ/QQQQQQQQ/8/8/8/8/k6K w - - 0 1").unwrap();
+        let mut huge = EvalParams::default();
+        huge.piece_values[PieceType::Queen as usize] = 100_000;
+
+        let eval = eval_position(&board);
+        assert!(eval > 0 && eval < SCORE_MATE_BOUND);
+        assert_eq!(eval_with_params(&board, &huge), EVAL_LIMIT);
+
+        board.from_fen("K6k/8/8/8/8/qqqqqqqq/qqqqqqqq/qqqqqqqq b - - 0 1").unwrap();
+        assert_eq!(eval_with_params(&board, &huge), -EVAL_LIMIT);
+    }
+
+    /// Checks the mobility area on a knight facing two enemy pawns: of its 8 attacked squares, the own king's square and
+    /// the 2 squares covered by the pawns are outside the area, the pawns themselves stay in as capture targets.
+    #[test]
+    fn mobility_area_excludes_king_and_pawn_attacks() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/8/2p1p3/8/3N4/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(mobility(&board, Color::White, Bitboard(!0)), 8);
+        assert_eq!(mobility(&board, Color::White, mobility_area(&board, Color::White)), 5);
+    }
+
+    /// Checks the tropism term: bringing a queen from a3 to e5, closer to the black king, must raise the eval, and by more
+    /// than it does with the tropism weights zeroed.
+    #[test]
+    fn queen_near_the_king_gains_tropism() {
+        let mut board = Board::new();
+        board.from_fen("rnb1kbnr/pppppppp/8/8/8/Q7/PPPPPPPP/RNB1KBNR w KQkq - 0 1").unwrap();
+        let (far, far_tropism) = (eval_position(&board), king_tropism(&board, Color::White, &TROPISM_WEIGHTS));
+        let far_without = eval_with_params(
+            &board,
+            &EvalParams {
+                tropism_weights: [0; PieceType::NUM],
+                ..EvalParams::default()
+            },
+        );
+        board.from_fen("rnb1kbnr/pppppppp/8/4Q3/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1").unwrap();
+        let (near, near_tropism) = (eval_position(&board), king_tropism(&board, Color::White, &TROPISM_WEIGHTS));
+        let near_without = eval_with_params(
+            &board,
+            &EvalParams {
+                tropism_weights: [0; PieceType::NUM],
+                ..EvalParams::default()
+            },
+        );
+
+        assert!(near_tropism > far_tropism);
+        assert!(near > far);
+        assert!(near - far > near_without - far_without);
+    }
+
+    /// Checks the king drive in KQvK, with the white king and queen on the same squares: the black king cornered on h8
+    /// must score better for White than centralized on e5, and the term must vanish once Black has a pawn.
+    #[test]
+    fn king_drive_toward_the_corner() {
+        let mut board = Board::new();
+        board.from_fen("7k/8/8/8/8/2Q5/8/2K5 w - - 0 1").unwrap();
+        let (cornered, cornered_drive) = (eval_position(&board), king_drive(&board));
+        board.from_fen("8/8/8/4k3/8/2Q5/8/2K5 w - - 0 1").unwrap();
+        let (centralized, centralized_drive) = (eval_position(&board), king_drive(&board));
+        assert!(cornered > centralized);
+        assert!(cornered_drive > centralized_drive);
+
+        board.from_fen("8/8/8/8/2q5/8/5K2/6k1 b - - 0 1").unwrap();
+        assert!(king_drive(&board) < 0);
+        board.from_fen("7k/7p/8/8/8/2Q5/8/2K5 w - - 0 1").unwrap();
+        assert_eq!(king_drive(&board), 0);
+    }
+
+    /// Checks that the king drive stays off without mating material: a lone bishop or two knights against a bare king
+    /// get no bonus, while bishop and knight do.
+    #[test]
+    fn no_king_drive_without_mating_material() {
+        let mut board = Board::new();
+        for fen in ["7k/8/8/8/8/2B5/8/2K5 w - - 0 1", "7k/8/8/8/8/2NN4/8/2K5 w - - 0 1", "2k5/8/8/8/8/8/2b5/K7 b - - 0 1"] {
+            board.from_fen(fen).unwrap();
+            assert_eq!(king_drive(&board), 0, "{fen}");
+        }
+        board.from_fen("7k/8/8/8/8/2BN4/8/2K5 w - - 0 1").unwrap();
+        assert!(king_drive(&board) > 0);
+    }
+
+    /// Checks that scaling happens before the clamp: a value past the limit is halved rather than clamped first, and
+    /// scaled evaluations still clamp to `±EVAL_LIMIT` for either side.
+    #[test]
+    fn scaled_evals_clamp_to_the_limit() {
+        assert_eq!(scale_eval(200, 50), 100);
+        assert_eq!(scale_eval(-200, 150), -300);
+        assert_eq!(scale_eval(EVAL_LIMIT + 1000, 50), (EVAL_LIMIT + 1000) / 2);
+        assert_eq!(scale_eval(i32::MAX, 1000), EVAL_LIMIT);
+
+        let mut board = Board::new();
+        board.from_fen("QQQQQQQQ/QQQQQQQQ/QQQQQQQQ/8/8/8/8/k6K w - - 0 1").unwrap();
+        assert_eq!(eval_position_scaled(&board, 100), eval_position(&board));
+        assert_eq!(eval_position_scaled(&board, 1000), EVAL_LIMIT);
+        board.from_fen("K6k/8/8/8/8/qqqqqqqq/qqqqqqqq/qqqqqqqq b - - 0 1").unwrap();
+        assert_eq!(eval_position_scaled(&board, 1000), -EVAL_LIMIT);
+        assert!(eval_position_scaled(&board, 10).abs() < eval_position(&board).abs());
+    }
+}
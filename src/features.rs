@@ -0,0 +1,87 @@
+//! Position features for evaluation experiments.
+//!
+//! `Board::features` gathers, in one read-only snapshot, the quantities the evaluation is built from: material, game
+//! phase, pawn structure, king squares and mobility. It is meant for tuning outside the engine and for preparing the
+//! inputs of a network, not for the search, which computes only what it needs.
+
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::eval::{game_phase, mobility, mobility_area};
+use crate::types::{Color, PieceType, Square, piece_value};
+
+/// Snapshot of a position, each per-color array indexed by `Color`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Features {
+    pub piece_counts: [[u32; PieceType::NUM]; 2], // Indexed by color, then piece type
+    pub material: i32,                            // White minus black, with the evaluation piece values
+    pub phase: i32,                               // From `PHASE_MAX` in the opening down to 0
+    pub pawns_per_file: [[u8; 8]; 2],             // Indexed by color, then file a to h
+    pub king_squares: [Square; 2],
+    pub passed_pawns: [Bitboard; 2], // Pawns with no enemy pawn ahead on their file or the adjacent ones
+    pub mobility: [i32; 2],          // Squares of the mobility area attacked by knights, bishops, rooks and queens
+}
+
+impl Board {
+    /// Returns the features of the position.
+    pub fn features(&self) -> Features {
+        let piece_types = [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King];
+        let piece_counts = [Color::White, Color::Black].map(|color| piece_types.map(|pt| self.piece_count(pt, color)));
+        let material =
+            piece_types.into_iter().map(|pt| (piece_counts[Color::White][pt] as i32 - piece_counts[Color::Black][pt] as i32) * piece_value(pt)).sum();
+
+        let mut pawns_per_file = [[0u8; 8]; 2];
+        let mut passed_pawns = [Bitboard(0); 2];
+        for color in [Color::White, Color::Black] {
+            let their_pawns = self.pieces_of(!color, PieceType::Pawn);
+            for sq in self.pieces_of(color, PieceType::Pawn).squares() {
+                pawns_per_file[color][sq.file() as usize] += 1;
+                if self.attack_tables.front_span[color][sq] & their_pawns == Bitboard(0) {
+                    passed_pawns[color] |= sq.bb();
+                }
+            }
+        }
+
+        Features {
+            piece_counts,
+            material,
+            phase: game_phase(self),
+            pawns_per_file,
+            king_squares: [self.king_square(Color::White), self.king_square(Color::Black)],
+            passed_pawns,
+            mobility: [Color::White, Color::Black].map(|color| mobility(self, color, mobility_area(self, color))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the features against the evaluation they come from: the material must be `material_balance` and the
+    /// phase `game_phase`, on positions from the opening to the endgame, and the passed pawns of a pawn ending must be
+    /// found.
+    #[test]
+    fn features_agree_with_the_evaluation() {
+        const FENS: [&str; 3] = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        let mut board = Board::new();
+        for fen in FENS {
+            board.from_fen(fen).unwrap();
+            let features = board.features();
+            assert_eq!(features.material, board.material_balance(), "{fen}");
+            assert_eq!(features.phase, game_phase(&board), "{fen}");
+            for color in [Color::White, Color::Black] {
+                let pawns: u8 = features.pawns_per_file[color].iter().sum();
+                assert_eq!(pawns as u32, features.piece_counts[color][PieceType::Pawn], "{fen}");
+                assert_eq!(features.king_squares[color], board.king_square(color), "{fen}");
+            }
+        }
+
+        // The g pawns block each other, the b6 and c3 pawns have no enemy pawn ahead
+        board.from_fen("4k3/8/1P4p1/8/8/2p5/6P1/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.features().passed_pawns, [Square::B6.bb(), Square::C3.bb()]);
+    }
+}
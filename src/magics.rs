@@ -5,7 +5,7 @@
 //!
 //! - Computes relevant occupancy masks for each square
 //! - Enumerates all possible blocker configurations
-//! - Searches for collision-free magic multipliers
+//! - Searches for collision-free magic multipliers, or takes the baked ones (see `MagicMode`)
 //! - Builds flat attack tables indexed via magic hashing
 //!
 //! Once initialized, attack lookup requires only:
@@ -15,16 +15,22 @@
 //!     - a table lookup
 //!
 //! The generated tables are read-only after initialization and contain no runtime branches, locks, or collision checks.
+//!
+//! The shift is computed from the mask popcount by default. With the `shift-table` feature it is loaded from the
+//! per-square shift tables instead, so both variants can be benchmarked (`benchmark_perft`, `benchmark_shift_modes`).
+//! With the `black-magic` feature, lookups go to the shared black magic table of `black_magics.rs` instead.
 
 use rand::RngCore;
 use rand::SeedableRng;
 use rand::rngs::SmallRng;
 
 use crate::bitboard::Bitboard;
+#[cfg(feature = "black-magic")]
+use crate::black_magics::BlackMagicTables;
 use crate::types::Square;
 
-const ROOK_DELTAS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
-const BISHOP_DELTAS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+pub(crate) const ROOK_DELTAS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+pub(crate) const BISHOP_DELTAS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
 
 /// Total number of rook magic attack entries.
 ///
@@ -43,6 +49,75 @@ const ROOK_MAP_SIZE: usize = 102400; // Σ over sq=0..63 (2 ^ rook_relevant_bits
 /// The exact total is 5248.
 const BISHOP_MAP_SIZE: usize = 5248; // Σ over sq=0..63 (2 ^ bishop_relevant_bits[sq])
 
+/// Seed of the magic search used by `generate_magics`, kept fixed so the generated magics are reproducible.
+pub const DEFAULT_MAGIC_SEED: u64 = 0xD10FA; //🥚
+
+/// Candidate magics tried per square before giving up. With the fixed seeds a magic is found long before.
+const MAX_MAGIC_ATTEMPTS: usize = 10_000_000;
+
+/// How the magics are obtained when the attack tables are built.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum MagicMode {
+    /// Run the magic search, about half a second in release builds and much more in debug builds.
+    Search,
+    /// Use `BAKED_ROOK_MAGICS` and `BAKED_BISHOP_MAGICS`, only the attack tables are filled.
+    #[default]
+    Baked,
+}
+
+impl MagicMode {
+    /// Parses a mode as given to the `MagicMode` option, `Search` or `Baked`, case-insensitively.
+    pub fn parse(value: &str) -> Result<Self, &'static str> {
+        match value.to_ascii_lowercase().as_str() {
+            "search" => Ok(MagicMode::Search),
+            "baked" => Ok(MagicMode::Baked),
+            _ => Err("MagicMode must be Search or Baked"),
+        }
+    }
+}
+
+/// Rook magics found by the search with `DEFAULT_MAGIC_SEED`, indexed by square.
+#[rustfmt::skip]
+pub const BAKED_ROOK_MAGICS: [u64; 64] = [
+    0x028000124000A18B, 0x10C0200040001004, 0x4100084301102000, 0x0100100020080500,
+    0x0A00302012000814, 0x0080040002008001, 0x0200020001040088, 0x0200008022010044,
+    0x4400800661804000, 0x8302002081004200, 0x0441001020004100, 0x0101000900100420,
+    0x1210800800840080, 0x0002000200080510, 0xC040800200010080, 0x4802003041008402,
+    0x4200208000401080, 0x0006060021004382, 0x8100420012048020, 0x4022020020081040,
+    0x0800050010080100, 0x1000808002000400, 0x8000040002810850, 0x00001200046B8401,
+    0x0080004040002000, 0x0120200080400080, 0x8A00200100401100, 0x2020210100081000,
+    0x0010040080080080, 0xA041000300080400, 0x900001C400021008, 0x0110204200208401,
+    0x908004C004C02000, 0x0200401000402000, 0x0030040020200800, 0x0019001001000820,
+    0x0804800800800400, 0x8000800200800400, 0xD00A000402000801, 0x0224240042000081,
+    0x0280002000434000, 0x0020810042020020, 0x4082004020820019, 0x0101000810010022,
+    0x000C028008008004, 0x0003000400290042, 0x0C02000401020088, 0x4000404400820001,
+    0x0140324880030100, 0x0018200840008080, 0x4003104904200100, 0x21041000200D0100,
+    0x1400080080040080, 0x00C0201004400801, 0x0002000448018200, 0x3400210044008200,
+    0x4204102044800101, 0x000600810130A242, 0x0020100C40200101, 0x0202204510004901,
+    0x0002008810210402, 0x4002002810096426, 0x000001209A100804, 0x0008040040810022,
+];
+
+/// Bishop magics found by the search with `DEFAULT_MAGIC_SEED`, indexed by square.
+#[rustfmt::skip]
+pub const BAKED_BISHOP_MAGICS: [u64; 64] = [
+    0x0004014421020202, 0x0010010200820800, 0x0084091202128001, 0x0124441080100008,
+    0x48084840108C2002, 0x008090108810002A, 0x0021010120200180, 0x0800402808480400,
+    0xA001082001024208, 0x0240201849010020, 0x8408502C00802800, 0x2024080581100090,
+    0x0C01C212104A2100, 0x00040202D2200000, 0x0110410841042120, 0x41000D008211214C,
+    0x2008402029100080, 0x0C10006001290320, 0x0050010104002444, 0x8002000440150000,
+    0x8000810400E00010, 0x0803000080600240, 0x0208810052082022, 0x0018210201040230,
+    0x0008084020023020, 0x0406A24020180E10, 0x0020404004010200, 0x4090040000401020,
+    0x0081004004004050, 0x0308020010404218, 0x000812010080C400, 0x0000610042011100,
+    0x002882090810200A, 0x2002010400101020, 0x080F080100080040, 0x0202004041240100,
+    0x0402348400020020, 0x6802044100121000, 0x1408050042040E40, 0x0105130200022200,
+    0x1A9402A010020545, 0x0004310108021020, 0x032090C128001002, 0x8008802018008101,
+    0x0151C05812000042, 0x0411020881010604, 0x0802220801000204, 0xB442080A00920428,
+    0x0030880402203002, 0x48230084D0082021, 0x0421850088048442, 0x8430100042120820,
+    0x000020190B040181, 0x0220852004010200, 0x1C0A021424040000, 0x00A2224C09020408,
+    0x0302002404240400, 0x2010832886082000, 0x0208011104884400, 0x8440400040228808,
+    0x8008808410020214, 0x2000212008220820, 0xA020880210820A00, 0x0004200081010100,
+];
+
 /// Precomputed magic bitboard data for sliding piece attack generation.
 ///
 /// This structure stores all information required to compute rook and bishop
@@ -55,10 +130,15 @@ pub struct MagicTables {
     pub rook_magics: [u64; 64],   // Magic multiplier for rooks
     pub bishop_magics: [u64; 64], // Magic multiplier for bishops
 
-    pub rook_attacks: [Bitboard; ROOK_MAP_SIZE],     // Flat rook attack table, indexed by offsets[sq] + magic_index
-    pub bishop_attacks: [Bitboard; BISHOP_MAP_SIZE], // Flat bishop attack table, indexed by offsets[sq] + magic_index
-    pub rook_offsets: [usize; 64],                   // Starting index in `rook_attacks` for each square
-    pub bishop_offsets: [usize; 64],                 // Starting index in `bishop_attacks` for each square
+    pub rook_attacks: Box<[Bitboard]>,   // Flat rook attack table, indexed by offsets[sq] + magic_index
+    pub bishop_attacks: Box<[Bitboard]>, // Flat bishop attack table, indexed by offsets[sq] + magic_index
+    pub rook_offsets: [usize; 64],       // Starting index in `rook_attacks` for each square
+    pub bishop_offsets: [usize; 64],     // Starting index in `bishop_attacks` for each square
+    pub rook_shifts: [u32; 64],          // 64 - relevant bits, read with the `shift-table` feature
+    pub bishop_shifts: [u32; 64],        // 64 - relevant bits, read with the `shift-table` feature
+
+    #[cfg(feature = "black-magic")]
+    pub black: BlackMagicTables, // Shared-table layout, replaces the flat tables for lookups
 }
 
 impl MagicTables {
@@ -68,13 +148,81 @@ impl MagicTables {
             bishop_masks: [Bitboard(0); 64],
             rook_magics: [0; 64],
             bishop_magics: [0; 64],
-            rook_attacks: [Bitboard(0); ROOK_MAP_SIZE],
-            bishop_attacks: [Bitboard(0); BISHOP_MAP_SIZE],
+            rook_attacks: vec![Bitboard(0); ROOK_MAP_SIZE].into_boxed_slice(),
+            bishop_attacks: vec![Bitboard(0); BISHOP_MAP_SIZE].into_boxed_slice(),
             rook_offsets: [0; 64],
             bishop_offsets: [0; 64],
+            rook_shifts: [0; 64],
+            bishop_shifts: [0; 64],
+            #[cfg(feature = "black-magic")]
+            black: BlackMagicTables::empty(),
         }
     }
 
+    /// Returns rook attacks from `sq` under the given board occupancy.
+    #[cfg(not(feature = "black-magic"))]
+    #[inline(always)]
+    pub fn rook_lookup(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        self.flat_rook_lookup(sq, occupancy)
+    }
+
+    /// Returns rook attacks from `sq` under the given board occupancy.
+    #[cfg(feature = "black-magic")]
+    #[inline(always)]
+    pub fn rook_lookup(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        self.black.rook_lookup(sq, occupancy)
+    }
+
+    /// Returns bishop attacks from `sq` under the given board occupancy.
+    #[cfg(not(feature = "black-magic"))]
+    #[inline(always)]
+    pub fn bishop_lookup(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        self.flat_bishop_lookup(sq, occupancy)
+    }
+
+    /// Returns bishop attacks from `sq` under the given board occupancy.
+    #[cfg(feature = "black-magic")]
+    #[inline(always)]
+    pub fn bishop_lookup(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        self.black.bishop_lookup(sq, occupancy)
+    }
+
+    /// Returns rook attacks from the flat tables.
+    ///
+    /// Occupancy of the board is masked to relevant squares, multiplied by the magic number, and indexed into
+    /// a flat attack table.
+    #[inline(always)]
+    pub fn flat_rook_lookup(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        let mask = self.rook_masks[sq];
+        let relevant_occupancy = occupancy & mask;
+        let magic = self.rook_magics[sq];
+
+        // Calculating `(64 - mask.0.count_ones())` on the fly SHOULD be just as fast than loading it from a table
+        // mov rax, [mask]                              mov rax, [mask]
+        // popcnt rcx, rax                 vs           move rcx, [shift]
+        // sub rcx, 64
+        //
+        // 1 load + 2 ALU = ~9 cycles    <---->         2 loads = ~10 cycles
+        // The `shift-table` feature selects the table, `cfg!` is constant so the other branch is compiled out.
+        let shift = if cfg!(feature = "shift-table") { self.rook_shifts[sq] } else { 64 - mask.0.count_ones() };
+        let idx = ((relevant_occupancy.0.wrapping_mul(magic)) >> shift) as usize;
+
+        self.rook_attacks[self.rook_offsets[sq] + idx]
+    }
+
+    /// Returns bishop attacks from the flat tables.
+    #[inline(always)]
+    pub fn flat_bishop_lookup(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        let mask = self.bishop_masks[sq];
+        let relevant_occupancy = occupancy & mask;
+        let magic = self.bishop_magics[sq];
+
+        let shift = if cfg!(feature = "shift-table") { self.bishop_shifts[sq] } else { 64 - mask.0.count_ones() };
+        let idx = ((relevant_occupancy.0.wrapping_mul(magic)) >> shift) as usize;
+
+        self.bishop_attacks[self.bishop_offsets[sq] + idx]
+    }
+
     /// Generates magic numbers and populates flat attack tables for rooks and bishops.
     ///
     /// For each square:
@@ -88,13 +236,70 @@ impl MagicTables {
     /// Note:
     /// The specific magic values chosen do not affect runtime performance. Any collision-free magic produces identical lookup speed.
     pub fn generate_magics(&mut self) {
+        self.generate_magics_seeded(DEFAULT_MAGIC_SEED);
+    }
+
+    /// Fills the tables with magics obtained as `mode` says: searched with `DEFAULT_MAGIC_SEED`, or baked.
+    pub fn init(&mut self, mode: MagicMode) {
+        match mode {
+            MagicMode::Search => self.generate_magics(),
+            MagicMode::Baked => self.load_magics(&BAKED_ROOK_MAGICS, &BAKED_BISHOP_MAGICS),
+        }
+    }
+
+    /// Fills the tables from known magics, skipping the search.
+    ///
+    /// Panics, naming the square, if a magic maps two occupancies with different attacks to the same entry.
+    pub fn load_magics(&mut self, rook_magics: &[u64; 64], bishop_magics: &[u64; 64]) {
+        self.init_relevant_occupancy_masks();
+        self.rook_magics = *rook_magics;
+        self.bishop_magics = *bishop_magics;
+
+        let sliders = [
+            (&self.rook_masks, &self.rook_magics, &mut self.rook_offsets, &mut self.rook_shifts, &mut self.rook_attacks[..], &ROOK_DELTAS),
+            (&self.bishop_masks, &self.bishop_magics, &mut self.bishop_offsets, &mut self.bishop_shifts, &mut self.bishop_attacks[..], &BISHOP_DELTAS),
+        ];
+        for (masks, magics, offsets, shifts, flat_table, deltas) in sliders {
+            flat_table.fill(Bitboard(0));
+            let mut offset = 0usize;
+            for sq in Square::ALL {
+                let shift = 64 - masks[sq].0.count_ones();
+                for (occ, attack) in Self::enumerate_occupancies(masks[sq]).into_iter().zip(Self::attacks_for_square(sq, deltas)) {
+                    let entry = &mut flat_table[offset + (occ.0.wrapping_mul(magics[sq]) >> shift) as usize];
+                    if *entry != Bitboard(0) && *entry != attack {
+                        panic!("Magic for square {} maps different attacks to the same entry", sq);
+                    }
+                    *entry = attack; // Sliding attacks are never empty, so an empty entry is a free one
+                }
+                offsets[sq] = offset;
+                shifts[sq] = shift;
+                offset += 1 << masks[sq].0.count_ones();
+            }
+        }
+        #[cfg(feature = "black-magic")]
+        {
+            self.black = BlackMagicTables::new();
+        }
+    }
+
+    /// Same as `generate_magics`, with the search driven by `seed`. Each seed gives a different, equally valid set of
+    /// magics, which allows re-running the search to look for smaller or faster-converging ones.
+    pub fn generate_magics_seeded(&mut self, seed: u64) {
         self.init_relevant_occupancy_masks();
 
         let rook_attacks = self.generate_all_rook_attacks();
         let bishop_attacks = self.generate_all_bishop_attacks();
 
-        Self::search_loop(&self.rook_masks, &rook_attacks, &mut self.rook_magics, &mut self.rook_offsets, &mut self.rook_attacks);
-        Self::search_loop(&self.bishop_masks, &bishop_attacks, &mut self.bishop_magics, &mut self.bishop_offsets, &mut self.bishop_attacks);
+        Self::search_loop(seed, &self.rook_masks, &rook_attacks, &mut self.rook_magics, &mut self.rook_offsets, &mut self.rook_attacks);
+        Self::search_loop(seed, &self.bishop_masks, &bishop_attacks, &mut self.bishop_magics, &mut self.bishop_offsets, &mut self.bishop_attacks);
+        for sq in 0..64 {
+            self.rook_shifts[sq] = 64 - self.rook_masks[sq].0.count_ones();
+            self.bishop_shifts[sq] = 64 - self.bishop_masks[sq].0.count_ones();
+        }
+        #[cfg(feature = "black-magic")]
+        {
+            self.black = BlackMagicTables::new();
+        }
 
         // Invariant check
         let total_rook_slots: usize = self.rook_masks.iter().map(|m| 1usize << m.0.count_ones()).sum();
@@ -114,11 +319,16 @@ impl MagicTables {
     /// The resulting lookup is branchless and O(1):
     ///     index = offsets[sq] + ((occ & mask) * magic >> shift)
     /// A flat table is preferred to a matrix since different squares have a different number of relevant occupancies.
-    fn search_loop(masks: &[Bitboard; 64], attacks: &Vec<Vec<Bitboard>>, magics: &mut [u64; 64], offsets: &mut [usize; 64], flat_table: &mut [Bitboard]) {
+    ///
+    /// Panics, naming the square, if no magic is found in `MAX_MAGIC_ATTEMPTS` attempts: a missing magic would leave
+    /// the attack table silently wrong.
+    fn search_loop(
+        seed: u64, masks: &[Bitboard; 64], attacks: &[Vec<Bitboard>], magics: &mut [u64; 64], offsets: &mut [usize; 64], flat_table: &mut [Bitboard],
+    ) {
         let mut offset = 0usize;
 
         for sq in 0..64 {
-            let mut rng = SmallRng::seed_from_u64(0xD10FA ^ (sq as u64 * 0xD10BE571A)); //🥚
+            let mut rng = SmallRng::seed_from_u64(seed ^ (sq as u64 * 0xD10BE571A));
 
             let mask = masks[sq];
             let relevant_bits = mask.0.count_ones() as usize;
@@ -130,7 +340,8 @@ impl MagicTables {
 
             let mut temp_table = vec![None; table_size];
 
-            'search: for _attempt in 0..10_000_000 {
+            let mut found = false;
+            'search: for _attempt in 0..MAX_MAGIC_ATTEMPTS {
                 let magic = Self::sparse_random(&mut rng);
 
                 // Quick entropy rejection (from Stockfish). If high bits are mostly zero -> more collisions.
@@ -160,15 +371,20 @@ impl MagicTables {
                 }
 
                 offset += table_size; //Next square
+                found = true;
                 break;
             }
+
+            if !found {
+                panic!("No magic found for square {} after {} attempts", Square::new(sq as u8), MAX_MAGIC_ATTEMPTS);
+            }
         }
     }
 
     /// Generates a candidate magic number with sparse bits set, inspired by Stockfish's sparse_rand.
     /// Sparse numbers reduce collisions in magic bitboards. Deterministic if the same seed is used.
     #[inline(always)]
-    fn sparse_random(rng: &mut SmallRng) -> u64 {
+    pub(crate) fn sparse_random(rng: &mut SmallRng) -> u64 {
         rng.next_u64() & rng.next_u64() & rng.next_u64()
     }
 
@@ -182,7 +398,7 @@ impl MagicTables {
 
     // Generic relevant occupancy mask generator for sliding pieces. Excludes edge squares (rank/file 0 or 7).
     #[inline(always)]
-    fn relevant_occupancy_mask(square: Square, deltas: &[(i8, i8)]) -> Bitboard {
+    pub(crate) fn relevant_occupancy_mask(square: Square, deltas: &[(i8, i8)]) -> Bitboard {
         let mut mask = Bitboard(0);
         let from_rank = square.rank() as i8;
         let from_file = square.file() as i8;
@@ -209,24 +425,24 @@ impl MagicTables {
     // Generates all possible rook attacks for all squares and occupancies.
     // Used in magic number generation to populate the flat attack tables.
     fn generate_all_rook_attacks(&self) -> Vec<Vec<Bitboard>> {
-        (0..64).map(|idx| Square::new(idx)).map(|sq| Self::attacks_for_square(sq, &ROOK_DELTAS)).collect()
+        (0..64).map(Square::new).map(|sq| Self::attacks_for_square(sq, &ROOK_DELTAS)).collect()
     }
 
     // Generates all possible bishop attacks for all squares and occupancies.
     // Used in magic number generation to populate the flat attack tables.
     fn generate_all_bishop_attacks(&self) -> Vec<Vec<Bitboard>> {
-        (0..64).map(|idx| Square::new(idx)).map(|sq| Self::attacks_for_square(sq, &BISHOP_DELTAS)).collect()
+        (0..64).map(Square::new).map(|sq| Self::attacks_for_square(sq, &BISHOP_DELTAS)).collect()
     }
 
     // Generates all attacks for a specific square and piece (given by deltas)
-    fn attacks_for_square(square: Square, deltas: &[(i8, i8)]) -> Vec<Bitboard> {
+    pub(crate) fn attacks_for_square(square: Square, deltas: &[(i8, i8)]) -> Vec<Bitboard> {
         let mask = Self::relevant_occupancy_mask(square, deltas);
         let occupancies = Self::enumerate_occupancies(mask);
         occupancies.iter().map(|occ| Self::sliding_attack(square, deltas, *occ)).collect()
     }
 
     /// Enumerates all possible occupancies for a given relevant mask.
-    fn enumerate_occupancies(mask: Bitboard) -> Vec<Bitboard> {
+    pub(crate) fn enumerate_occupancies(mask: Bitboard) -> Vec<Bitboard> {
         let num_relevant_bits = mask.0.count_ones() as usize;
         let mut occupancies = Vec::with_capacity(1 << num_relevant_bits); //1<<n = 2^n
 
@@ -263,7 +479,7 @@ impl MagicTables {
             let mut to_rank = from_rank + delta_rank;
             let mut to_file = from_file + delta_file;
 
-            while to_rank >= 0 && to_rank < 8 && to_file >= 0 && to_file < 8 {
+            while (0..8).contains(&to_rank) && (0..8).contains(&to_file) {
                 let sq_index = (to_rank * 8 + to_file) as u8;
                 let sq = Square::new(sq_index);
                 attacks |= sq.bb();
@@ -277,6 +493,30 @@ impl MagicTables {
         attacks
     }
 
+    /// Times rook index computations with the computed and the stored shift, in the same binary.
+    ///
+    /// Only the index is timed, the full effect on move generation is measured by running `benchmark_perft` with and
+    /// without the `shift-table` feature.
+    #[allow(dead_code)]
+    pub fn benchmark_shift_modes(&self) {
+        const LOOKUPS: usize = 50_000_000;
+        let mut rng = SmallRng::seed_from_u64(0x5EED);
+        let occupancies: Vec<u64> = (0..1024).map(|_| rng.next_u64() & rng.next_u64()).collect();
+
+        let computed = |sq: usize, occ: u64| (occ & self.rook_masks[sq].0).wrapping_mul(self.rook_magics[sq]) >> (64 - self.rook_masks[sq].0.count_ones());
+        let stored = |sq: usize, occ: u64| (occ & self.rook_masks[sq].0).wrapping_mul(self.rook_magics[sq]) >> self.rook_shifts[sq];
+
+        for (name, index) in [("computed", &computed as &dyn Fn(usize, u64) -> u64), ("stored", &stored)] {
+            let start = std::time::Instant::now();
+            let mut checksum = 0u64;
+            for i in 0..LOOKUPS {
+                checksum ^= std::hint::black_box(index(i & 63, occupancies[i & 1023]));
+            }
+            let elapsed = start.elapsed().as_secs_f64();
+            println!("{:>8} shift: {:.2} M index/s (checksum {:x})", name, LOOKUPS as f64 / elapsed / 1e6, checksum);
+        }
+    }
+
     #[allow(dead_code)]
     pub fn print(&self) {
         println!("=== MAGIC TABLES ===");
@@ -295,3 +535,98 @@ impl MagicTables {
         println!("Total bishop table size : {}", self.bishop_attacks.len());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the lookups against a ray walk for every square and every relevant occupancy, alone and with all the
+    /// other squares occupied. The `shift-table` and `black-magic` features change what is read, so this must pass
+    /// with every combination of them.
+    #[test]
+    fn lookups_match_a_ray_walk() {
+        let mut tables = Box::new(MagicTables::new());
+        tables.init(MagicMode::Baked);
+        for sq in Square::ALL {
+            for occ in MagicTables::enumerate_occupancies(tables.rook_masks[sq]) {
+                for occupancy in [occ, occ | !tables.rook_masks[sq]] {
+                    let expected = MagicTables::sliding_attack(sq, &ROOK_DELTAS, occupancy);
+                    assert_eq!(tables.flat_rook_lookup(sq, occupancy), expected, "rook on {sq}");
+                    assert_eq!(tables.rook_lookup(sq, occupancy), expected, "rook on {sq}");
+                }
+            }
+            for occ in MagicTables::enumerate_occupancies(tables.bishop_masks[sq]) {
+                for occupancy in [occ, occ | !tables.bishop_masks[sq]] {
+                    let expected = MagicTables::sliding_attack(sq, &BISHOP_DELTAS, occupancy);
+                    assert_eq!(tables.flat_bishop_lookup(sq, occupancy), expected, "bishop on {sq}");
+                    assert_eq!(tables.bishop_lookup(sq, occupancy), expected, "bishop on {sq}");
+                }
+            }
+        }
+    }
+
+    /// Checks that every square got a magic: a zero magic maps all occupancies to the same entry.
+    #[test]
+    fn every_square_gets_a_magic() {
+        let mut tables = Box::new(MagicTables::new());
+        tables.generate_magics();
+        assert!(!tables.rook_magics.contains(&0));
+        assert!(!tables.bishop_magics.contains(&0));
+    }
+
+    /// Checks that two different seeds both give collision-free magics whose tables return the correct attacks for every
+    /// square and every relevant occupancy.
+    #[test]
+    fn seeded_magics_give_correct_attacks() {
+        let mut tables = [MagicTables::new(), MagicTables::new()];
+        tables[0].generate_magics_seeded(DEFAULT_MAGIC_SEED);
+        tables[1].generate_magics_seeded(0x5EED_CAFE);
+        assert_ne!(tables[0].rook_magics, tables[1].rook_magics);
+        assert_ne!(tables[0].bishop_magics, tables[1].bishop_magics);
+
+        for magic_tables in &tables {
+            assert!(!magic_tables.rook_magics.contains(&0) && !magic_tables.bishop_magics.contains(&0));
+            for sq in Square::ALL {
+                let rook_occupancies = MagicTables::enumerate_occupancies(magic_tables.rook_masks[sq]);
+                for (occ, attack) in rook_occupancies.into_iter().zip(MagicTables::attacks_for_square(sq, &ROOK_DELTAS)) {
+                    assert_eq!(magic_tables.flat_rook_lookup(sq, occ), attack, "rook on {sq}");
+                }
+                let bishop_occupancies = MagicTables::enumerate_occupancies(magic_tables.bishop_masks[sq]);
+                for (occ, attack) in bishop_occupancies.into_iter().zip(MagicTables::attacks_for_square(sq, &BISHOP_DELTAS)) {
+                    assert_eq!(magic_tables.flat_bishop_lookup(sq, occ), attack, "bishop on {sq}");
+                }
+            }
+        }
+    }
+
+    /// Checks that both magic modes build identical tables: the same magics, offsets and flat attack tables, hence
+    /// the same attacks for every square and every occupancy.
+    #[test]
+    fn magic_modes_build_identical_tables() {
+        let (mut searched, mut baked) = (Box::new(MagicTables::new()), Box::new(MagicTables::new()));
+        searched.init(MagicMode::Search);
+        baked.init(MagicMode::Baked);
+        assert_eq!(searched.rook_magics, baked.rook_magics);
+        assert_eq!(searched.bishop_magics, baked.bishop_magics);
+        assert_eq!(searched.rook_offsets, baked.rook_offsets);
+        assert_eq!(searched.bishop_offsets, baked.bishop_offsets);
+        assert_eq!(searched.rook_shifts, baked.rook_shifts);
+        assert_eq!(searched.bishop_shifts, baked.bishop_shifts);
+        assert!(searched.rook_attacks == baked.rook_attacks);
+        assert!(searched.bishop_attacks == baked.bishop_attacks);
+        for sq in Square::ALL {
+            for occ in MagicTables::enumerate_occupancies(searched.rook_masks[sq] | searched.bishop_masks[sq]) {
+                assert_eq!(searched.rook_lookup(sq, occ), baked.rook_lookup(sq, occ), "rook on {sq}");
+                assert_eq!(searched.bishop_lookup(sq, occ), baked.bishop_lookup(sq, occ), "bishop on {sq}");
+            }
+        }
+    }
+
+    /// Checks the parsing of the magic mode option, case insensitive.
+    #[test]
+    fn magic_mode_parsing() {
+        assert_eq!(MagicMode::parse("baked"), Ok(MagicMode::Baked));
+        assert_eq!(MagicMode::parse("Search"), Ok(MagicMode::Search));
+        assert!(MagicMode::parse("fast").is_err());
+    }
+}
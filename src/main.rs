@@ -4,24 +4,48 @@
 //! This crate implements a complete chess engine written in (mostly) safe Rust.
 //!
 //! # Architecture
+//! - `bench.rs`: search benchmark on a fixed position set
 //! - `bitboard.rs`: low-level bitboard definition
 //! - `board.rs`: chessboard representation
+//! - `clock.rs`: search limits, stop flag, deadlines and node limit
+//! - `engine.rs`: engine state between searches, position, ponder and infinite search handling
+//! - `epd.rs`: EPD position lines
+//! - `eval.rs`: static evaluation
+//! - `features.rs`: position features for evaluation tuning
 //! - `movegen.rs`: move generation
 //! - `attack.rs`: attack tables generation on startup
 //! - `magics.rs`: sliding piece attack generation on startup
+//! - `black_magics.rs`: alternative sliding attack layout with a shared table
 //! - `moves.rs`: low-level move representation
+//! - `zobrist.rs`: Zobrist hashing keys
+//! - `pgn.rs`: PGN game import, conversion to EPD
+//! - `polyglot.rs`: Polyglot opening book keys
+//! - `stack.rs`: per-ply search state, killers, static evals and PVs
+//! - `tt.rs`: transposition table
 
 #![allow(dead_code)]
 
 mod attack;
+mod bench;
 mod bitboard;
+mod black_magics;
 mod board;
+mod clock;
+mod engine;
+mod epd;
+mod eval;
+mod features;
 mod magics;
 mod movegen;
 mod moves;
 mod perft;
+mod pgn;
+mod polyglot;
 mod search;
+mod stack;
+mod tt;
 mod types;
+mod zobrist;
 
 use crate::board::Board;
 use crate::search::Searcher;
@@ -33,6 +57,25 @@ const ZUGZWANG: &str = "8/8/8/3k4/3P4/3K4/8/8 w - - 0 1";
 const TACTICAL_MATE: &str = "r1b1k2r/pppp1ppp/2n2n2/1B2p3/4P3/2N5/PPPP1PPP/R1BQK2R w kq - 0 1";
 
 fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // `--magic-mode Search` runs the magic search instead of using the baked magics, before any table is built
+    if let Some(i) = args.iter().position(|arg| arg == "--magic-mode") {
+        let mode = args.get(i + 1).map_or(Err("--magic-mode needs a value"), |value| magics::MagicMode::parse(value));
+        if let Err(e) = mode.and_then(attack::set_magic_mode) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        args.drain(i..=i + 1);
+    }
+
+    // `masca bench [depth]` runs the search benchmark and exits
+    if args.get(1).map(String::as_str) == Some("bench") {
+        let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(bench::BENCH_DEPTH);
+        bench::bench(depth);
+        return;
+    }
+
     //benchmark_perft(6);
     let mut board = Board::new();
     //board.from_fen(KIWIPETE).unwrap();
@@ -7,7 +7,7 @@
 //! All inner loops are optimized for branchless execution, bitboard manipulation, and monomorphization
 
 use crate::bitboard::Bitboard;
-use crate::board::{BK, BQ, Board, WK, WQ};
+use crate::board::Board;
 use crate::moves::{Move, MoveKind};
 use crate::types::{Color, PieceType, Square};
 
@@ -21,13 +21,22 @@ pub struct MoveList {
 }
 
 impl MoveList {
+    /// Creates an empty list. The 256 moves live on the stack, so this only costs the initialization of the array;
+    /// in hot loops that allow it, prefer reusing a list with `clear`.
     pub fn new() -> Self {
         Self { moves: [Move::NULL_MOVE; 256], count: 0 }
     }
 
+    /// Empties the list so it can be filled again. Old moves are not erased, only the count is reset.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+
     /// Pushes a move into the list.
     #[inline(always)]
     pub fn push(&mut self, m: Move) {
+        debug_assert!(!m.is_null(), "Null move pushed into a move list");
         self.moves[self.count] = m;
         self.count += 1;
     }
@@ -50,10 +59,50 @@ impl MoveList {
         self.moves[i]
     }
 
+    /// Returns true if `m` is in the list. Never true for `NULL_MOVE`, which is not pushed.
+    #[inline(always)]
+    pub fn contains(&self, m: Move) -> bool {
+        self.moves[..self.count].contains(&m)
+    }
+
     /// Allows iteration over the move list.
     pub fn iter(&self) -> impl Iterator<Item = Move> + '_ {
         self.moves[..self.count].iter().copied()
     }
+
+    /// Picks the highest-scored move among the remaining ones (`start..count`), swaps it with its score to `start` and
+    /// returns it.
+    ///
+    /// One selection sort step: searches usually cut off after the first few moves, so sorting the whole list upfront
+    /// would mostly be wasted.
+    #[inline(always)]
+    pub fn pick_best(&mut self, scores: &mut [i32], start: usize) -> Move {
+        let mut best = start;
+        for i in (start + 1)..self.count {
+            if scores[i] > scores[best] {
+                best = i;
+            }
+        }
+        self.swap(start, best);
+        scores.swap(start, best);
+        self.moves[start]
+    }
+
+    /// Sorts the moves by decreasing score with an insertion sort, moving the scores along. Equal scores keep their
+    /// generation order.
+    pub fn sort_by_scores(&mut self, scores: &mut [i32]) {
+        for i in 1..self.count {
+            let (m, score) = (self.moves[i], scores[i]);
+            let mut j = i;
+            while j > 0 && scores[j - 1] < score {
+                self.moves[j] = self.moves[j - 1];
+                scores[j] = scores[j - 1];
+                j -= 1;
+            }
+            self.moves[j] = m;
+            scores[j] = score;
+        }
+    }
 }
 
 /// Trait for pieces that can generate pseudo-legal attacks.
@@ -90,8 +139,9 @@ impl Attacker for Re {
 
 /// Rook move generation using magic bitboards.
 ///
-/// Occupancy of the board is masked to relevant squares, multiplied by the magic number, and indexed into
-/// a flat attack table. `(64 - mask.popcount())` is computed on-the-fly for maximum performance.
+/// See `MagicTables::rook_lookup`. By default the shift `64 - mask.popcount()` is computed on the fly, with the
+/// `shift-table` feature it is read from the per-square shift table, and with the `black-magic` feature the attacks
+/// come from the shared black magic table instead of the flat tables.
 ///
 /// "Tor" means tower in piedmontese.
 pub struct Tor;
@@ -100,28 +150,15 @@ impl Attacker for Tor {
 
     #[inline(always)]
     fn get_attacks(from: Square, board: &Board) -> Bitboard {
-        let mt = &board.attack_tables.magic_tables;
-        let mask = mt.rook_masks[from];
-        let relevant_occupancy = board.occupied_squares() & mask;
-        let magic = mt.rook_magics[from];
-
-        // Calculating `(64 - mask.0.count_ones())` on the fly SHOULD be just as fast than loading it from a table
-        // mov rax, [mask]                              mov rax, [mask]
-        // popcnt rcx, rax                 vs           move rcx, [shift]
-        // sub rcx, 64
-        //
-        // 1 load + 2 ALU = ~9 cycles    <---->         2 loads = ~10 cycles
-        let idx = ((relevant_occupancy.0.wrapping_mul(magic)) >> (64 - mask.0.count_ones())) as usize;
-
-        let offset = mt.rook_offsets[from];
-        mt.rook_attacks[offset + idx]
+        board.attack_tables.magic_tables.rook_lookup(from, board.occupied_squares())
     }
 }
 
 /// Bishop move generation using magic bitboards.
 ///
-/// Occupancy of the board is masked to relevant squares, multiplied by the magic number, and indexed into
-/// a flat attack table. `(64 - mask.popcount())` is computed on-the-fly for maximum performance.
+/// See `MagicTables::bishop_lookup`. By default the shift `64 - mask.popcount()` is computed on the fly, with the
+/// `shift-table` feature it is read from the per-square shift table, and with the `black-magic` feature the attacks
+/// come from the shared black magic table instead of the flat tables.
 ///
 /// "Alfè" means standard-bearer in Piedmontese.
 pub struct Alfè;
@@ -130,15 +167,7 @@ impl Attacker for Alfè {
 
     #[inline(always)]
     fn get_attacks(from: Square, board: &Board) -> Bitboard {
-        let mt = &board.attack_tables.magic_tables;
-        let mask = mt.bishop_masks[from];
-        let relevant_occupancy = board.occupied_squares() & mask;
-        let magic = mt.bishop_magics[from];
-
-        let idx = ((relevant_occupancy.0.wrapping_mul(magic)) >> (64 - mask.0.count_ones())) as usize;
-
-        let offset = mt.bishop_offsets[from];
-        mt.bishop_attacks[offset + idx]
+        board.attack_tables.magic_tables.bishop_lookup(from, board.occupied_squares())
     }
 }
 
@@ -150,7 +179,7 @@ impl Attacker for Argina {
 
     #[inline(always)]
     fn get_attacks(from: Square, board: &Board) -> Bitboard {
-        Tor::get_attacks(from, &board) | Alfè::get_attacks(from, &board)
+        Tor::get_attacks(from, board) | Alfè::get_attacks(from, board)
     }
 }
 
@@ -162,7 +191,114 @@ pub fn generate_all_moves(board: &Board, moves: &mut MoveList) {
     }
 }
 
+/// Generates all legal moves for the current side to move.
+///
+/// In double check only the king can move, so nothing else is generated. In single check the other pieces must
+/// capture or block the checker. Pinned pieces are restricted to the line through their king, so only king moves and
+/// en passant need an explicit king-safety test.
+pub fn generate_legal_moves(board: &Board, moves: &mut MoveList) {
+    match board.side_to_move() {
+        Color::White => generate_legal::<true>(board, moves),  // ⚪️
+        Color::Black => generate_legal::<false>(board, moves), // ⚫️
+    }
+}
+
+/// Legal move generation, monomorphized over the side to move.
+fn generate_legal<const WHITE: bool>(board: &Board, moves: &mut MoveList) {
+    let checkers = board.checkers();
+    if checkers.popcnt() > 1 {
+        generate_legal_king_moves(board, moves);
+        return;
+    }
+
+    let our_color = if WHITE { Color::White } else { Color::Black };
+    let king_sq = board.king_square(our_color);
+    let pinned = board.pinned();
+    let in_check = checkers != Bitboard(0);
+
+    // In single check, non-king moves must capture the checker or block the line it attacks along.
+    // Knight and pawn checkers have nothing in between, so they can only be captured.
+    let target = if in_check { checkers | board.attack_tables.between[king_sq][checkers.square()] } else { !Bitboard(0) };
+
+    generate_legal_piece_moves::<Caval, WHITE>(board, moves, target, pinned, king_sq);
+    generate_legal_piece_moves::<Alfè, WHITE>(board, moves, target, pinned, king_sq);
+    generate_legal_piece_moves::<Tor, WHITE>(board, moves, target, pinned, king_sq);
+    generate_legal_piece_moves::<Argina, WHITE>(board, moves, target, pinned, king_sq);
+    generate_legal_king_moves(board, moves);
+    if !in_check {
+        generate_castling::<WHITE>(board, moves);
+    }
+
+    // Pawns: pinned pawns stay on the pin line, en passant can uncover a rank attack and is always verified
+    let mut pawn_moves = MoveList::new();
+    generate_pawn_quiets::<WHITE, true, true, true>(board, &mut pawn_moves);
+    generate_pawn_captures::<WHITE, true, true, true>(board, &mut pawn_moves);
+    for m in pawn_moves.iter() {
+        let legal = if m.is_enpassant() {
+            board.leaves_king_safe(m)
+        } else {
+            target & m.to().bb() != Bitboard(0)
+                && (pinned & m.from().bb() == Bitboard(0) || board.attack_tables.line[king_sq][m.from()] & m.to().bb() != Bitboard(0))
+        };
+        if legal {
+            moves.push(m);
+        }
+    }
+}
+
+/// Legal move generation for knights, bishops, rooks and queens.
+///
+/// Destinations are restricted to `target`, which is every square outside of check and the checker plus the
+/// blocking squares in single check. A pinned piece may only move along the line through its king and the pinner (capturing the pinner included),
+/// which removes the need to verify king safety after the move. Pinned knights therefore never move.
+#[inline(always)]
+fn generate_legal_piece_moves<P: Attacker, const WHITE: bool>(board: &Board, moves: &mut MoveList, target: Bitboard, pinned: Bitboard, king_sq: Square) {
+    let us = if WHITE { board.color(Color::White) } else { board.color(Color::Black) };
+    let them = if WHITE { board.color(Color::Black) } else { board.color(Color::White) };
+
+    let mut pieces = board.piece(P::TYPE) & us;
+    while pieces != Bitboard(0) {
+        let from = Square::new(pieces.pop_lsb() as u8);
+        let mut attacks = P::get_attacks(from, board) & target & !us;
+        if pinned & from.bb() != Bitboard(0) {
+            attacks &= board.attack_tables.line[king_sq][from];
+        }
+
+        while attacks != Bitboard(0) {
+            let to = Square::new(attacks.pop_lsb() as u8);
+            if them & to.bb() != Bitboard(0) {
+                moves.push(Move::new_special(from, to, MoveKind::Capture));
+            } else {
+                moves.push(Move::new_normal(from, to));
+            }
+        }
+    }
+}
+
+/// Generates legal king moves for the side to move.
+///
+/// Squares attacked by the opponent are computed with the king removed from the occupancy, so the king cannot step back along the line
+/// of a checking slider.
+pub fn generate_legal_king_moves(board: &Board, moves: &mut MoveList) {
+    let us = board.side_to_move();
+    let them = board.color(!us);
+    let king_sq = board.king_square(us);
+    let attacked = board.all_attacks_with_occupancy(!us, board.occupied_squares() ^ king_sq.bb());
+
+    let mut targets = board.attack_tables.king[king_sq] & !board.color(us) & !attacked;
+    while targets != Bitboard(0) {
+        let to = Square::new(targets.pop_lsb() as u8);
+        if them & to.bb() != Bitboard(0) {
+            moves.push(Move::new_special(king_sq, to, MoveKind::Capture));
+        } else {
+            moves.push(Move::new_normal(king_sq, to));
+        }
+    }
+}
+
 /// Generates of pseudo-legal captures for the current side to move.
+///
+/// Quiet promotions are noisy moves too, so they are emitted here rather than by `generate_quiets`.
 pub fn generate_all_captures(board: &Board, moves: &mut MoveList) {
     match board.side_to_move() {
         Color::White => generate_white_captures(board, moves), // ⚪️
@@ -170,6 +306,47 @@ pub fn generate_all_captures(board: &Board, moves: &mut MoveList) {
     }
 }
 
+/// Generates pseudo-legal promotions (quiet and capture) for the current side to move.
+///
+/// Promotions are very forcing, this allows a staged picker or quiescence to try them before other moves.
+/// With `UNDERPROMOTIONS` false only queen promotions are generated, which is usually enough outside quiescence.
+pub fn generate_promotions<const UNDERPROMOTIONS: bool>(board: &Board, moves: &mut MoveList) {
+    match board.side_to_move() {
+        Color::White => {
+            generate_pawn_captures::<true, false, true, UNDERPROMOTIONS>(board, moves);
+            generate_pawn_quiets::<true, false, true, UNDERPROMOTIONS>(board, moves);
+        }
+        Color::Black => {
+            generate_pawn_captures::<false, false, true, UNDERPROMOTIONS>(board, moves);
+            generate_pawn_quiets::<false, false, true, UNDERPROMOTIONS>(board, moves);
+        }
+    }
+}
+
+/// Generates pseudo-legal quiet moves for the current side to move.
+///
+/// Includes castling and double pushes, excludes promotions. Together with `generate_all_captures` it covers
+/// exactly the moves of `generate_all_moves`, which allows for staged generation.
+pub fn generate_quiets(board: &Board, moves: &mut MoveList) {
+    match board.side_to_move() {
+        Color::White => generate_white_quiets(board, moves), // ⚪️
+        Color::Black => generate_black_quiets(board, moves), // ⚫️
+    }
+}
+
+/// Generates pseudo-legal quiet moves that give check, directly or by discovery, for the current side to move.
+///
+/// Lets quiescence try checking moves at its first ply, to catch tactics that start with a quiet check.
+pub fn generate_quiet_checks(board: &Board, moves: &mut MoveList) {
+    let mut quiets = MoveList::new();
+    generate_quiets(board, &mut quiets);
+    for m in quiets.iter() {
+        if board.gives_check(m) {
+            moves.push(m);
+        }
+    }
+}
+
 /// Generates all captures for white. ⚪️
 pub fn generate_white_captures(board: &Board, moves: &mut MoveList) {
     generate_moves::<Caval, true, true>(board, moves);
@@ -177,7 +354,8 @@ pub fn generate_white_captures(board: &Board, moves: &mut MoveList) {
     generate_moves::<Alfè, true, true>(board, moves);
     generate_moves::<Tor, true, true>(board, moves);
     generate_moves::<Argina, true, true>(board, moves);
-    generate_pawn_captures::<true>(board, moves);
+    generate_pawn_captures::<true, true, true, true>(board, moves);
+    generate_pawn_quiets::<true, false, true, true>(board, moves);
 }
 
 /// Generates all captures for black. ⚫️
@@ -187,47 +365,81 @@ pub fn generate_black_captures(board: &Board, moves: &mut MoveList) {
     generate_moves::<Alfè, false, true>(board, moves);
     generate_moves::<Tor, false, true>(board, moves);
     generate_moves::<Argina, false, true>(board, moves);
-    generate_pawn_captures::<false>(board, moves);
+    generate_pawn_captures::<false, true, true, true>(board, moves);
+    generate_pawn_quiets::<false, false, true, true>(board, moves);
 }
 
-/// Generates all moves for white. ⚪️
-pub fn generate_white_moves(board: &Board, moves: &mut MoveList) {
+/// Generates all quiet moves for white. ⚪️
+pub fn generate_white_quiets(board: &Board, moves: &mut MoveList) {
     generate_moves::<Caval, true, false>(board, moves);
-    generate_moves::<Caval, true, true>(board, moves);
     generate_moves::<Re, true, false>(board, moves);
-    generate_moves::<Re, true, true>(board, moves);
     generate_moves::<Alfè, true, false>(board, moves);
-    generate_moves::<Alfè, true, true>(board, moves);
     generate_moves::<Tor, true, false>(board, moves);
-    generate_moves::<Tor, true, true>(board, moves);
     generate_moves::<Argina, true, false>(board, moves);
-    generate_moves::<Argina, true, true>(board, moves);
-
-    generate_pawn_quiets::<true>(board, moves);
-    generate_pawn_captures::<true>(board, moves);
-
+    generate_pawn_quiets::<true, true, false, true>(board, moves);
     generate_castling::<true>(board, moves);
 }
 
-/// Generates all moves for black. ⚫️
-pub fn generate_black_moves(board: &Board, moves: &mut MoveList) {
+/// Generates all quiet moves for black. ⚫️
+pub fn generate_black_quiets(board: &Board, moves: &mut MoveList) {
     generate_moves::<Caval, false, false>(board, moves);
-    generate_moves::<Caval, false, true>(board, moves);
     generate_moves::<Re, false, false>(board, moves);
-    generate_moves::<Re, false, true>(board, moves);
     generate_moves::<Alfè, false, false>(board, moves);
-    generate_moves::<Alfè, false, true>(board, moves);
     generate_moves::<Tor, false, false>(board, moves);
-    generate_moves::<Tor, false, true>(board, moves);
     generate_moves::<Argina, false, false>(board, moves);
-    generate_moves::<Argina, false, true>(board, moves);
+    generate_pawn_quiets::<false, true, false, true>(board, moves);
+    generate_castling::<false>(board, moves);
+}
 
-    generate_pawn_quiets::<false>(board, moves);
-    generate_pawn_captures::<false>(board, moves);
+/// Generates all moves for white. ⚪️
+pub fn generate_white_moves(board: &Board, moves: &mut MoveList) {
+    generate_piece_moves::<true>(board, moves);
+
+    generate_pawn_quiets::<true, true, true, true>(board, moves);
+    generate_pawn_captures::<true, true, true, true>(board, moves);
+
+    generate_castling::<true>(board, moves);
+}
+
+/// Generates all moves for black. ⚫️
+pub fn generate_black_moves(board: &Board, moves: &mut MoveList) {
+    generate_piece_moves::<false>(board, moves);
+
+    generate_pawn_quiets::<false, true, true, true>(board, moves);
+    generate_pawn_captures::<false, true, true, true>(board, moves);
 
     generate_castling::<false>(board, moves);
 }
 
+/// Generates quiet moves and captures of knights, kings, bishops, rooks and queens.
+///
+/// Loops over a const array of monomorphized generators, so both colors share the list of piece types. The loop is
+/// unrolled and the calls inlined by the compiler.
+#[inline(always)]
+pub fn generate_piece_moves<const WHITE: bool>(board: &Board, moves: &mut MoveList) {
+    for generate in PieceGenerators::<WHITE>::ALL {
+        generate(board, moves);
+    }
+}
+
+/// Holds the piece move generators of one color, quiets before captures for each piece type.
+struct PieceGenerators<const WHITE: bool>;
+
+impl<const WHITE: bool> PieceGenerators<WHITE> {
+    const ALL: [fn(&Board, &mut MoveList); 10] = [
+        generate_moves::<Caval, WHITE, false>,
+        generate_moves::<Caval, WHITE, true>,
+        generate_moves::<Re, WHITE, false>,
+        generate_moves::<Re, WHITE, true>,
+        generate_moves::<Alfè, WHITE, false>,
+        generate_moves::<Alfè, WHITE, true>,
+        generate_moves::<Tor, WHITE, false>,
+        generate_moves::<Tor, WHITE, true>,
+        generate_moves::<Argina, WHITE, false>,
+        generate_moves::<Argina, WHITE, true>,
+    ];
+}
+
 /// Generic move generation for leaper and sliding pieces.
 ///
 /// # Parameters
@@ -248,7 +460,7 @@ pub fn generate_moves<P: Attacker, const WHITE: bool, const CAPTURE: bool>(board
 
     while attackers != Bitboard(0) {
         let from = Square::new(attackers.pop_lsb() as u8);
-        let mut attacks = P::get_attacks(from, &board) & target_mask;
+        let mut attacks = P::get_attacks(from, board) & target_mask;
 
         while attacks != Bitboard(0) {
             let to = Square::new(attacks.pop_lsb() as u8);
@@ -261,19 +473,43 @@ pub fn generate_moves<P: Attacker, const WHITE: bool, const CAPTURE: bool>(board
     }
 }
 
+/// Pushes the four promotions (queen first) of the pawn move `from`-`to`, or only the queen one without `UNDERPROMOTIONS`.
+///
+/// Shared by the quiet and capture generators, so both always emit the same set of promotion pieces.
+#[inline(always)]
+pub fn push_promotions<const UNDERPROMOTIONS: bool>(from: Square, to: Square, is_capture: bool, moves: &mut MoveList) {
+    const QUIET: [MoveKind; 4] = [MoveKind::PromotionQ, MoveKind::PromotionR, MoveKind::PromotionB, MoveKind::PromotionN];
+    const CAPTURE: [MoveKind; 4] = [MoveKind::PromotionCaptureQ, MoveKind::PromotionCaptureR, MoveKind::PromotionCaptureB, MoveKind::PromotionCaptureN];
+
+    let kinds = if is_capture { &CAPTURE } else { &QUIET };
+    let count = if UNDERPROMOTIONS { 4 } else { 1 };
+    for &kind in &kinds[..count] {
+        moves.push(Move::new_special(from, to, kind));
+    }
+}
+
 /// Pawn capture moves, including promotions and en passant.
 ///
 /// Uses precomputed `pawn_capture` tables and single-bit operations. Fully branchless inside loops except for promotion/en-passant handling.
 /// Generic over `const WHITE` to remove runtime color checks.
 ///
+/// # Parameters
+/// - `CAPTURES: bool` — generate captures that do not promote, including en passant
+/// - `PROMOTIONS: bool` — generate promotion captures
+/// - `UNDERPROMOTIONS: bool` — also generate promotions to rook, bishop and knight, not only to queen
+///
 /// # Details
 /// - Captures enemy pieces or the en passant square
-/// - Generates all promotion captures automatically
+/// - Generates promotion captures automatically
 /// - Branch-minimized inner loop with bitwise operations
 #[inline(always)]
-pub fn generate_pawn_captures<const WHITE: bool>(board: &Board, moves: &mut MoveList) {
+pub fn generate_pawn_captures<const WHITE: bool, const CAPTURES: bool, const PROMOTIONS: bool, const UNDERPROMOTIONS: bool>(
+    board: &Board, moves: &mut MoveList,
+) {
     let our_color = if WHITE { Color::White } else { Color::Black };
-    let mut pawns = board.piece(PieceType::Pawn) & board.color(our_color);
+    let seventh_rank = if WHITE { Bitboard(0x00FF000000000000u64) } else { Bitboard(0x000000000000FF00u64) };
+    let pawn_filter = if CAPTURES { !seventh_rank } else { Bitboard(0) } | if PROMOTIONS { seventh_rank } else { Bitboard(0) };
+    let mut pawns = board.pieces_of(our_color, PieceType::Pawn) & pawn_filter;
 
     let them = if WHITE { board.color(Color::Black) } else { board.color(Color::White) };
     let promotion_rank = if WHITE { Bitboard(0xFF00000000000000u64) } else { Bitboard(0x00000000000000FFu64) };
@@ -292,10 +528,7 @@ pub fn generate_pawn_captures<const WHITE: bool>(board: &Board, moves: &mut Move
             if (to_bb & ep_square) != Bitboard(0) {
                 moves.push(Move::new_special(from, to, MoveKind::EnPassant));
             } else if promotion_rank & to_bb != Bitboard(0) {
-                moves.push(Move::new_special(from, to, MoveKind::PromotionCaptureQ));
-                moves.push(Move::new_special(from, to, MoveKind::PromotionCaptureR));
-                moves.push(Move::new_special(from, to, MoveKind::PromotionCaptureB));
-                moves.push(Move::new_special(from, to, MoveKind::PromotionCaptureN));
+                push_promotions::<UNDERPROMOTIONS>(from, to, true, moves);
             } else {
                 moves.push(Move::new_special(from, to, MoveKind::Capture));
             }
@@ -308,14 +541,21 @@ pub fn generate_pawn_captures<const WHITE: bool>(board: &Board, moves: &mut Move
 /// Uses precomputed `pawn_push` and `pawn_double_push` tables. Fully branchless inside loops except for promotion handling.
 /// Generic over `const WHITE` to remove runtime color checks.
 ///
+/// # Parameters
+/// - `PUSHES: bool` — generate single and double pushes that do not promote
+/// - `PROMOTIONS: bool` — generate quiet promotions
+/// - `UNDERPROMOTIONS: bool` — also generate promotions to rook, bishop and knight, not only to queen
+///
 /// # Details
 /// - Single push only if target square empty
 /// - Double push only if both squares empty
-/// - Generates all promotions automatically
+/// - Generates promotions automatically
 #[inline(always)]
-pub fn generate_pawn_quiets<const WHITE: bool>(board: &Board, moves: &mut MoveList) {
+pub fn generate_pawn_quiets<const WHITE: bool, const PUSHES: bool, const PROMOTIONS: bool, const UNDERPROMOTIONS: bool>(board: &Board, moves: &mut MoveList) {
     let our_color = if WHITE { Color::White } else { Color::Black };
-    let mut pawns = board.piece(PieceType::Pawn) & board.color(our_color);
+    let seventh_rank = if WHITE { Bitboard(0x00FF000000000000u64) } else { Bitboard(0x000000000000FF00u64) };
+    let pawn_filter = if PUSHES { !seventh_rank } else { Bitboard(0) } | if PROMOTIONS { seventh_rank } else { Bitboard(0) };
+    let mut pawns = board.pieces_of(our_color, PieceType::Pawn) & pawn_filter;
 
     let pawn_pushes = &board.attack_tables.pawn_push[our_color];
     let pawn_double = &board.attack_tables.pawn_double_push[our_color];
@@ -333,10 +573,7 @@ pub fn generate_pawn_quiets<const WHITE: bool>(board: &Board, moves: &mut MoveLi
             attacks ^= to_bb; // pop_lsb() would re-execute lsb() internally, xoring directly is faster
 
             if promotion_rank & to_bb != Bitboard(0) {
-                moves.push(Move::new_special(from, to, MoveKind::PromotionQ));
-                moves.push(Move::new_special(from, to, MoveKind::PromotionR));
-                moves.push(Move::new_special(from, to, MoveKind::PromotionB));
-                moves.push(Move::new_special(from, to, MoveKind::PromotionN));
+                push_promotions::<UNDERPROMOTIONS>(from, to, false, moves);
             } else {
                 moves.push(Move::new_normal(from, to));
 
@@ -352,56 +589,324 @@ pub fn generate_pawn_quiets<const WHITE: bool>(board: &Board, moves: &mut MoveLi
 
 /// Generates castling moves, if possible.  
 pub fn generate_castling<const WHITE: bool>(board: &Board, moves: &mut MoveList) {
-    let rights = board.castling_rights();
     let occupancy = board.occupied_squares();
 
     if WHITE {
         // King side (e1g1)
-        if rights & WK != 0 {
-            if occupancy & (Square::F1.bb() | Square::G1.bb()) == Bitboard(0) {
-                if !board.is_square_attacked(Square::E1, Color::Black)
-                    && !board.is_square_attacked(Square::F1, Color::Black)
-                    && !board.is_square_attacked(Square::G1, Color::Black)
-                {
-                    moves.push(Move::new_special(Square::E1, Square::G1, MoveKind::KingCastle));
-                }
-            }
+        if board.can_castle_kingside(Color::White)
+            && occupancy & (Square::F1.bb() | Square::G1.bb()) == Bitboard(0)
+            && !board.is_square_attacked(Square::E1, Color::Black)
+            && !board.is_square_attacked(Square::F1, Color::Black)
+            && !board.is_square_attacked(Square::G1, Color::Black)
+        {
+            moves.push(Move::new_special(Square::E1, Square::G1, MoveKind::KingCastle));
         }
 
         // Queen side (e1c1)
-        if rights & WQ != 0 {
-            if occupancy & (Square::B1.bb() | Square::C1.bb() | Square::D1.bb()) == Bitboard(0) {
-                if !board.is_square_attacked(Square::C1, Color::Black)
-                    && !board.is_square_attacked(Square::D1, Color::Black)
-                    && !board.is_square_attacked(Square::E1, Color::Black)
-                {
-                    moves.push(Move::new_special(Square::E1, Square::C1, MoveKind::QueenCastle));
-                }
-            }
+        if board.can_castle_queenside(Color::White)
+            && occupancy & (Square::B1.bb() | Square::C1.bb() | Square::D1.bb()) == Bitboard(0)
+            && !board.is_square_attacked(Square::C1, Color::Black)
+            && !board.is_square_attacked(Square::D1, Color::Black)
+            && !board.is_square_attacked(Square::E1, Color::Black)
+        {
+            moves.push(Move::new_special(Square::E1, Square::C1, MoveKind::QueenCastle));
         }
     } else {
         // King side (e8g8)
-        if rights & BK != 0 {
-            if occupancy & (Square::F8.bb() | Square::G8.bb()) == Bitboard(0) {
-                if !board.is_square_attacked(Square::E8, Color::White)
-                    && !board.is_square_attacked(Square::F8, Color::White)
-                    && !board.is_square_attacked(Square::G8, Color::White)
-                {
-                    moves.push(Move::new_special(Square::E8, Square::G8, MoveKind::KingCastle));
-                }
-            }
+        if board.can_castle_kingside(Color::Black)
+            && occupancy & (Square::F8.bb() | Square::G8.bb()) == Bitboard(0)
+            && !board.is_square_attacked(Square::E8, Color::White)
+            && !board.is_square_attacked(Square::F8, Color::White)
+            && !board.is_square_attacked(Square::G8, Color::White)
+        {
+            moves.push(Move::new_special(Square::E8, Square::G8, MoveKind::KingCastle));
         }
 
         // Queen side (e8c8)
-        if rights & BQ != 0 {
-            if occupancy & (Square::B8.bb() | Square::C8.bb() | Square::D8.bb()) == Bitboard(0) {
-                if !board.is_square_attacked(Square::C8, Color::White)
-                    && !board.is_square_attacked(Square::D8, Color::White)
-                    && !board.is_square_attacked(Square::E8, Color::White)
-                {
-                    moves.push(Move::new_special(Square::E8, Square::C8, MoveKind::QueenCastle));
-                }
+        if board.can_castle_queenside(Color::Black)
+            && occupancy & (Square::B8.bb() | Square::C8.bb() | Square::D8.bb()) == Bitboard(0)
+            && !board.is_square_attacked(Square::C8, Color::White)
+            && !board.is_square_attacked(Square::D8, Color::White)
+            && !board.is_square_attacked(Square::E8, Color::White)
+        {
+            moves.push(Move::new_special(Square::E8, Square::C8, MoveKind::QueenCastle));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Piece;
+
+    const FENS: [&str; 5] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ];
+
+    #[test]
+    fn quiets_and_captures_split_all_moves() {
+        let mut board = Board::new();
+        for fen in FENS.into_iter().flat_map(|fen| [fen.to_string(), fen.replace(" w ", " b ")]) {
+            board.from_fen(&fen).unwrap();
+            let (mut all, mut quiets, mut captures) = (MoveList::new(), MoveList::new(), MoveList::new());
+            generate_all_moves(&board, &mut all);
+            generate_quiets(&board, &mut quiets);
+            generate_all_captures(&board, &mut captures);
+
+            assert_eq!(quiets.count() + captures.count(), all.count(), "{fen}");
+            assert!(quiets.iter().all(|m| !m.is_noisy()), "{fen}");
+            assert!(captures.iter().all(|m| m.is_noisy()), "{fen}");
+        }
+    }
+
+    /// Returns the legal moves in UCI notation, sorted.
+    fn legal_uci(board: &Board) -> Vec<String> {
+        let mut moves = MoveList::new();
+        generate_legal_moves(board, &mut moves);
+        let mut uci: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+        uci.sort();
+        uci
+    }
+
+    #[test]
+    fn double_check_only_moves_the_king() {
+        let mut board = Board::new();
+        // Rook on the e file and bishop on the a5-e1 diagonal, d2 and e2 stay attacked
+        board.from_fen("4r2k/8/8/8/1b6/8/3P4/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.checkers().popcnt(), 1);
+        board.from_fen("4r2k/8/8/8/1b6/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.checkers().popcnt(), 2);
+        assert_eq!(legal_uci(&board), ["e1d1", "e1f1", "e1f2"]);
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin_line() {
+        let mut board = Board::new();
+        board.from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        assert!(legal_uci(&board).iter().all(|m| !m.starts_with("e2")));
+
+        board.from_fen("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+        let rook_moves: Vec<String> = legal_uci(&board).into_iter().filter(|m| m.starts_with("e2")).collect();
+        assert_eq!(rook_moves, ["e2e3", "e2e4", "e2e5", "e2e6", "e2e7"]);
+    }
+
+    #[test]
+    fn single_check_captures_or_blocks() {
+        let mut board = Board::new();
+        let piece_moves = |board: &Board| -> Vec<String> { legal_uci(board).into_iter().filter(|m| !m.starts_with("e1")).collect() };
+
+        // Rook check: the bishop takes the rook or the rook blocks on e3
+        board.from_fen("4k3/8/8/8/4r3/R7/2B5/4K3 w - - 0 1").unwrap();
+        assert_eq!(piece_moves(&board), ["a3e3", "c2e4"]);
+        // No castling out of check
+        board.from_fen("4k3/8/8/8/4r3/8/2B5/R3K3 w Q - 0 1").unwrap();
+        assert_eq!(piece_moves(&board), ["c2e4"]);
+        assert!(!legal_uci(&board).contains(&"e1c1".to_string()));
+
+        // Knight check: nothing to block, the bishop takes the knight or the king moves
+        board.from_fen("4k3/8/8/8/8/3n4/2B5/R3K3 w Q - 0 1").unwrap();
+        assert_eq!(piece_moves(&board), ["c2d3"]);
+        assert_eq!(legal_uci(&board).len(), 5);
+    }
+
+    #[test]
+    fn push_and_capture_promotions() {
+        let mut board = Board::new();
+        board.from_fen("1n2k3/2P5/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotions: Vec<String> = legal_uci(&board).into_iter().filter(|m| m.starts_with("c7")).collect();
+        assert_eq!(promotions, ["c7b8b", "c7b8n", "c7b8q", "c7b8r", "c7c8b", "c7c8n", "c7c8q", "c7c8r"]);
+    }
+
+    #[test]
+    fn promotions_only() {
+        let mut board = Board::new();
+        // c7 pushes or takes on b8, f7 pushes or takes on g8, h7 pushes or takes on g8, the black a2 pawn is not to move
+        board.from_fen("1n4r1/2P2P1P/8/7k/8/8/p7/4K3 w - - 0 1").unwrap();
+        let mut moves = MoveList::new();
+        generate_promotions::<true>(&board, &mut moves);
+        assert_eq!(moves.count(), 24);
+        assert!(moves.iter().all(|m| m.is_promotion()));
+        assert_eq!(moves.iter().filter(|m| m.is_capture()).count(), 12);
+    }
+
+    #[test]
+    fn cleared_list_regenerates_the_same_moves() {
+        let mut board = Board::new();
+        let mut reused = MoveList::new();
+        for fen in FENS {
+            board.from_fen(fen).unwrap();
+            reused.clear();
+            assert_eq!(reused.count(), 0);
+            generate_all_moves(&board, &mut reused);
+
+            let mut fresh = MoveList::new();
+            generate_all_moves(&board, &mut fresh);
+            assert!(reused.iter().eq(fresh.iter()), "{fen}");
+        }
+    }
+
+    /// The piece generator calls as they were written out before `PieceGenerators`.
+    fn unrolled_piece_moves<const WHITE: bool>(board: &Board, moves: &mut MoveList) {
+        generate_moves::<Caval, WHITE, false>(board, moves);
+        generate_moves::<Caval, WHITE, true>(board, moves);
+        generate_moves::<Re, WHITE, false>(board, moves);
+        generate_moves::<Re, WHITE, true>(board, moves);
+        generate_moves::<Alfè, WHITE, false>(board, moves);
+        generate_moves::<Alfè, WHITE, true>(board, moves);
+        generate_moves::<Tor, WHITE, false>(board, moves);
+        generate_moves::<Tor, WHITE, true>(board, moves);
+        generate_moves::<Argina, WHITE, false>(board, moves);
+        generate_moves::<Argina, WHITE, true>(board, moves);
+    }
+
+    /// Walks the legal move tree to `depth`, checking at every node that both piece generators give the same moves in
+    /// the same order, and returns the number of leaves.
+    fn perft_piece_generators(board: &mut Board, depth: u32) -> u64 {
+        let (mut looped, mut unrolled) = (MoveList::new(), MoveList::new());
+        if board.side_to_move() == Color::White {
+            generate_piece_moves::<true>(board, &mut looped);
+            unrolled_piece_moves::<true>(board, &mut unrolled);
+        } else {
+            generate_piece_moves::<false>(board, &mut looped);
+            unrolled_piece_moves::<false>(board, &mut unrolled);
+        }
+        assert!(looped.iter().eq(unrolled.iter()), "{}", board.to_fen());
+        if depth == 0 {
+            return 1;
+        }
+
+        let mut moves = MoveList::new();
+        generate_legal_moves(board, &mut moves);
+        let mut nodes = 0;
+        for m in moves.iter() {
+            board.make_move(m);
+            nodes += perft_piece_generators(board, depth - 1);
+            board.unmake_move(m);
+        }
+        nodes
+    }
+
+    #[test]
+    fn piece_generators_match_the_unrolled_calls() {
+        const NODES: [u64; 5] = [8902, 97862, 2812, 9467, 62379];
+        let mut board = Board::new();
+        for (fen, nodes) in FENS.into_iter().zip(NODES) {
+            board.from_fen(fen).unwrap();
+            assert_eq!(perft_piece_generators(&mut board, 3), nodes, "{fen}");
+        }
+    }
+
+    /// Checks the underpromotion toggle of `generate_promotions`: the pawn on a7 can promote on a8 and by capturing on b8,
+    /// which gives 4 moves per promotion square with underpromotions and only the queen promotion without.
+    #[test]
+    fn underpromotion_toggle() {
+        let mut board = Board::new();
+        board.from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+
+        let mut all = MoveList::new();
+        generate_promotions::<true>(&board, &mut all);
+        let mut queens = MoveList::new();
+        generate_promotions::<false>(&board, &mut queens);
+
+        assert_eq!(all.count(), 8);
+        assert_eq!(all.iter().filter(|m| m.is_underpromotion()).count(), 6);
+        assert_eq!(queens.count(), 2);
+        assert!(queens.iter().all(|m| !m.is_underpromotion()));
+    }
+
+    /// Checks that the quiet check generator emits the only quiet checking move, a knight check, and not the capture
+    /// available to the same knight.
+    #[test]
+    fn quiet_checks_skip_captures() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/N7/8/1p6/8/8/4K3 w - - 0 1").unwrap();
+
+        let mut checks = MoveList::new();
+        generate_quiet_checks(&board, &mut checks);
+        assert_eq!(checks.iter().collect::<Vec<_>>(), [Move::new_normal(Square::A6, Square::C7)]);
+    }
+
+    /// Checks that repeatedly picking the best move, and sorting, both yield the moves of a position by non-increasing
+    /// score, without losing or duplicating any.
+    #[test]
+    fn pick_best_and_sort_order_by_score() {
+        let mut board = Board::new();
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut generated = MoveList::new();
+        generate_all_moves(&board, &mut generated);
+        let score_of = |m: Move| (m.to() as i32 * 7 + m.from() as i32) % 23 - 11; // Arbitrary, with ties and negatives
+
+        let mut picked = MoveList::new();
+        generate_all_moves(&board, &mut picked);
+        let mut scores: Vec<i32> = picked.iter().map(score_of).collect();
+        let picks: Vec<Move> = (0..picked.count()).map(|i| picked.pick_best(&mut scores, i)).collect();
+
+        let mut sorted = MoveList::new();
+        generate_all_moves(&board, &mut sorted);
+        let mut sorted_scores: Vec<i32> = sorted.iter().map(score_of).collect();
+        sorted.sort_by_scores(&mut sorted_scores);
+
+        for order in [picks, sorted.iter().collect()] {
+            assert!(order.windows(2).all(|pair| score_of(pair[0]) >= score_of(pair[1])));
+            let mut expected: Vec<String> = generated.iter().map(|m| m.to_string()).collect();
+            let mut actual: Vec<String> = order.iter().map(|m| m.to_string()).collect();
+            expected.sort_unstable();
+            actual.sort_unstable();
+            assert_eq!(actual, expected);
+        }
+        for (m, &score) in sorted.iter().zip(&sorted_scores) {
+            assert_eq!(score_of(m), score);
+        }
+    }
+
+    /// Checks the promotion captures of a white pawn on b7 taking a rook on a8 or c8: all eight must be generated with the
+    /// flag of their promoted piece, and making each must remove the rook, place the promoted piece and keep the hash in
+    /// sync, while unmaking restores the position.
+    #[test]
+    fn promotion_captures_make_and_unmake() {
+        const FEN: &str = "r1r1k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+        let mut board = Board::new();
+        board.from_fen(FEN).unwrap();
+        let mut moves = MoveList::new();
+        generate_all_moves(&board, &mut moves);
+        let captures: Vec<Move> = moves.iter().filter(|m| m.from() == Square::B7 && m.is_capture()).collect();
+        assert_eq!(captures.len(), 8);
+
+        for to in [Square::A8, Square::C8] {
+            for (kind, promoted) in [
+                (MoveKind::PromotionCaptureN, PieceType::Knight),
+                (MoveKind::PromotionCaptureB, PieceType::Bishop),
+                (MoveKind::PromotionCaptureR, PieceType::Rook),
+                (MoveKind::PromotionCaptureQ, PieceType::Queen),
+            ] {
+                let m = Move::new_special(Square::B7, to, kind);
+                assert_eq!(captures.iter().filter(|&&c| c == m).count(), 1, "{m}");
+                assert!(m.is_promotion());
+                assert!(m.promotion_piece() == promoted);
+                board.make_move(m);
+                assert_eq!(board.piece_on(to), Some(Piece::new(Color::White, promoted)));
+                assert_eq!(board.piece_on(Square::B7), None);
+                assert_eq!(board.piece_count(PieceType::Rook, Color::Black), 1);
+                assert_eq!(board.piece_count(PieceType::Pawn, Color::White), 0);
+                assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+                board.unmake_move(m);
+                assert_eq!(board.to_fen(), FEN);
             }
         }
     }
+
+    /// Checks that `contains` finds the generated moves only: not the null move, nor a move of another position.
+    #[test]
+    fn contains_only_generated_moves() {
+        let mut board = Board::new();
+        board.from_fen(FENS[1]).unwrap();
+        let moves = board.pseudo_legal_moves();
+        assert!(moves.iter().all(|m| moves.contains(m)));
+        assert!(!moves.contains(Move::NULL_MOVE));
+        assert!(!moves.contains(Move::new_normal(Square::A2, Square::A5)));
+    }
 }
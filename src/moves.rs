@@ -41,10 +41,39 @@ pub enum MoveKind {
     PromotionCaptureQ = 0b1111,
 }
 
+impl MoveKind {
+    /// Every move kind, in encoding order.
+    #[rustfmt::skip]
+    pub const ALL: [MoveKind; 14] = [
+        MoveKind::Normal, MoveKind::DoublePush, MoveKind::KingCastle, MoveKind::QueenCastle,
+        MoveKind::Capture, MoveKind::EnPassant,
+        MoveKind::PromotionN, MoveKind::PromotionB, MoveKind::PromotionR, MoveKind::PromotionQ,
+        MoveKind::PromotionCaptureN, MoveKind::PromotionCaptureB, MoveKind::PromotionCaptureR, MoveKind::PromotionCaptureQ,
+    ];
+
+    /// Decodes the 4 flag bits of a move, `None` for the two unused values `0b0110` and `0b0111`.
+    pub const fn from_bits(bits: u8) -> Option<MoveKind> {
+        match bits {
+            0b0000..=0b0101 => Some(Self::ALL[bits as usize]),
+            0b1000..=0b1111 => Some(Self::ALL[bits as usize - 2]),
+            _ => None,
+        }
+    }
+}
+
 impl Move {
     /// Null-move definition required for null-move pruning.
+    ///
+    /// Its encoding is 0, the same as a normal move from a1 to a1, which no generator can produce. It also stands for
+    /// "no move" in the TT, the killers and the PV.
     pub const NULL_MOVE: Move = Move { encoding: 0 };
 
+    /// Returns true if this is `NULL_MOVE`.
+    #[inline(always)]
+    pub const fn is_null(self) -> bool {
+        self.encoding == 0
+    }
+
     /// Encodes a "normal" move.
     pub const fn new_normal(from: Square, to: Square) -> Self {
         Self { encoding: (from as u16) | ((to as u16) << 6) }
@@ -57,6 +86,41 @@ impl Move {
         }
     }
 
+    /// Encodes a move from raw fields, as read from an external format.
+    ///
+    /// Debug builds check that the squares are below 64 and that `flags` is a valid `MoveKind`.
+    pub const fn new_validated(from: u8, to: u8, flags: u8) -> Self {
+        debug_assert!(from < 64 && to < 64, "Move square out of range");
+        debug_assert!(MoveKind::from_bits(flags).is_some(), "Invalid move kind");
+        Self {
+            encoding: (from as u16 & 0x3F) | ((to as u16 & 0x3F) << 6) | ((flags as u16 & 0xF) << 12),
+        }
+    }
+
+    /// Rebuilds a move from its 16-bit encoding, as returned by `to_bits`.
+    ///
+    /// Squares always fit in their 6 bits, but the flags may not be a valid `MoveKind` in a corrupted encoding: `None`
+    /// then, rather than a move that `kind` could not decode.
+    #[inline(always)]
+    pub const fn from_bits(bits: u16) -> Option<Self> {
+        if MoveKind::from_bits((bits >> 12) as u8).is_some() { Some(Self { encoding: bits }) } else { None }
+    }
+
+    /// Returns the 16-bit encoding, to persist the move.
+    #[inline(always)]
+    pub const fn to_bits(self) -> u16 {
+        self.encoding
+    }
+
+    /// Returns the kind of the move.
+    #[inline(always)]
+    pub const fn kind(self) -> MoveKind {
+        match MoveKind::from_bits((self.encoding >> 12) as u8) {
+            Some(kind) => kind,
+            None => unreachable!(),
+        }
+    }
+
     /// Returns the origin square.
     #[inline(always)]
     pub const fn from(self) -> Square {
@@ -81,6 +145,12 @@ impl Move {
         (self.encoding & 0x8000) != 0
     }
 
+    /// Checks whether the move promotes to a rook, bishop or knight.
+    #[inline(always)]
+    pub const fn is_underpromotion(self) -> bool {
+        self.is_promotion() && (self.encoding & 0x3000) != 0x3000
+    }
+
     /// Checks whether the move is quiet.
     #[inline(always)]
     #[allow(dead_code)]
@@ -150,3 +220,37 @@ impl fmt::Display for Move {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that every `MoveKind` round-trips through the 16-bit encoding, on corner squares, and that the unused flag
+    /// values are rejected.
+    #[test]
+    fn move_kinds_round_trip_through_bits() {
+        for kind in MoveKind::ALL {
+            for (from, to) in [(Square::A1, Square::H8), (Square::H8, Square::A1), (Square::E2, Square::E4)] {
+                let m = Move::new_special(from, to, kind);
+                let back = Move::from_bits(m.to_bits()).unwrap();
+                assert_eq!(back, m);
+                assert_eq!((back.kind(), back.from(), back.to()), (kind, from, to));
+                assert_eq!(Move::new_validated(from as u8, to as u8, kind as u8), m);
+                assert_eq!(MoveKind::from_bits(kind as u8), Some(kind));
+            }
+        }
+        assert_eq!(MoveKind::from_bits(0b0110), None);
+        assert_eq!(MoveKind::from_bits(0b0111), None);
+        assert_eq!(MoveKind::from_bits(0b1_0000), None);
+    }
+
+    /// Checks that encodings with an unused flag value, as a corrupted TT entry or file could hold, are refused by
+    /// `from_bits` instead of panicking later in `kind`.
+    #[test]
+    fn corrupted_move_bits_are_refused() {
+        let e2e4 = Move::new_special(Square::E2, Square::E4, MoveKind::DoublePush).to_bits() & 0x0FFF;
+        assert_eq!(Move::from_bits(e2e4 | (0b0110 << 12)), None);
+        assert_eq!(Move::from_bits(e2e4 | (0b0111 << 12)), None);
+        assert_eq!(Move::from_bits(e2e4), Some(Move::new_normal(Square::E2, Square::E4)));
+    }
+}
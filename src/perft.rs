@@ -4,14 +4,16 @@
 //! validate move generation correctness by counting the number of leaf nodes reachable
 //! from a given position at a specified search depth.
 //!
-//! Perft works by recursively generating pseudo-legal moves, filtering illegal moves
-//! by checking king safety after each move, and summing the number of reachable nodes.
+//! Perft works by recursively generating legal moves and summing the number of reachable nodes.
+//! Illegal moves are filtered by the legal generator, which only tests king safety for king moves,
+//! en passant, pinned pieces and check evasions.
 
 use std::time::Instant;
 
 use crate::board::Board;
 use crate::movegen::MoveList;
 use crate::movegen::generate_all_moves;
+use crate::movegen::generate_legal_moves;
 
 #[allow(dead_code)]
 pub fn benchmark_perft(depth: u64) {
@@ -60,26 +62,180 @@ pub fn benchmark_perft(depth: u64) {
     println!("Avg NPS: {:.2} nodes/sec", nps);
 }
 
+/// Counts the leaf nodes of the legal move tree to `depth`.
+///
+/// Bulk counting: at depth 1 the legal moves are counted without being made, since each would only return 1.
 pub fn perft(board: &mut Board, depth: u64) -> u64 {
     if depth == 0 {
         return 1;
     }
 
     let mut list = MoveList::new();
-    generate_all_moves(board, &mut list);
+    generate_legal_moves(board, &mut list);
+    if depth == 1 {
+        return list.count() as u64;
+    }
+
+    let mut nodes = 0;
+
+    for m in list.iter() {
+        board.make_move(m);
+        nodes += perft(board, depth - 1);
+        board.unmake_move(m);
+    }
+
+    nodes
+}
+
+/// Perft without bulk counting, making and unmaking every move down to depth 0. Slower than `perft`, kept to check
+/// that the make/unmake of the leaf moves is sound.
+pub fn perft_full(board: &mut Board, depth: u64) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let mut list = MoveList::new();
+    generate_legal_moves(board, &mut list);
 
     let mut nodes = 0;
 
     for m in list.iter() {
         board.make_move(m);
+        nodes += perft_full(board, depth - 1);
+        board.unmake_move(m);
+    }
+
+    nodes
+}
+
+/// Perft split by root move, as (move in UCI notation, nodes below it).
+pub fn perft_divide(board: &mut Board, depth: u64) -> Vec<(String, u64)> {
+    let mut list = MoveList::new();
+    generate_legal_moves(board, &mut list);
+
+    let mut divide = Vec::new();
+    for m in list.iter() {
+        board.make_move(m);
+        divide.push((m.to_string(), perft(board, depth.saturating_sub(1))));
+        board.unmake_move(m);
+    }
+    divide
+}
+
+/// Perft that treats positions drawn by repetition or the fifty-move rule as leaves, as the search does.
+///
+/// A position below the root that repeats an earlier one, or whose halfmove clock reached 100, counts as one node and
+/// is not expanded. The root itself is always expanded. The board history is kept by make/unmake, so repetitions of
+/// positions played before the root are seen too.
+pub fn perft_no_draws(board: &mut Board, depth: u64) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let mut list = MoveList::new();
+    generate_legal_moves(board, &mut list);
+
+    let mut nodes = 0;
+    for m in list.iter() {
+        board.make_move(m);
+        nodes += if board.is_repetition() || board.is_fifty_move_draw() { 1 } else { perft_no_draws(board, depth - 1) };
+        board.unmake_move(m);
+    }
+    nodes
+}
+
+/// A root move whose count differs from the reference, `None` when the move is missing on one side.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PerftMismatch {
+    pub mv: String,
+    pub nodes: Option<u64>,
+    pub reference: Option<u64>,
+}
+
+/// Runs `perft_divide` on `fen` and returns the root moves that disagree with `reference`, e.g. the divide output of
+/// another engine. Moves missing from either side are reported too, so an empty result means the counts match.
+pub fn perft_compare(fen: &str, depth: u64, reference: &[(String, u64)]) -> Result<Vec<PerftMismatch>, &'static str> {
+    let mut board = Board::new();
+    board.from_fen(fen)?;
+    let divide = perft_divide(&mut board, depth);
 
-        if !board.king_in_check(!board.side_to_move()) {
-            nodes += perft(board, depth - 1);
+    let lookup = |list: &[(String, u64)], mv: &str| list.iter().find(|(m, _)| m == mv).map(|&(_, nodes)| nodes);
+    let mut mismatches = Vec::new();
+    for (mv, nodes) in &divide {
+        let expected = lookup(reference, mv);
+        if expected != Some(*nodes) {
+            mismatches.push(PerftMismatch { mv: mv.clone(), nodes: Some(*nodes), reference: expected });
+        }
+    }
+    for (mv, nodes) in reference {
+        if lookup(&divide, mv).is_none() {
+            mismatches.push(PerftMismatch { mv: mv.clone(), nodes: None, reference: Some(*nodes) });
         }
+    }
+    Ok(mismatches)
+}
+
+/// Fixed-size cache of perft subtree counts, keyed on (Zobrist key, depth).
+///
+/// Deep perft reaches the same positions through different move orders many times. The table is a power-of-two array
+/// of entries with always-replace semantics, so memory stays bounded regardless of the depth.
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+    mask: usize,
+}
 
+#[derive(Copy, Clone, Default)]
+struct PerftEntry {
+    key: u64,
+    depth: u64,
+    nodes: u64,
+}
+
+impl PerftTable {
+    /// Creates a table using at most `size_mb` megabytes.
+    pub fn new(size_mb: usize) -> Self {
+        let max_entries = (size_mb.max(1) << 20) / size_of::<PerftEntry>();
+        let len = 1usize << max_entries.ilog2(); // Round down to a power of two
+        Self { entries: vec![PerftEntry::default(); len], mask: len - 1 }
+    }
+
+    #[inline(always)]
+    fn probe(&self, key: u64, depth: u64) -> Option<u64> {
+        let entry = self.entries[key as usize & self.mask];
+        (entry.key == key && entry.depth == depth).then_some(entry.nodes)
+    }
+
+    #[inline(always)]
+    fn store(&mut self, key: u64, depth: u64, nodes: u64) {
+        self.entries[key as usize & self.mask] = PerftEntry { key, depth, nodes };
+    }
+}
+
+/// Perft with a cache of subtree counts, see `PerftTable`.
+///
+/// Returns the same counts as `perft`, as long as no two positions reached share a Zobrist key.
+pub fn perft_hashed(board: &mut Board, depth: u64, table: &mut PerftTable) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let key = board.zobrist_key();
+    if let Some(nodes) = table.probe(key, depth) {
+        return nodes;
+    }
+
+    let mut list = MoveList::new();
+    generate_legal_moves(board, &mut list);
+
+    let mut nodes = 0;
+
+    for m in list.iter() {
+        board.make_move(m);
+        nodes += perft_hashed(board, depth - 1, table);
         board.unmake_move(m);
     }
 
+    table.store(key, depth, nodes);
     nodes
 }
 
@@ -112,3 +268,317 @@ pub fn kiwipete(depth: u64) {
         println!("perft({}): {}", depth, nodes);
     }
 }
+
+/// Leaf move counts broken down by kind, as in the extended perft tables.
+///
+/// Captures include en passant, checks include checkmates. Stalemates are counted among the quiet, non-checking leaves,
+/// which costs making every last-ply move, so this is much slower than `perft`.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub struct PerftStats {
+    pub nodes: u64,
+    pub captures: u64,
+    pub en_passant: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    pub checkmates: u64,
+    pub stalemates: u64,
+}
+
+impl std::ops::AddAssign for PerftStats {
+    fn add_assign(&mut self, other: Self) {
+        self.nodes += other.nodes;
+        self.captures += other.captures;
+        self.en_passant += other.en_passant;
+        self.castles += other.castles;
+        self.promotions += other.promotions;
+        self.checks += other.checks;
+        self.checkmates += other.checkmates;
+        self.stalemates += other.stalemates;
+    }
+}
+
+/// Perft that classifies the moves made at the last ply, to pinpoint which kind of move generation is wrong.
+pub fn perft_stats(board: &mut Board, depth: u64) -> PerftStats {
+    let mut stats = PerftStats::default();
+    if depth == 0 {
+        stats.nodes = 1;
+        return stats;
+    }
+
+    let mut list = MoveList::new();
+    generate_legal_moves(board, &mut list);
+
+    for m in list.iter() {
+        if depth == 1 {
+            stats.nodes += 1;
+            stats.captures += (m.is_capture() || m.is_enpassant()) as u64;
+            stats.en_passant += m.is_enpassant() as u64;
+            stats.castles += m.is_castling() as u64;
+            stats.promotions += m.is_promotion() as u64;
+            stats.checks += board.gives_check(m) as u64;
+            board.make_move(m);
+            stats.checkmates += board.is_checkmate() as u64;
+            stats.stalemates += board.is_stalemate() as u64;
+            board.unmake_move(m);
+        } else {
+            board.make_move(m);
+            stats += perft_stats(board, depth - 1);
+            board.unmake_move(m);
+        }
+    }
+
+    stats
+}
+
+/// Moves of the legal generator missing from the make/unmake-filtered pseudo-legal moves, and the other way round,
+/// both in UCI notation.
+pub fn move_set_difference(board: &mut Board) -> (Vec<String>, Vec<String>) {
+    let mut legal = MoveList::new();
+    generate_legal_moves(board, &mut legal);
+    let legal: Vec<String> = legal.iter().map(|m| m.to_string()).collect();
+
+    // Trusted baseline: every pseudo-legal move that does not leave the own king attacked once made
+    let mut pseudo = MoveList::new();
+    generate_all_moves(board, &mut pseudo);
+    let us = board.side_to_move();
+    let mut filtered = Vec::new();
+    for m in pseudo.iter() {
+        board.make_move(m);
+        if !board.king_in_check(us) {
+            filtered.push(m.to_string());
+        }
+        board.unmake_move(m);
+    }
+
+    let only_legal = legal.iter().filter(|m| !filtered.contains(m)).cloned().collect();
+    let only_filtered = filtered.iter().filter(|m| !legal.contains(m)).cloned().collect();
+    (only_legal, only_filtered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::generate_all_moves;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    /// The six positions of the usual perft suite, as (FEN, depth, nodes).
+    #[rustfmt::skip]
+    const PERFT_SUITE: [(&str, u64, u64); 6] = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 3, 8902),
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3, 97862),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+        ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3, 9467),
+        ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3, 62379),
+        ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", 3, 89890),
+    ];
+
+    /// Perft filtering pseudo-legal moves by making them and testing the king, without the shortcuts of the legal
+    /// generator.
+    fn perft_naive(board: &mut Board, depth: u64) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let mut list = MoveList::new();
+        generate_all_moves(board, &mut list);
+
+        let mut nodes = 0;
+        for m in list.iter() {
+            board.make_move(m);
+            if !board.king_in_check(!board.side_to_move()) {
+                nodes += perft_naive(board, depth - 1);
+            }
+            board.unmake_move(m);
+        }
+        nodes
+    }
+
+    #[test]
+    fn legal_generator_matches_naive_filter() {
+        let mut board = Board::new();
+        for (fen, depth, nodes) in PERFT_SUITE {
+            board.from_fen(fen).unwrap();
+            assert_eq!(perft(&mut board, depth), nodes, "{fen}");
+            assert_eq!(perft_naive(&mut board, depth), nodes, "{fen}");
+        }
+    }
+
+    #[test]
+    fn hashed_perft_matches_perft() {
+        let mut board = Board::new();
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut table = PerftTable::new(16);
+        assert_eq!(perft_hashed(&mut board, 3, &mut table), perft(&mut board, 3));
+        assert_eq!(perft_hashed(&mut board, 3, &mut table), 97862); // Now answered from the table
+    }
+
+    /// Depth 6 of a rook ending with castling rights, small enough for a debug build while the table sees many
+    /// transpositions, castling included.
+    #[test]
+    fn hashed_perft_matches_perft_depth_6() {
+        let mut board = Board::new();
+        board.from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let mut table = PerftTable::new(16);
+        assert_eq!(perft_hashed(&mut board, 6, &mut table), 661_072);
+        assert_eq!(perft(&mut board, 6), 661_072);
+    }
+
+    /// Castling positions and the castling moves the side to move must have, as (FEN, moves).
+    const CASTLING_CASES: [(&str, &[&str]); 4] = [
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &["e1c1", "e1g1"]), // Nothing in the way
+        ("r3k2r/8/8/8/8/8/8/RN2K2R w KQkq - 0 1", &["e1g1"]),        // Piece on b1, only the rook path is blocked
+        ("r3k2r/8/8/8/8/8/5r2/R3K2R w KQ - 0 1", &["e1c1"]),         // f1 attacked, the king would cross it
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", &["e8c8", "e8g8"]),
+    ];
+
+    /// Castling is generated exactly when the rook path is empty and the king path is not attacked. Locks the current
+    /// behavior of `generate_castling`.
+    #[test]
+    fn castling_generation() {
+        let mut board = Board::new();
+        for (fen, expected) in CASTLING_CASES {
+            board.from_fen(fen).unwrap();
+            let mut list = MoveList::new();
+            generate_legal_moves(&board, &mut list);
+            let mut castles: Vec<String> = list.iter().filter(|m| m.is_castling()).map(|m| m.to_string()).collect();
+            castles.sort();
+            assert_eq!(castles, expected, "{fen}");
+        }
+    }
+
+    /// Published breakdowns, as (FEN, depth, nodes, captures, en passant, castles, promotions, checks, checkmates,
+    /// stalemates).
+    #[rustfmt::skip]
+    const PERFT_STATS_CASES: [(&str, u64, [u64; 8]); 4] = [
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 1, [48, 8, 0, 2, 0, 0, 0, 0]),
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 2, [2039, 351, 1, 91, 0, 3, 0, 0]),
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3, [97862, 17102, 45, 3162, 0, 993, 1, 0]),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 4, [197281, 1576, 0, 0, 0, 469, 8, 0]),
+    ];
+
+    #[test]
+    fn perft_stats_match_published_breakdowns() {
+        let mut board = Board::new();
+        for (fen, depth, [nodes, captures, en_passant, castles, promotions, checks, checkmates, stalemates]) in PERFT_STATS_CASES {
+            board.from_fen(fen).unwrap();
+            let expected = PerftStats {
+                nodes,
+                captures,
+                en_passant,
+                castles,
+                promotions,
+                checks,
+                checkmates,
+                stalemates,
+            };
+            assert_eq!(perft_stats(&mut board, depth), expected, "{fen} at depth {depth}");
+        }
+    }
+
+    /// Checks that `perft_compare` flags exactly the root move whose reference count was tampered with.
+    #[test]
+    fn perft_compare_flags_the_tampered_move() {
+        const FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let mut board = Board::new();
+        board.from_fen(FEN).unwrap();
+        let mut reference = perft_divide(&mut board, 3);
+        assert_eq!(perft_compare(FEN, 3, &reference), Ok(vec![]));
+
+        let tampered = reference.iter().position(|(mv, _)| mv == "e2e4").unwrap();
+        reference[tampered].1 += 1;
+        let expected = PerftMismatch {
+            mv: "e2e4".to_string(),
+            nodes: Some(reference[tampered].1 - 1),
+            reference: Some(reference[tampered].1),
+        };
+        assert_eq!(perft_compare(FEN, 3, &reference), Ok(vec![expected]));
+    }
+
+    /// Panics, listing the symmetric difference in UCI notation, if the legal generator and the make/unmake-filtered
+    /// pseudo-legal moves disagree on `board`.
+    fn assert_move_sets_agree(board: &mut Board) {
+        let (only_legal, only_filtered) = move_set_difference(board);
+        assert!(
+            only_legal.is_empty() && only_filtered.is_empty(),
+            "Move sets disagree on {}: only legal generator {:?}, only make/unmake filter {:?}",
+            board.to_fen(),
+            only_legal,
+            only_filtered
+        );
+    }
+
+    /// Plays seeded random games from the starting position and from Kiwipete, checking after every move that the legal
+    /// generator agrees with the make/unmake filter. A disagreement panics with the differing moves.
+    #[test]
+    fn move_sets_agree_in_random_games() {
+        const GAMES: usize = 200;
+        const MAX_PLIES: usize = 200;
+        let mut rng = SmallRng::seed_from_u64(0x6A3E5);
+        let mut board = Board::new();
+
+        for game in 0..GAMES {
+            if game % 2 == 0 {
+                board.set_startpos();
+            } else {
+                board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+            }
+            for _ in 0..MAX_PLIES {
+                assert_move_sets_agree(&mut board);
+
+                let mut legal = MoveList::new();
+                generate_legal_moves(&board, &mut legal);
+                if legal.count() == 0 || board.is_fifty_move_draw() {
+                    break;
+                }
+                board.make_move(legal.get(rng.random_range(0..legal.count())));
+            }
+        }
+    }
+
+    /// Checks `perft_no_draws` against `perft`: equal where no draw can occur within the depth, fewer nodes where knights
+    /// can shuffle back to a repeated position, or where the first quiet move ends the fifty moves.
+    #[test]
+    fn perft_no_draws_stops_at_draws() {
+        let mut board = Board::new();
+        board.set_startpos();
+        assert_eq!(perft_no_draws(&mut board, 3), perft(&mut board, 3));
+
+        for fen in ["n3k3/8/8/8/8/8/8/N3K3 w - - 0 1", "4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80"] {
+            board.from_fen(fen).unwrap();
+            let key = board.zobrist_key();
+            let (plain, no_draws) = (perft(&mut board, 5), perft_no_draws(&mut board, 5));
+            assert!(no_draws < plain, "{fen}");
+            assert_eq!(board.zobrist_key(), key);
+        }
+    }
+
+    /// Checks the terminal counts of `perft_stats` one move from the end: with a back rank weakness, Ra8, Qd8 and Re8
+    /// are the only checks and all mate, and against a cornered king Qb6 and Qc7 are the only stalemates.
+    #[test]
+    fn perft_stats_count_mates_and_stalemates() {
+        let mut board = Board::new();
+        board.from_fen("6k1/5ppp/8/8/8/8/8/R2QR1K1 w - - 0 1").unwrap();
+        let stats = perft_stats(&mut board, 1);
+        assert_eq!((stats.checks, stats.checkmates, stats.stalemates), (3, 3, 0));
+
+        board.from_fen("k7/8/8/2Q5/8/8/8/K7 w - - 0 1").unwrap();
+        let stats = perft_stats(&mut board, 1);
+        assert_eq!((stats.nodes, stats.checkmates, stats.stalemates), (28, 0, 2));
+    }
+
+    /// Checks that bulk counting changes nothing: `perft` and `perft_full` must both give the published counts of the
+    /// suite, at every depth up to the listed one.
+    #[test]
+    fn bulk_counting_matches_full_perft() {
+        let mut board = Board::new();
+        for (fen, depth, nodes) in PERFT_SUITE {
+            board.from_fen(fen).unwrap();
+            for d in 1..=depth {
+                assert_eq!(perft(&mut board, d), perft_full(&mut board, d), "{fen} at depth {d}");
+            }
+            assert_eq!(perft(&mut board, depth), nodes, "{fen}");
+        }
+    }
+}
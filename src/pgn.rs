@@ -0,0 +1,177 @@
+//! Portable Game Notation (PGN) import.
+//!
+//! Only what replaying a game needs is read: the `FEN` tag for games not starting from the initial position, and the
+//! SAN moves of the main line. Comments, variations, move numbers, NAGs and the result are skipped. Games replayed
+//! this way are the source of test and tuning positions, see `pgn_to_epd`.
+
+use crate::board::Board;
+use crate::epd::to_epd;
+use crate::moves::Move;
+
+/// Loads the starting position of the game into `board` and returns the main line moves, checked for legality.
+///
+/// The board is left in the starting position, also when a move is illegal.
+pub fn read_pgn(board: &mut Board, pgn: &str) -> Result<Vec<Move>, &'static str> {
+    let mut fen = None;
+    let mut movetext = String::new();
+    for line in pgn.lines().map(str::trim) {
+        if let Some(tag) = line.strip_prefix('[') {
+            let (name, value) = tag.trim_end_matches(']').split_once(char::is_whitespace).ok_or("Malformed PGN tag")?;
+            if name == "FEN" {
+                fen = Some(value.trim().trim_matches('"').to_string());
+            }
+        } else if !line.starts_with('%') {
+            movetext += line;
+            movetext.push('\n');
+        }
+    }
+    match fen {
+        Some(fen) => board.set_fen(&fen)?,
+        None => board.set_startpos(),
+    }
+
+    let mut moves = Vec::new();
+    let mut result = Ok(());
+    for token in movetext_tokens(&movetext)? {
+        match board.parse_san(token) {
+            Ok(m) => {
+                board.make_move(m);
+                moves.push(m);
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    for &m in moves.iter().rev() {
+        board.unmake_move(m);
+    }
+    result.map(|_| moves)
+}
+
+/// Returns the SAN moves of the main line, without comments, variations, move numbers, NAGs and the result.
+fn movetext_tokens(movetext: &str) -> Result<Vec<&str>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut depth = 0; // Nesting of variations
+    let mut rest = movetext;
+    while let Some(ch) = rest.chars().next() {
+        match ch {
+            '{' => rest = &rest[rest.find('}').ok_or("Unterminated PGN comment")? + 1..],
+            ';' => rest = rest.find('\n').map_or("", |end| &rest[end..]),
+            '(' => {
+                depth += 1;
+                rest = &rest[1..];
+            }
+            ')' => {
+                depth -= 1;
+                if depth < 0 {
+                    return Err("Unbalanced PGN variation");
+                }
+                rest = &rest[1..];
+            }
+            _ if ch.is_whitespace() => rest = &rest[ch.len_utf8()..],
+            _ => {
+                let end = rest.find(|c: char| c.is_whitespace() || "{}();".contains(c)).unwrap_or(rest.len());
+                let token = &rest[..end];
+                let token = token.rsplit_once('.').map_or(token, |(_, san)| san); // Move number, maybe glued to the move
+                let skipped = token.is_empty() || token.starts_with('$') || matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*");
+                if depth == 0 && !skipped {
+                    tokens.push(token);
+                }
+                rest = &rest[end..];
+            }
+        }
+    }
+    if depth != 0 {
+        return Err("Unbalanced PGN variation");
+    }
+    Ok(tokens)
+}
+
+/// Returns a sampler keeping every `n`th ply from `first_ply` on, as in "every 4th ply after move 10".
+pub fn every_nth_ply(n: usize, first_ply: usize) -> impl Fn(usize) -> bool {
+    move |ply| ply >= first_ply && (ply - first_ply).is_multiple_of(n)
+}
+
+/// Replays a PGN and returns an EPD line for each position `sample` keeps, given the number of plies played.
+///
+/// With `best_move`, the move played from the position is recorded as its `bm` operation; the final position of the
+/// game is then never sampled, as no move was played from it.
+pub fn pgn_to_epd(pgn: &str, sample: impl Fn(usize) -> bool, best_move: bool) -> Result<Vec<String>, &'static str> {
+    let mut board = Board::new();
+    let moves = read_pgn(&mut board, pgn)?;
+
+    let mut lines = Vec::new();
+    for ply in 0..=moves.len() {
+        let played = moves.get(ply).copied();
+        if sample(ply) && (played.is_some() || !best_move) {
+            let line = match played {
+                Some(m) if best_move => {
+                    let san = board.to_san(m);
+                    to_epd(&board, &[("bm", &san)])
+                }
+                _ => to_epd(&board, &[]),
+            };
+            lines.push(line);
+        }
+        if let Some(m) = played {
+            board.make_move(m);
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epd::parse_epd;
+
+    const OPERA_GAME: &str = "[Event \"Paris\"]\n[White \"Paul Morphy\"]\n[Black \"Duke Karl / Count Isouard\"]\n\n\
+        1.e4 e5 2.Nf3 d6 {Philidor Defence} 3.d4 Bg4 (3...exd4 4.Nxd4) 4.dxe5 Bxf3 5.Qxf3 dxe5 6.Bc4 Nf6 7.Qb3 Qe7\n\
+        8.Nc3 c6 9.Bg5 b5 $6 10.Nxb5 cxb5 11.Bxb5+ Nbd7 12.O-O-O Rd8 13.Rxd7 Rxd7 14.Rd1 Qe6 15.Bxd7+ Nxd7 16.Qb8+ Nxb8\n\
+        17.Rd8# 1-0";
+
+    /// The Opera game has a comment, a variation and a NAG to skip. Every line sampled from move 11 on must parse back
+    /// into the same position, with the played move as `bm`, including the `Nbd7` disambiguation, queenside castling
+    /// and the final mate.
+    #[test]
+    fn pgn_to_epd_lines_parse_back() {
+        const EXPECTED: [&str; 13] = ["Bxb5+", "Nbd7", "O-O-O", "Rd8", "Rxd7", "Rxd7", "Rd1", "Qe6", "Bxd7+", "Nxd7", "Qb8+", "Nxb8", "Rd8#"];
+
+        let lines = pgn_to_epd(OPERA_GAME, every_nth_ply(1, 20), true).unwrap();
+        assert_eq!(lines.len(), EXPECTED.len());
+        let mut board = Board::new();
+        for (line, expected) in lines.iter().zip(EXPECTED) {
+            let record = parse_epd(line).unwrap();
+            board.set_fen(&record.fen).unwrap();
+            let bm = record.operation("bm").unwrap();
+            assert_eq!(bm, expected);
+            assert_eq!(board.to_san(board.parse_san(bm).unwrap()), bm);
+            assert_eq!(to_epd(&board, &[("bm", bm)]), *line);
+        }
+    }
+
+    #[test]
+    fn pgn_to_epd_sampling() {
+        let sample = every_nth_ply(4, 20);
+        assert!(sample(20) && sample(24) && !sample(21) && !sample(16));
+
+        // Without best moves, the final position is sampled too
+        assert_eq!(pgn_to_epd(OPERA_GAME, every_nth_ply(1, 33), false).unwrap().len(), 1);
+        assert_eq!(pgn_to_epd(OPERA_GAME, every_nth_ply(1, 33), true).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn read_pgn_from_a_fen_tag() {
+        let mut board = Board::new();
+        let moves = read_pgn(&mut board, "[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e4 Kd7 2. e5 (2. Kd2) Ke6 *").unwrap();
+        assert_eq!(moves.len(), 4);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+
+        assert!(read_pgn(&mut board, "1. e4 e5 2. Ke3").is_err(), "Illegal move");
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!(read_pgn(&mut board, "1. e4 (1. d4 e5").is_err(), "Unbalanced variation");
+        assert!(read_pgn(&mut board, "1. e4 {Unterminated").is_err(), "Unbalanced comment");
+    }
+}
@@ -1,124 +1,485 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
 use crate::board::Board;
-use crate::movegen::{MoveList, generate_all_captures, generate_all_moves};
+use crate::clock::Clock;
+use crate::movegen::{MoveList, generate_all_captures, generate_all_moves, generate_quiet_checks};
 use crate::moves::Move;
-use crate::types::{PieceType, piece_value};
+use crate::stack::SearchStack;
+use crate::tt::{Bound, TranspositionTable};
+use crate::types::{PieceType, PieceValues};
+
+/// Number of plies the search stack holds. Search and quiescence return the static eval
+/// at the last ply instead of going deeper, so extensions can never index past it.
+pub const MAX_DEPTH: usize = 64;
 
 const SCORE_INF: i32 = 32_000;
+const LOSING_CAPTURE_PENALTY: i32 = 100_000; // Sends captures losing material below the quiet moves in move ordering
+const TT_MOVE_SCORE: i32 = 300_000; // Above every MVV-LVA score, a pawn taking a queen being 89_900 by default
+const PV_MOVE_SCORE: i32 = 200_000;
 const SCORE_MATE: i32 = 29_000;
+pub(crate) const SCORE_MATE_BOUND: i32 = SCORE_MATE - MAX_DEPTH as i32; // Scores beyond this are mate scores, a mate is found within MAX_DEPTH plies
+const SCORE_DRAW: i32 = 0;
+
+const TT_SIZE_MB: usize = 16;
+const NODE_FLUSH_INTERVAL: u64 = 1024; // Nodes counted locally before being added to the shared total
+const CURRMOVE_DELAY: Duration = Duration::from_secs(1); // Root moves are only reported on long searches
+
+/// Switches for the pruning and reduction heuristics of the search, all enabled by default.
+///
+/// Disabling everything turns the search into plain alpha-beta, which gives a ground truth to bisect which heuristic
+/// causes a wrong best move.
+#[derive(Copy, Clone, Debug)]
+pub struct SearchConfig {
+    pub null_move: bool,  // Null move pruning
+    pub lmr: bool,        // Late move reductions
+    pub tt_cutoffs: bool, // Return TT scores at non-PV nodes, the TT move is still used for ordering
+    pub qs_checks: bool,  // Also try quiet checks at the first quiescence ply
+}
+
+impl SearchConfig {
+    pub const NO_PRUNING: SearchConfig = SearchConfig {
+        null_move: false,
+        lmr: false,
+        tt_cutoffs: false,
+        qs_checks: false,
+    };
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            null_move: true,
+            lmr: true,
+            tt_cutoffs: true,
+            qs_checks: true,
+        }
+    }
+}
+
+/// Receives the `info` lines produced during search, without the trailing newline.
+pub trait InfoSink {
+    fn info(&mut self, line: &str);
+}
+
+/// Prints info lines to stdout, as expected by UCI.
+pub struct StdoutSink;
+
+impl InfoSink for StdoutSink {
+    fn info(&mut self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+/// Discards info lines, for searches run as a library call.
+pub struct NullSink;
+
+impl InfoSink for NullSink {
+    fn info(&mut self, _line: &str) {}
+}
+
+/// Search score in centipawns from the side to move's point of view, mate scores are `SCORE_MATE` minus the mate ply.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub struct Score(pub i32);
+
+impl Score {
+    /// Checks whether the score announces a forced mate, for either side.
+    pub fn is_mate(self) -> bool {
+        self.0.abs() >= SCORE_MATE_BOUND
+    }
+
+    /// Formats the score for UCI: `cp N` for normal scores, `mate N` for mate scores, where N counts moves rather than
+    /// plies and is negative when the side to move is getting mated.
+    pub fn to_uci(self) -> String {
+        if !self.is_mate() {
+            return format!("cp {}", self.0);
+        }
+        let moves = (SCORE_MATE - self.0.abs() + 1) / 2; // Mated at ply N: odd plies are our moves, even plies theirs
+        format!("mate {}", if self.0 > 0 { moves } else { -moves })
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_uci())
+    }
+}
+
+/// Outcome of a search, taken from the last completed iteration.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<Move>, // None when the side to move has no legal move
+    pub score: Score,
+    pub depth: i32,
+    pub seldepth: i32,
+    pub nodes: u64,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+    pub nps: u64,
+}
+
+impl fmt::Display for SearchResult {
+    /// Formats the result as the body of a UCI info line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "depth {} seldepth {} score {} nodes {} nps {} time {} pv",
+            self.depth,
+            self.seldepth,
+            self.score,
+            self.nodes,
+            self.nps,
+            self.time.as_millis()
+        )?;
+        for m in &self.pv {
+            write!(f, " {}", m)?;
+        }
+        Ok(())
+    }
+}
+
+/// Per-thread node counter, aggregated into a total shared between search threads.
+///
+/// Each node bumps a plain local `u64`, and only every `NODE_FLUSH_INTERVAL` nodes the pending count is added to the
+/// shared atomic with a relaxed `fetch_add`, so threads do not contend on a cache line at every node. The total lags
+/// behind by less than `NODE_FLUSH_INTERVAL` nodes per thread until `flush`, which is also called on drop.
+pub struct NodeCounter {
+    local: u64,   // Nodes searched by this thread since the last reset
+    pending: u64, // Nodes not yet added to `total`
+    total: Arc<AtomicU64>,
+}
+
+impl NodeCounter {
+    /// Creates a counter adding into `total`.
+    pub fn new(total: Arc<AtomicU64>) -> Self {
+        Self { local: 0, pending: 0, total }
+    }
+
+    #[inline(always)]
+    pub fn increment(&mut self) {
+        self.local += 1;
+        self.pending += 1;
+        if self.pending >= NODE_FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    /// Adds the pending nodes to the shared total.
+    pub fn flush(&mut self) {
+        self.total.fetch_add(self.pending, Ordering::Relaxed);
+        self.pending = 0;
+    }
+
+    /// Flushes, then restarts the local count. The shared total is left to its owner.
+    pub fn reset(&mut self) {
+        self.flush();
+        self.local = 0;
+    }
+
+    /// Returns the nodes searched by this thread since the last reset.
+    #[inline(always)]
+    pub fn local(&self) -> u64 {
+        self.local
+    }
+
+    /// Returns the shared total, as last flushed by every thread.
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for NodeCounter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
 
 pub struct Searcher<'a> {
     board: &'a mut Board,
 
     best_move: Move,
-    nodes: u64,
-
-    pv_table: [[Move; 64]; 64],
-    pv_length: [usize; 64],
+    nodes: NodeCounter,
+    seldepth: usize, // Deepest ply reached in this search, quiescence included
 
-    killers: [[Move; 2]; 64],
+    stack: SearchStack, // Per-ply killers, static evals and PVs
 
     lmr_table: [[usize; 64]; 64], // Late Move Reductions (LMR) table
+
+    tt: TranspositionTable,
+    config: SearchConfig,
+    fail_soft: bool,           // Return the best score found rather than clamping it to the window
+    piece_values: PieceValues, // Values of MVV-LVA ordering and SEE
+
+    info_sink: Box<dyn InfoSink>,
+    currmove_delay: Duration, // Search time after which the root move being searched is reported
+
+    clock: Clock,    // Stop flag, deadlines and node limit; once it stops, the current iteration is abandoned
+    stoppable: bool, // False during the first iteration, so a search always has a result
 }
 
 impl<'a> Searcher<'a> {
     pub fn new(board: &'a mut Board) -> Self {
         Self {
-            board: board,
+            board,
             best_move: Move::NULL_MOVE,
-            nodes: 0,
-
-            pv_table: [[Move::NULL_MOVE; 64]; 64],
-            pv_length: [0; 64],
+            nodes: NodeCounter::new(Arc::new(AtomicU64::new(0))),
+            seldepth: 0,
 
-            killers: [[Move::NULL_MOVE; 2]; 64], // Most beta cutoffs are caused by at most 2 moves per ply
+            stack: SearchStack::new(),
 
             lmr_table: Self::init_lmr_table(),
+
+            tt: TranspositionTable::new(TT_SIZE_MB),
+            config: SearchConfig::default(),
+            fail_soft: true,
+            piece_values: PieceValues::default(),
+
+            info_sink: Box::new(StdoutSink),
+            currmove_delay: CURRMOVE_DELAY,
+
+            clock: Clock::new(),
+            stoppable: false,
         }
     }
 
+    /// Ends the search when `stop` is set, for instance by the thread reading UCI commands.
+    ///
+    /// The flag is read every `CHECK_INTERVAL` nodes. The iteration in progress is abandoned and the result of the
+    /// last completed one is returned; the first iteration always completes.
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.clock.set_stop_flag(stop);
+    }
+
+    /// Replaces the search limits. The clock is restarted when the search starts, deadlines count from there.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Sends info lines to `sink` instead of stdout.
+    pub fn set_info_sink(&mut self, sink: Box<dyn InfoSink>) {
+        self.info_sink = sink;
+    }
+
+    /// Adds the nodes searched into `total`, shared with the other search threads.
+    pub fn set_shared_nodes(&mut self, total: Arc<AtomicU64>) {
+        self.nodes = NodeCounter::new(total);
+    }
+
+    /// Sets the search time after which `currmove` lines are emitted for each root move.
+    pub fn set_currmove_delay(&mut self, delay: Duration) {
+        self.currmove_delay = delay;
+    }
+
+    /// Enables or disables individual search heuristics.
+    pub fn set_config(&mut self, config: SearchConfig) {
+        self.config = config;
+    }
+
+    /// Sets the piece values read by MVV-LVA move ordering and by SEE, which decides the losing captures.
+    pub fn set_piece_values(&mut self, values: PieceValues) {
+        self.piece_values = values;
+    }
+
+    /// Selects fail-soft (default) or fail-hard alpha-beta.
+    ///
+    /// Both find the same best move, but fail-soft scores can lie outside the window and give the TT tighter bounds.
+    pub fn set_fail_soft(&mut self, fail_soft: bool) {
+        self.fail_soft = fail_soft;
+    }
+
+    /// Returns the best root move found by the last search.
+    pub fn best_move(&self) -> Move {
+        self.best_move
+    }
+
+    /// Returns the search stack, as left by the last search.
+    pub fn stack(&self) -> &SearchStack {
+        &self.stack
+    }
+
+    /// Returns the transposition table.
+    pub fn tt(&self) -> &TranspositionTable {
+        &self.tt
+    }
+
     /// Performs iterative deepening search using Principal Variation Search (PVS).
     ///
     /// The search starts from depth 1 and progressively increases up to `max_depth`. For each depth, the best score is
-    /// computed and search statistics are printed in a format compatible with typical chess engine UCI-style logging.
-    pub fn iterative_deepening(&mut self, max_depth: usize) {
-        self.nodes = 0;
+    /// computed and search statistics are sent to the info sink in a format compatible with UCI. The search ends
+    /// earlier when the clock stops it, when its soft deadline leaves no time for another iteration, or when a mate is
+    /// found within the searched depth.
+    ///
+    /// The position must be reachable: with the side not to move in check, the king could be captured and scores are
+    /// meaningless. Load untrusted positions with `Board::from_fen_validated`.
+    pub fn iterative_deepening(&mut self, max_depth: usize) -> SearchResult {
+        debug_assert!(!self.board.king_in_check(!self.board.side_to_move()), "Searching a position where the side not to move is in check");
+        self.clock.restart();
+        self.tt.new_search();
+        self.nodes.reset();
+        self.seldepth = 0;
         self.best_move = Move::NULL_MOVE;
-        self.pv_table.iter_mut().for_each(|t| t.fill(Move::NULL_MOVE));
-        self.pv_length.fill(0);
-        self.killers = [[Move::NULL_MOVE; 2]; 64];
+        self.stack.clear();
 
+        let mut result = SearchResult {
+            best_move: None,
+            score: Score(0),
+            depth: 0,
+            seldepth: 0,
+            nodes: 0,
+            time: Duration::ZERO,
+            pv: Vec::new(),
+            nps: 0,
+        };
         for depth in 1..=max_depth {
+            self.stoppable = depth > 1;
             let score = self.search::<true>(depth, 0, -SCORE_INF, SCORE_INF);
+            if self.clock.stopped() {
+                self.best_move = result.best_move.unwrap_or(Move::NULL_MOVE); // Partial iteration, keep the last result
+                break;
+            }
 
-            print!("info depth {} score cp {} nodes {} pv", depth, score, self.nodes);
-            for i in 0..self.pv_length[0] {
-                print!(" {}", self.pv_table[0][i]);
+            let time = self.clock.elapsed();
+            result = SearchResult {
+                best_move: (!self.best_move.is_null()).then_some(self.best_move),
+                score: Score(score),
+                depth: depth as i32,
+                seldepth: self.seldepth as i32,
+                nodes: self.nodes.local(),
+                time,
+                pv: self.stack[0].pv().to_vec(),
+                nps: (self.nodes.local() as u128 * 1_000_000_000 / time.as_nanos().max(1)) as u64,
+            };
+
+            let mut line = format!(
+                "info depth {} seldepth {} score {} nodes {} hashfull {} pv",
+                depth,
+                self.seldepth,
+                result.score,
+                self.nodes.local(),
+                self.tt.hashfull()
+            );
+            for m in self.stack[0].pv() {
+                line += &format!(" {}", m);
             }
-            println!("");
+            self.info_sink.info(&line);
 
-            // TODO: early exit
+            // A mate found within the searched depth cannot be improved, and past the soft deadline the next iteration
+            // would likely be cut short
+            if (result.score.is_mate() && SCORE_MATE - score.abs() <= depth as i32) || self.clock.soft_expired() {
+                break;
+            }
         }
+        result
+    }
+
+    /// Returns true once the clock has stopped the search, never during the first iteration.
+    #[inline(always)]
+    fn should_stop(&mut self) -> bool {
+        self.stoppable && self.clock.should_stop(self.nodes.local())
     }
 
     /// Principal variation search (PVS).
+    ///
+    /// In fail-soft mode the returned score can lie outside `[alpha, beta]`, in fail-hard mode it is clamped to it.
     fn search<const IS_PV: bool>(&mut self, depth: usize, ply: usize, mut alpha: i32, beta: i32) -> i32 {
-        self.nodes += 1;
+        self.nodes.increment();
+        if self.should_stop() {
+            return 0; // The interrupted iteration is discarded
+        }
+        self.seldepth = self.seldepth.max(ply);
+        self.stack[ply].pv_length = 0;
+
+        // 0 - Repeated position: a draw, whatever the TT holds for its key, since the entry may come from a line where
+        //     the position was not repeated. Then the last ply the search arrays hold, no child can be searched.
+        if ply > 0 && self.board.is_repetition() {
+            return SCORE_DRAW;
+        }
+        if ply >= MAX_DEPTH - 1 {
+            return self.board.evaluate_relative();
+        }
 
         // 1 - Target depth reached, quiescence search.
         if depth == 0 {
-            return self.quiescence(ply, alpha, beta);
+            return self.quiescence(ply, alpha, beta, self.config.qs_checks);
+        }
+
+        // 2 - Transposition table lookup: cut off on a deep enough entry whose bound proves the window.
+        let key = self.board.zobrist_key();
+        let tt_entry = self.tt.probe(key);
+        if let Some(entry) = tt_entry
+            && self.config.tt_cutoffs
+            && !IS_PV
+            && ply > 0
+            && entry.depth as usize >= depth
+        {
+            let score = score_from_tt(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return self.clamp(score, alpha, beta),
+                Bound::Lower if score >= beta => return self.clamp(score, alpha, beta),
+                Bound::Upper if score <= alpha => return self.clamp(score, alpha, beta),
+                _ => {}
+            }
         }
 
-        // 2 - Generate all moves and score them.
+        // 3 - Record the static eval, then generate all moves and score them.
+        let in_check = self.board.king_in_check(self.board.side_to_move());
+        self.stack[ply].static_eval = if in_check { None } else { Some(self.board.evaluate_relative()) };
         let mut moves = MoveList::new();
         let mut scores = [0i32; 256];
         generate_all_moves(self.board, &mut moves);
-        self.score_moves::<false>(&moves, ply, &mut scores);
+        // The TT move is trusted only if pseudo-legal here: a null move, a corrupted entry or a key shared with another
+        // position give no TT move
+        let tt_move = tt_entry.map(|entry| entry.best_move).filter(|&m| moves.contains(m)).unwrap_or(Move::NULL_MOVE);
+        self.score_moves::<false>(&moves, ply, tt_move, &mut scores);
 
-        // 3 - Iterate over possible moves.
+        // 4 - Iterate over possible moves.
+        let original_alpha = alpha;
+        let mut best_score = -SCORE_INF;
+        let mut best_move = Move::NULL_MOVE;
         let mut legal_move_count = 0; // Flag used for mate and stalemate detection
+        let pinned = self.board.pinned();
         for move_idx in 0..moves.count() {
-            self.pick_best_move(&mut moves, &mut scores, move_idx);
-            let m = moves.get(move_idx);
+            let m = moves.pick_best(&mut scores, move_idx);
 
-            // 4 - Null move pruning
-            let in_check = self.board.king_in_check(self.board.side_to_move());
-            if !IS_PV && depth >= 3 && !in_check {
+            // 5 - Null move pruning
+            if self.config.null_move && !IS_PV && null_move_allowed(self.board, depth, in_check) {
                 self.board.make_null_move();
                 let score = -self.search::<false>(depth - 1 - 2, ply + 1, -beta, -beta + 1);
                 self.board.unmake_null_move();
+                if self.clock.stopped() {
+                    return 0;
+                }
 
                 if score >= beta {
-                    return beta;
+                    // Mates found after passing are not proven, never return them
+                    return if self.fail_soft && score < SCORE_MATE_BOUND { score } else { beta };
                 }
             }
 
-            // 5 - Make move, undo and continue if illegal.
-            self.board.make_move(m);
-            let in_check = self.board.king_in_check(!self.board.side_to_move());
-            if in_check {
-                self.board.unmake_move(m);
+            // 6 - Skip illegal moves, make the legal ones.
+            if !self.board.is_legal_fast(m, pinned, in_check) {
                 continue;
             }
+            self.stack[ply].moved_piece = self.board.piece_on(m.from());
+            self.board.make_move(m);
             legal_move_count += 1;
 
-            // 6 - Late Move Reductions
+            if ply == 0 && self.clock.elapsed() >= self.currmove_delay {
+                self.info_sink.info(&format!("info depth {} currmove {} currmovenumber {}", depth, m, legal_move_count));
+            }
+
+            // 7 - Late Move Reductions
             let mut reduction = 0usize;
             let gives_check = self.board.king_in_check(self.board.side_to_move());
-            if !IS_PV
-                && depth >= 3
-                && move_idx >= 3
-                && !m.is_capture()
-                && !m.is_promotion()
-                && !gives_check
-                && m != self.killers[ply][0]
-                && m != self.killers[ply][1]
+            if self.config.lmr && !IS_PV && depth >= 3 && move_idx >= 3 && !m.is_capture() && !m.is_promotion() && !gives_check && !self.stack[ply].is_killer(m)
             {
                 reduction = self.lmr_table[depth.min(63)][move_idx.min(63)];
             }
             let reduced_depth = depth.saturating_sub(reduction);
 
-            // 7 - Principal Variation Search (PVS): only search the first/best move with full window.
+            // 8 - Principal Variation Search (PVS): only search the first/best move with full window.
             let mut score: i32;
             if IS_PV {
                 if move_idx == 0 {
@@ -138,28 +499,26 @@ impl<'a> Searcher<'a> {
                 }
             }
 
-            // 8 - Unmake move
+            // 9 - Unmake move, scores of an interrupted search are meaningless
             self.board.unmake_move(m);
+            if self.clock.stopped() {
+                return 0;
+            }
 
-            // 9 - Update alpha, beta, and PV-table
+            // 10 - Update alpha, beta, and PV-table
+            best_score = best_score.max(score);
             if score >= beta {
                 if !m.is_capture() {
-                    self.killers[ply][1] = self.killers[ply][0];
-                    self.killers[ply][0] = m;
+                    self.stack[ply].store_killer(m);
                 }
-                return beta; // Fail-high, beta cutoff
+                let score = self.clamp(score, alpha, beta);
+                self.tt.store(key, m, score_to_tt(score, ply), depth, Bound::Lower);
+                return score; // Fail-high, beta cutoff
             }
             if score > alpha {
                 alpha = score;
-                self.pv_table[ply][ply] = m; // Update the PV for the current ply
-
-                // Copy the PV from the next ply into this ply's row
-                let next_ply = ply + 1;
-                let child_len = self.pv_length[next_ply];
-                for i in 0..child_len {
-                    self.pv_table[ply][ply + 1 + i] = self.pv_table[next_ply][next_ply + i];
-                }
-                self.pv_length[ply] = child_len + 1;
+                best_move = m;
+                self.stack.update_pv(ply, m);
 
                 if ply == 0 {
                     self.best_move = m;
@@ -167,28 +526,48 @@ impl<'a> Searcher<'a> {
             }
         }
 
-        // 10 - Checkmate & stalemate detection
+        // 11 - Checkmate & stalemate detection
         if legal_move_count == 0 {
             return if self.board.king_in_check(self.board.side_to_move()) {
                 -SCORE_MATE + (ply as i32) // Checkmate in N
             } else {
-                0 // Stalemate
+                SCORE_DRAW // Stalemate
             };
         }
 
-        alpha
+        // 12 - Store the result: exact if a move raised alpha, otherwise an upper bound.
+        let (score, bound) = if alpha > original_alpha { (alpha, Bound::Exact) } else { (self.clamp(best_score, alpha, beta), Bound::Upper) };
+        self.tt.store(key, best_move, score_to_tt(score, ply), depth, bound);
+        score
     }
 
     /// Performs quiescence search.
-    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
-        self.nodes += 1;
+    ///
+    /// With `checks`, quiet moves giving check are tried after the captures. Only the first quiescence ply sets it, so
+    /// the search cannot keep checking forever.
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32, checks: bool) -> i32 {
+        self.nodes.increment();
+        if self.should_stop() {
+            return 0;
+        }
+        self.seldepth = self.seldepth.max(ply);
+
+        // Long sequences of checks answered by checks could run past the ply-indexed arrays
+        if ply >= MAX_DEPTH {
+            return self.board.evaluate_relative();
+        }
 
+        // In check every evasion is tried, so a quiet check cannot pass for a cutoff and mates are recognized.
         let in_check = self.board.king_in_check(self.board.side_to_move());
+        let mut best_score = -SCORE_MATE + (ply as i32); // Kept if no evasion is legal
+        self.stack[ply].static_eval = None;
         if !in_check {
             let eval = self.board.evaluate_relative();
+            self.stack[ply].static_eval = Some(eval);
             if eval >= beta {
-                return beta;
+                return self.clamp(eval, alpha, beta);
             }
+            best_score = eval;
             if alpha < eval {
                 alpha = eval;
             }
@@ -196,49 +575,74 @@ impl<'a> Searcher<'a> {
 
         let mut moves = MoveList::new();
         let mut scores = [0i32; 256];
-        generate_all_captures(self.board, &mut moves);
-        self.score_moves::<false>(&moves, ply, &mut scores);
+        if in_check {
+            generate_all_moves(self.board, &mut moves);
+        } else {
+            generate_all_captures(self.board, &mut moves);
+            if checks {
+                generate_quiet_checks(self.board, &mut moves);
+            }
+        }
+        self.score_moves::<false>(&moves, ply, Move::NULL_MOVE, &mut scores);
 
+        let pinned = self.board.pinned();
         for move_idx in 0..moves.count() {
-            self.pick_best_move(&mut moves, &mut scores, move_idx);
-            let m = moves.get(move_idx);
+            let m = moves.pick_best(&mut scores, move_idx);
 
-            self.board.make_move(m);
-            if self.board.king_in_check(!self.board.side_to_move()) {
-                self.board.unmake_move(m);
+            if !self.board.is_legal_fast(m, pinned, in_check) {
                 continue;
             }
+            self.stack[ply].moved_piece = self.board.piece_on(m.from());
+            self.board.make_move(m);
 
-            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            let score = -self.quiescence(ply + 1, -beta, -alpha, false);
             self.board.unmake_move(m);
+            if self.clock.stopped() {
+                return 0;
+            }
 
+            best_score = best_score.max(score);
             if score >= beta {
-                return beta;
+                return self.clamp(score, alpha, beta);
             }
             if score > alpha {
                 alpha = score;
             }
         }
 
-        alpha
+        self.clamp(best_score, alpha, beta)
     }
 
-    /// Assigns each move a score depending on how promising it is.
+    /// Fail-hard mode clamps every score to the window, fail-soft mode returns it unchanged.
     #[inline(always)]
-    fn score_moves<const QUIESCENCE: bool>(&self, moves: &MoveList, ply: usize, scores: &mut [i32; 256]) {
-        let n = moves.count();
+    fn clamp(&self, score: i32, alpha: i32, beta: i32) -> i32 {
+        if self.fail_soft { score } else { score.clamp(alpha, beta) }
+    }
 
-        for i in 0..n {
-            scores[i] = self.score_move::<QUIESCENCE>(moves.get(i), ply);
+    /// Assigns each move a score depending on how promising it is.
+    #[inline(always)]
+    fn score_moves<const QUIESCENCE: bool>(&self, moves: &MoveList, ply: usize, tt_move: Move, scores: &mut [i32; 256]) {
+        for (i, m) in moves.iter().enumerate() {
+            scores[i] = self.score_move::<QUIESCENCE>(m, ply, tt_move);
         }
     }
 
-    /// Assigns a score to a specific move. Uses PV-table, MVV-LVA and killer move heuristics.
+    /// Assigns a score to a specific move. Uses TT move, PV-table, MVV-LVA with SEE and killer move heuristics.
+    #[inline(always)]
+    fn score_move<const QUIESCENCE: bool>(&self, m: Move, ply: usize, tt_move: Move) -> i32 {
+        self.score_move_with_reason::<QUIESCENCE>(m, ply, tt_move).0
+    }
+
+    /// Like `score_move`, also returning which heuristic gave the score.
     #[inline(always)]
-    fn score_move<const QUIESCENCE: bool>(&self, m: Move, ply: usize) -> i32 {
-        // 1 - PV Move gets highest priority
-        if !QUIESCENCE && m == self.pv_table[ply][ply] {
-            return 20000;
+    fn score_move_with_reason<const QUIESCENCE: bool>(&self, m: Move, ply: usize, tt_move: Move) -> (i32, &'static str) {
+        debug_assert!(ply < MAX_DEPTH, "Move ordering past MAX_DEPTH");
+        // 1 - TT move, then PV move get highest priority
+        if m == tt_move {
+            return (TT_MOVE_SCORE, "tt");
+        }
+        if !QUIESCENCE && m == self.stack[ply].pv[0] {
+            return (PV_MOVE_SCORE, "pv");
         }
 
         // 2 - Captures, MVV-LVA (most valuable victim - least valuable attacker)
@@ -246,35 +650,42 @@ impl<'a> Searcher<'a> {
             let attacker = self.board.piece_on_unchecked(m.from()).get_type();
             let victim = if m.is_enpassant() { PieceType::Pawn } else { self.board.piece_on_unchecked(m.to()).get_type() };
 
-            // Formula: (Victim * 100) - Attacker.
-            // A Pawn (1) taking a Queen (5) = 900 - 1 = 899 (High priority)
-            // A Queen (9) taking a Pawn (1) = 100 - 9 = 91 (Lower priority)
-            return (piece_value(victim) * 100) - piece_value(attacker);
+            let mvv_lva = mvv_lva(&self.piece_values, victim, attacker);
+
+            // Captures losing material in the exchange are tried after the quiet moves
+            return if !self.board.see_ge_with(m, 0, &self.piece_values) { (mvv_lva - LOSING_CAPTURE_PENALTY, "losing capture") } else { (mvv_lva, "capture") };
         }
 
         // 3 - Killer moves
         if !QUIESCENCE {
-            if m == self.killers[ply][0] {
-                return 9000;
-            } else if m == self.killers[ply][1] {
-                return 8000;
+            if m == self.stack[ply].killers[0] {
+                return (9000, "killer 1");
+            } else if m == self.stack[ply].killers[1] {
+                return (8000, "killer 2");
             }
         }
 
-        0 // Quiet moves
+        (0, "quiet") // Quiet moves
     }
 
-    /// Picks the best move among the remaining ones (start_idx..last_idx) and places it at start_idx.
-    #[inline(always)]
-    fn pick_best_move(&self, moves: &mut MoveList, scores: &mut [i32; 256], start_idx: usize) {
-        let mut best_idx = start_idx;
-        for i in (start_idx + 1)..moves.count() {
-            if scores[i] > scores[best_idx] {
-                best_idx = i;
-            }
-        }
-        moves.swap(start_idx, best_idx);
-        scores.swap(start_idx, best_idx);
+    /// Lists the legal moves of the current position in the order the main search would try them at `ply` with
+    /// `tt_move` from the TT, each with its ordering score and the heuristic that gave it.
+    ///
+    /// Meant for diagnosing ordering regressions: killers and the PV are the ones left by the last search.
+    pub fn debug_dump(&self, ply: usize, tt_move: Move) -> Vec<(Move, i32, &'static str)> {
+        let mut moves = MoveList::new();
+        let mut scores = [0i32; 256];
+        generate_all_moves(self.board, &mut moves);
+        self.score_moves::<false>(&moves, ply, tt_move, &mut scores);
+
+        (0..moves.count())
+            .map(|i| moves.pick_best(&mut scores, i))
+            .filter(|&m| self.board.leaves_king_safe(m))
+            .map(|m| {
+                let (score, reason) = self.score_move_with_reason::<false>(m, ply, tt_move);
+                (m, score, reason)
+            })
+            .collect()
     }
 
     /// Initializes the Late Move Reduction (LMR) table.
@@ -288,17 +699,436 @@ impl<'a> Searcher<'a> {
     fn init_lmr_table() -> [[usize; 64]; 64] {
         let mut table = [[0usize; 64]; 64];
 
-        for depth in 1..64 {
-            for move_number in 1..64 {
+        for (depth, row) in table.iter_mut().enumerate().skip(1) {
+            for (move_number, entry) in row.iter_mut().enumerate().skip(1) {
                 let d = depth as f64;
                 let m = move_number as f64;
 
                 let reduction = (d.ln() * m.ln() / 2.0) as usize;
 
-                table[depth][move_number] = reduction;
+                *entry = reduction;
             }
         }
 
         table
     }
 }
+
+/// Ordering score of a capture: most valuable victim first, then least valuable attacker.
+///
+/// Formula: (Victim * 100) - Attacker.
+/// A Pawn (100) taking a Queen (900) = 90000 - 100 = 89900 (High priority)
+/// A Queen (900) taking a Pawn (100) = 10000 - 900 = 9100 (Lower priority)
+#[inline(always)]
+pub fn mvv_lva(values: &PieceValues, victim: PieceType, attacker: PieceType) -> i32 {
+    values.get(victim) * 100 - values.get(attacker)
+}
+
+/// Returns true if null-move pruning may be tried: deep enough, not in check, and the side to move has pieces besides
+/// pawns, since with only pawns left passing can be better than any move (zugzwang) and the null search would lie.
+#[inline(always)]
+pub fn null_move_allowed(board: &Board, depth: usize, in_check: bool) -> bool {
+    depth >= 3 && !in_check && board.non_pawn_material(board.side_to_move()) > 0
+}
+
+/// Converts a mate score from "mate in N from the root" to "mate in N from this node" before storing it in the TT.
+#[inline(always)]
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= SCORE_MATE_BOUND {
+        score + ply as i32
+    } else if score <= -SCORE_MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Inverse of `score_to_tt`.
+#[inline(always)]
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= SCORE_MATE_BOUND {
+        score - ply as i32
+    } else if score <= -SCORE_MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval_position;
+    use crate::movegen::generate_legal_moves;
+    use crate::types::{Color, Piece, Square, piece_value};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Fail-soft and fail-hard must agree on the best move, and the bounds fail-soft stores must be at least as tight:
+    /// for the positions two plies deep that both searches stored at the same depth and with the same bound type, a
+    /// fail-soft lower bound is never below the fail-hard one and an upper bound never above it, and some are strictly
+    /// tighter.
+    #[test]
+    fn fail_soft_agrees_with_fail_hard() {
+        const FENS: [&str; 4] = [
+            "r1bqk2r/pppp1ppp/2n2n2/1B2p3/3PP3/2N2N2/PPP2PPP/R1BQK2R w KQkq - 0 1",
+            "r1b1k2r/pppp1ppp/2n2n2/1B2p3/4P3/2N5/PPPP1PPP/R1BQK2R w kq - 0 1",
+            "8/8/8/3k4/3P4/3K4/8/8 w - - 0 1",
+            "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+        ];
+        let legal_moves = |board: &Board| {
+            let mut moves = MoveList::new();
+            generate_legal_moves(board, &mut moves);
+            moves
+        };
+
+        let (mut tighter, mut looser) = (0, 0);
+        for fen in FENS {
+            let (mut hard_board, mut soft_board) = (Board::new(), Board::new());
+            hard_board.from_fen(fen).unwrap();
+            soft_board.from_fen(fen).unwrap();
+            let mut hard = Searcher::new(&mut hard_board);
+            hard.set_info_sink(Box::new(NullSink));
+            hard.set_fail_soft(false);
+            hard.iterative_deepening(5);
+            let mut soft = Searcher::new(&mut soft_board);
+            soft.set_info_sink(Box::new(NullSink));
+            soft.iterative_deepening(5);
+            assert_eq!(hard.best_move(), soft.best_move(), "{fen}");
+
+            let mut board = Board::new();
+            board.from_fen(fen).unwrap();
+            for m in legal_moves(&board).iter() {
+                board.make_move(m);
+                for reply in legal_moves(&board).iter() {
+                    board.make_move(reply);
+                    let key = board.zobrist_key();
+                    if let (Some(h), Some(s)) = (hard.tt().probe(key), soft.tt().probe(key))
+                        && h.depth == s.depth
+                        && h.bound == s.bound
+                    {
+                        match h.bound {
+                            Bound::Lower => (tighter, looser) = (tighter + (s.score > h.score) as u32, looser + (s.score < h.score) as u32),
+                            Bound::Upper => (tighter, looser) = (tighter + (s.score < h.score) as u32, looser + (s.score > h.score) as u32),
+                            Bound::Exact => {}
+                        }
+                    }
+                    board.unmake_move(reply);
+                }
+                board.unmake_move(m);
+            }
+        }
+        assert!(tighter > 0 && looser == 0, "{tighter} tighter and {looser} looser bounds");
+    }
+
+    #[test]
+    fn quiescence_searches_all_evasions() {
+        // Fool's mate: checkmated, no capture and no evasion
+        let mut board = Board::new();
+        board.from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        let mut searcher = Searcher::new(&mut board);
+        assert_eq!(searcher.quiescence(0, -SCORE_INF, SCORE_INF, false), -SCORE_MATE);
+
+        // In check with only quiet king moves to get out of it: a real score, not the bottom of the window
+        let mut board = Board::new();
+        board.from_fen("4k3/4r3/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let mut searcher = Searcher::new(&mut board);
+        let score = searcher.quiescence(0, -SCORE_INF, SCORE_INF, false);
+        assert!(score.abs() < SCORE_MATE_BOUND, "{score}");
+    }
+
+    /// Keeps the info lines of a search, shared with the test that installed it.
+    struct CaptureSink(Rc<RefCell<Vec<String>>>);
+
+    impl InfoSink for CaptureSink {
+        fn info(&mut self, line: &str) {
+            self.0.borrow_mut().push(line.to_string());
+        }
+    }
+
+    #[test]
+    fn currmove_lines_follow_the_root_moves() {
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let mut board = Board::new();
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut legal_moves = MoveList::new();
+        generate_legal_moves(&board, &mut legal_moves);
+        let legal_moves: Vec<String> = legal_moves.iter().map(|m| m.to_string()).collect();
+
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(CaptureSink(lines.clone())));
+        searcher.set_currmove_delay(Duration::ZERO);
+        searcher.iterative_deepening(3);
+
+        for depth in 1..=3 {
+            let prefix = format!("info depth {depth} currmove ");
+            let currmoves: Vec<(String, usize)> = lines
+                .borrow()
+                .iter()
+                .filter_map(|line| line.strip_prefix(&prefix))
+                .map(|rest| {
+                    let (m, number) = rest.split_once(" currmovenumber ").unwrap();
+                    (m.to_string(), number.parse().unwrap())
+                })
+                .collect();
+            assert_eq!(currmoves.len(), legal_moves.len(), "depth {depth}");
+            assert!(currmoves.iter().enumerate().all(|(i, (_, number))| *number == i + 1), "depth {depth}");
+            let mut searched: Vec<&String> = currmoves.iter().map(|(m, _)| m).collect();
+            searched.sort();
+            searched.dedup();
+            assert_eq!(searched.len(), legal_moves.len(), "depth {depth}");
+            assert!(searched.iter().all(|m| legal_moves.contains(m)), "depth {depth}");
+        }
+    }
+
+    #[test]
+    fn no_pruning_finds_the_same_tactics() {
+        #[rustfmt::skip]
+        const CASES: [(&str, &str); 4] = [
+            ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "d1d8"),                                  // Back rank mate
+            ("r3k3/8/8/1N6/8/8/8/4K3 w - - 0 1", "b5c7"),                                      // Royal fork
+            ("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", "d2d5"),                                     // Hanging queen
+            ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", "h5f7"),  // Scholar's mate
+        ];
+        let mut board = Board::new();
+        for (fen, best) in CASES {
+            for config in [SearchConfig::default(), SearchConfig::NO_PRUNING] {
+                board.from_fen(fen).unwrap();
+                let mut searcher = Searcher::new(&mut board);
+                searcher.set_info_sink(Box::new(NullSink));
+                searcher.set_config(config);
+                let result = searcher.iterative_deepening(5);
+                assert_eq!(result.best_move.map(|m| m.to_string()).as_deref(), Some(best), "{fen} with {config:?}");
+            }
+        }
+    }
+
+    /// Checks that a depth-limited search returns a full `SearchResult`: best move heading a non-empty PV, nodes and NPS.
+    #[test]
+    fn search_result_is_complete() {
+        const DEPTH: usize = 5;
+        let mut board = Board::new();
+        board.from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(NullSink));
+        let result = searcher.iterative_deepening(DEPTH);
+
+        assert_eq!(result.depth, DEPTH as i32);
+        assert!(!result.pv.is_empty());
+        assert_eq!(result.best_move, Some(result.pv[0]));
+        assert!(result.nodes >= DEPTH as u64 && result.nps > 0 && !result.time.is_zero());
+    }
+
+    /// Checks that node counts aggregated from two threads add up to the per-thread counts, with nothing lost.
+    #[test]
+    fn node_counts_add_up_across_threads() {
+        const COUNTS: [u64; 2] = [100_003, 4 * NODE_FLUSH_INTERVAL + 17]; // Neither a multiple of the flush interval
+        let total = Arc::new(AtomicU64::new(0));
+
+        let handles = COUNTS.map(|count| {
+            let total = Arc::clone(&total);
+            std::thread::spawn(move || {
+                let mut counter = NodeCounter::new(total);
+                for _ in 0..count {
+                    counter.increment();
+                }
+                counter.local()
+            })
+        });
+        let local_sum: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(local_sum, COUNTS.iter().sum::<u64>());
+        assert_eq!(total.load(Ordering::Relaxed), local_sum);
+    }
+
+    /// Checks that `seldepth` counts the quiescence plies: a shallow search of a position full of captures must report a
+    /// selective depth beyond the nominal one.
+    #[test]
+    fn seldepth_counts_quiescence() {
+        const DEPTH: usize = 2;
+        let mut board = Board::new();
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(NullSink));
+        let result = searcher.iterative_deepening(DEPTH);
+
+        assert!(result.seldepth > DEPTH as i32);
+    }
+
+    /// Checks the null-move guard: declined for a side with only pawns, allowed once it has a piece.
+    #[test]
+    fn null_move_needs_a_piece() {
+        let mut board = Board::new();
+        board.from_fen("8/8/4k3/8/8/4P3/4K3/8 w - - 0 1").unwrap();
+        assert_eq!(board.non_pawn_material(Color::White), 0);
+        assert!(!null_move_allowed(&board, 6, false));
+        board.from_fen("8/8/4k3/8/8/4P3/4K3/6N1 w - - 0 1").unwrap();
+        assert_eq!(board.non_pawn_material(Color::White), piece_value(PieceType::Knight));
+        assert!(null_move_allowed(&board, 6, false));
+    }
+
+    /// Checks the UCI formatting of scores: centipawns, mate for the side to move and mate against it, counted in moves.
+    #[test]
+    fn scores_format_for_uci() {
+        // Scores as the search encodes them: the mated side scores `-SCORE_MATE + ply`, the mating side the negation
+        let mated_at = |ply: i32| Score(-SCORE_MATE + ply);
+        let cases = [(Score(-35), "cp -35"), (Score(-mated_at(5).0), "mate 3"), (mated_at(4), "mate -2"), (Score(-mated_at(1).0), "mate 1")];
+        for (score, uci) in cases {
+            assert_eq!(score.to_uci(), uci);
+        }
+    }
+
+    /// Checks that iterative deepening past `MAX_DEPTH` clamps at the last ply instead of indexing out of bounds, on a
+    /// locked pawn position where only the kings can move.
+    #[test]
+    fn depth_past_max_depth_is_clamped() {
+        let mut board = Board::new();
+        board.from_fen("k7/8/8/p1p1p1p1/P1P1P1P1/8/8/K7 w - - 0 1").unwrap();
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(NullSink));
+        let result = searcher.iterative_deepening(MAX_DEPTH + 4);
+        assert!((result.seldepth as usize) < MAX_DEPTH);
+        assert!(result.best_move.is_some());
+    }
+
+    /// Checks that a perpetual check is scored as a draw. White is a rook down and can only hold by checking forever from
+    /// e8 and h5: the position after 1. Qe8+ comes back at ply 5, when earlier iterations already stored a losing score
+    /// for its key in the TT.
+    #[test]
+    fn perpetual_check_is_a_draw() {
+        let mut board = Board::new();
+        board.from_fen("6k1/6p1/8/8/8/r7/q1PP4/2K1Q3 w - - 0 1").unwrap();
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(NullSink));
+        let result = searcher.iterative_deepening(8);
+        assert_eq!(result.score, Score(SCORE_DRAW));
+        assert_eq!(result.best_move.map(|m| m.to_string()).as_deref(), Some("e1e8"));
+    }
+
+    /// Checks that overriding the knight value reaches both SEE and MVV-LVA: a pawn taking an undefended knight must gain
+    /// the new value, and the knight capture must move ahead of the bishop capture in ordering.
+    #[test]
+    fn piece_value_override_reaches_see_and_ordering() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/8/2n1b3/3P4/8/8/4K3 w - - 0 1").unwrap();
+        let (takes_knight, takes_bishop) = (board.parse_uci_move("d4c5").unwrap(), board.parse_uci_move("d4e5").unwrap());
+        let (default, heavy_knight) = (PieceValues::default(), PieceValues::default().with(PieceType::Knight, 400));
+
+        assert_eq!(board.see_with(takes_knight, &default), 320);
+        assert_eq!(board.see_with(takes_knight, &heavy_knight), 400);
+        assert!(!board.see_ge_with(takes_knight, 350, &default));
+        assert!(board.see_ge_with(takes_knight, 350, &heavy_knight));
+        let mut searcher = Searcher::new(&mut board);
+        let default_order = searcher.score_move::<false>(takes_knight, 0, Move::NULL_MOVE) > searcher.score_move::<false>(takes_bishop, 0, Move::NULL_MOVE);
+        searcher.set_piece_values(heavy_knight);
+        let heavy_order = searcher.score_move::<false>(takes_knight, 0, Move::NULL_MOVE) > searcher.score_move::<false>(takes_bishop, 0, Move::NULL_MOVE);
+        assert!(!default_order);
+        assert!(heavy_order);
+    }
+
+    /// Checks the search stack after a search: the root static eval must be the one of `eval_position`, negated with
+    /// Black to move, and the root PV must be the reported one.
+    #[test]
+    fn root_stack_entry_matches_the_search() {
+        for fen in
+            ["r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4", "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 4 4"]
+        {
+            let mut board = Board::new();
+            board.from_fen(fen).unwrap();
+            let eval = if board.side_to_move() == Color::White { eval_position(&board) } else { -eval_position(&board) };
+            let mut searcher = Searcher::new(&mut board);
+            searcher.set_info_sink(Box::new(NullSink));
+            let result = searcher.iterative_deepening(5);
+
+            let root = &searcher.stack()[0];
+            assert_eq!(root.ply, 0);
+            assert_eq!(root.static_eval, Some(eval), "{fen}");
+            assert!(!result.pv.is_empty(), "{fen}");
+            assert_eq!(root.pv(), result.pv, "{fen}");
+        }
+    }
+
+    /// Checks the stack entries below the root: with Kb1 as the only legal move, a depth 1 search must record the king
+    /// as the piece moved from the root, and at ply 1 the static eval of the position after Kb1, for Black.
+    #[test]
+    fn child_stack_entry_follows_the_only_move() {
+        let mut board = Board::new();
+        board.from_fen("7k/8/8/8/8/1p6/1P6/K7 w - - 0 1").unwrap();
+        assert_eq!(board.legal_moves().count(), 1);
+        let mut child = Board::new();
+        child.from_fen("7k/8/8/8/8/1p6/1P6/1K6 b - - 1 1").unwrap();
+        let child_eval = -eval_position(&child);
+
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(NullSink));
+        searcher.iterative_deepening(1);
+        let stack = searcher.stack();
+        assert_eq!(stack[0].moved_piece, Some(Piece::new(Color::White, PieceType::King)));
+        assert_eq!(stack[1].ply, 1);
+        assert_eq!(stack[1].static_eval, Some(child_eval));
+        assert_eq!(stack[1].moved_piece, None, "Black has no capture or check to search");
+        assert_eq!(stack[0].excluded_move, Move::NULL_MOVE);
+    }
+
+    /// Checks the ordering dump: a quiet TT move must come first with the TT score, ahead of a winning capture, and the
+    /// rest must be listed by decreasing score with every legal move present once.
+    #[test]
+    fn debug_dump_lists_moves_in_order() {
+        let mut board = Board::new();
+        board.from_fen("4k3/8/8/2n5/3P4/8/8/4K1N1 w - - 0 1").unwrap();
+        let tt_move = board.parse_uci_move("g1f3").unwrap();
+        let legal_count = board.legal_moves().count();
+        let searcher = Searcher::new(&mut board);
+        let dump = searcher.debug_dump(0, tt_move);
+
+        assert_eq!(dump.first(), Some(&(tt_move, TT_MOVE_SCORE, "tt")));
+        assert_eq!(dump.get(1).map(|entry| entry.2), Some("capture"));
+        assert_eq!(dump.len(), legal_count);
+        assert!(dump.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    /// Checks the null move guards: `NULL_MOVE` must be null and no generated move may be, so that a null TT move is
+    /// never found in the move list and never gets the TT score.
+    #[test]
+    fn null_tt_move_is_no_move() {
+        let mut board = Board::new();
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let moves = board.pseudo_legal_moves();
+        assert!(Move::NULL_MOVE.is_null());
+        assert!(!moves.iter().any(Move::is_null));
+        assert!(!moves.contains(Move::NULL_MOVE));
+
+        let searcher = Searcher::new(&mut board);
+        assert!(searcher.debug_dump(0, Move::NULL_MOVE).iter().all(|&(_, _, reason)| reason != "tt"));
+    }
+
+    /// Checks TT moves that are not pseudo-legal in their position, as a corrupted entry or a key collision would give:
+    /// stored for the root and for a child, they must neither break the search nor be played or reported.
+    #[test]
+    fn foreign_tt_moves_are_never_played() {
+        const FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::new();
+        board.from_fen(FEN).unwrap();
+        let root_key = board.zobrist_key();
+        let reply = board.parse_uci_move("e2a6").unwrap();
+        board.make_move(reply);
+        let child_key = board.zobrist_key();
+        board.unmake_move(reply);
+
+        let foreign = [Move::new_normal(Square::A3, Square::A6), Move::new_normal(Square::E7, Square::E1)]; // Empty square, wrong side
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(NullSink));
+        searcher.tt.store(root_key, foreign[0], 0, 0, Bound::Upper);
+        searcher.tt.store(child_key, foreign[1], 0, 0, Bound::Upper);
+        let result = searcher.iterative_deepening(4);
+
+        assert!(result.pv.iter().all(|m| !foreign.contains(m)));
+        let mut replay = Board::new();
+        replay.from_fen(FEN).unwrap();
+        for &m in &result.pv {
+            assert!(replay.is_legal(m), "{m}");
+            replay.make_move(m);
+        }
+        assert_eq!(result.best_move, result.pv.first().copied());
+    }
+}
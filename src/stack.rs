@@ -0,0 +1,106 @@
+//! Search stack.
+//!
+//! The search keeps scratch state for each ply of the line being searched: the killer moves, the static evaluation,
+//! the piece moved to reach the next ply, the move excluded from a singular search, and the principal variation found
+//! below the node. Gathering them in one entry per ply replaces the separate arrays each feature would allocate.
+//!
+//! Entries are indexed by ply and survive between iterations of iterative deepening, so the killers and the PV of the
+//! previous iteration still guide move ordering. They are only cleared when a new search starts.
+
+use std::ops::{Index, IndexMut};
+
+use crate::moves::Move;
+use crate::search::MAX_DEPTH;
+use crate::types::Piece;
+
+/// Scratch state of one ply.
+#[derive(Copy, Clone)]
+pub struct StackEntry {
+    pub ply: usize,
+    pub killers: [Move; 2],         // Quiet moves that caused a beta cutoff at this ply, most recent first
+    pub static_eval: Option<i32>,   // Relative to the side to move, `None` when in check or not computed
+    pub moved_piece: Option<Piece>, // Piece moved by the move being searched from this ply
+    pub excluded_move: Move,        // Move skipped by a singular search, `NULL_MOVE` if none
+    pub pv: [Move; MAX_DEPTH],      // Principal variation from this ply, its first move played at this ply
+    pub pv_length: usize,
+}
+
+impl StackEntry {
+    const fn new(ply: usize) -> Self {
+        Self {
+            ply,
+            killers: [Move::NULL_MOVE; 2],
+            static_eval: None,
+            moved_piece: None,
+            excluded_move: Move::NULL_MOVE,
+            pv: [Move::NULL_MOVE; MAX_DEPTH],
+            pv_length: 0,
+        }
+    }
+
+    /// Returns true if `m` is one of the killers of this ply.
+    #[inline(always)]
+    pub fn is_killer(&self, m: Move) -> bool {
+        m == self.killers[0] || m == self.killers[1]
+    }
+
+    /// Records the quiet move `m` as the most recent killer, shifting the previous one to the second slot.
+    #[inline(always)]
+    pub fn store_killer(&mut self, m: Move) {
+        self.killers[1] = self.killers[0];
+        self.killers[0] = m;
+    }
+
+    /// Returns the principal variation from this ply.
+    pub fn pv(&self) -> &[Move] {
+        &self.pv[..self.pv_length]
+    }
+}
+
+/// One entry per ply, from the root at ply 0 up to `MAX_DEPTH - 1`.
+pub struct SearchStack {
+    entries: [StackEntry; MAX_DEPTH],
+}
+
+impl SearchStack {
+    pub fn new() -> Self {
+        Self { entries: std::array::from_fn(StackEntry::new) }
+    }
+
+    /// Resets every entry, for a new search.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Makes `m` followed by the PV of the next ply the PV of `ply`.
+    #[inline(always)]
+    pub fn update_pv(&mut self, ply: usize, m: Move) {
+        let (current, below) = self.entries.split_at_mut(ply + 1);
+        let (entry, child) = (&mut current[ply], &below[0]);
+        entry.pv[0] = m;
+        entry.pv[1..=child.pv_length].copy_from_slice(child.pv());
+        entry.pv_length = child.pv_length + 1;
+    }
+}
+
+impl Default for SearchStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<usize> for SearchStack {
+    type Output = StackEntry;
+
+    #[inline(always)]
+    fn index(&self, ply: usize) -> &StackEntry {
+        &self.entries[ply]
+    }
+}
+
+impl IndexMut<usize> for SearchStack {
+    #[inline(always)]
+    fn index_mut(&mut self, ply: usize) -> &mut StackEntry {
+        &mut self.entries[ply]
+    }
+}
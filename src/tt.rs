@@ -0,0 +1,126 @@
+//! Transposition table.
+//!
+//! This module implements the search transposition table (TT), a fixed-size cache of search results keyed on the
+//! position Zobrist key. Each entry stores the score of a subtree together with the kind of bound it represents, the
+//! depth it was searched to and the best move found, which is also used for move ordering.
+
+use crate::moves::Move;
+
+/// What a stored score says about the true value of the position.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Bound {
+    Exact, // Score is inside the window: it is the true value
+    Lower, // Search failed high: true value >= score
+    Upper, // Search failed low: true value <= score
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TTEntry {
+    pub key: u64,
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+    pub generation: u8, // Search that wrote the entry, 0 for never written
+}
+
+impl TTEntry {
+    const EMPTY: TTEntry = TTEntry {
+        key: 0,
+        best_move: Move::NULL_MOVE,
+        score: 0,
+        depth: 0,
+        bound: Bound::Upper,
+        generation: 0,
+    };
+}
+
+/// Fixed-size, always-replace transposition table.
+///
+/// The length is a power of two so that the index is the low bits of the key.
+pub struct TranspositionTable {
+    entries: Vec<TTEntry>,
+    mask: usize,
+    generation: u8, // Incremented at each new search, never 0
+}
+
+const HASHFULL_SAMPLE: usize = 1000;
+
+impl TranspositionTable {
+    /// Creates a table using at most `size_mb` megabytes.
+    pub fn new(size_mb: usize) -> Self {
+        let max_entries = (size_mb.max(1) << 20) / size_of::<TTEntry>();
+        let len = 1usize << max_entries.ilog2(); // Round down to a power of two
+        Self {
+            entries: vec![TTEntry::EMPTY; len],
+            mask: len - 1,
+            generation: 1,
+        }
+    }
+
+    /// Empties the table.
+    pub fn clear(&mut self) {
+        self.entries.fill(TTEntry::EMPTY);
+        self.generation = 1;
+    }
+
+    /// Starts a new search: entries written from now on belong to a new generation.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1).max(1);
+    }
+
+    /// Returns the permille of entries written during the current search, as in UCI `info hashfull`.
+    ///
+    /// Only the first entries are sampled, which is enough since keys are spread uniformly over the table.
+    pub fn hashfull(&self) -> u16 {
+        let sample = &self.entries[..HASHFULL_SAMPLE.min(self.entries.len())];
+        let used = sample.iter().filter(|e| e.generation == self.generation).count();
+        (used * 1000 / sample.len()) as u16
+    }
+
+    /// Returns the entry stored for `key`, if any.
+    #[inline(always)]
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        let entry = self.entries[key as usize & self.mask];
+        (entry.key == key).then_some(entry)
+    }
+
+    #[inline(always)]
+    pub fn store(&mut self, key: u64, best_move: Move, score: i32, depth: usize, bound: Bound) {
+        self.entries[key as usize & self.mask] = TTEntry {
+            key,
+            best_move,
+            score,
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            generation: self.generation,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::search::{NullSink, Searcher};
+
+    #[test]
+    fn hashfull_counts_the_current_search() {
+        let mut tt = TranspositionTable::new(1);
+        assert_eq!(tt.hashfull(), 0);
+        for key in 0..500 {
+            tt.store(key, Move::NULL_MOVE, 0, 1, Bound::Exact);
+        }
+        assert_eq!(tt.hashfull(), 500);
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0, "Entries of an older search");
+
+        let mut board = Board::new();
+        board.from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut searcher = Searcher::new(&mut board);
+        searcher.set_info_sink(Box::new(NullSink));
+        assert_eq!(searcher.tt().hashfull(), 0);
+        searcher.iterative_deepening(5);
+        assert!(searcher.tt().hashfull() > 0);
+    }
+}
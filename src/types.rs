@@ -121,6 +121,12 @@ impl Square {
         unsafe { std::mem::transmute(s - 9) }
     }
 
+    /// Returns the square mirrored vertically (rank r <-> rank 7-r), as seen from the other side of the board.
+    #[inline(always)]
+    pub const fn flipped(self) -> Square {
+        Square::new(self as u8 ^ 56)
+    }
+
     /// Turns the square into a bitboard
     #[inline(always)]
     pub fn bb(self) -> Bitboard {
@@ -133,8 +139,8 @@ impl fmt::Display for Square {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let idx = *self as u8;
 
-        let file = (idx % 8) as u8;
-        let rank = (idx / 8) as u8;
+        let file = idx % 8;
+        let rank = idx / 8;
 
         let file_char = (b'a' + file) as char;
         let rank_char = (b'1' + rank) as char;
@@ -205,8 +211,33 @@ pub fn piece_value(piece_type: PieceType) -> i32 {
     }
 }
 
+/// Piece values read by the static exchange evaluation and by MVV-LVA move ordering, so that changing a value affects
+/// both coherently. The default is `piece_value`, the values of the evaluation.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PieceValues(pub [i32; PieceType::NUM]);
+
+impl PieceValues {
+    /// Returns the value of `piece_type`.
+    #[inline(always)]
+    pub fn get(&self, piece_type: PieceType) -> i32 {
+        self.0[piece_type]
+    }
+
+    /// Returns a copy with `piece_type` worth `value`.
+    pub fn with(mut self, piece_type: PieceType, value: i32) -> Self {
+        self.0[piece_type] = value;
+        self
+    }
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        Self([PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King].map(piece_value))
+    }
+}
+
 #[repr(u8)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Piece {
     //White
     WhitePawn = 0,
@@ -295,3 +326,113 @@ impl Piece {
         }
     }
 }
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CastlingSide {
+    King,
+    Queen,
+}
+
+/// Castling rights, one bit per color and side.
+///
+/// From LSB on: white king side, white queen side, black king side, black queen side.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub struct CastlingRights(u8);
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights(0);
+    pub const ALL: CastlingRights = CastlingRights(0b1111);
+
+    #[inline(always)]
+    const fn bit(color: Color, side: CastlingSide) -> u8 {
+        1 << ((color as u8) * 2 + side as u8)
+    }
+
+    /// Returns the raw bits, e.g. to index Zobrist keys.
+    #[inline(always)]
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Builds the rights from raw bits as returned by `bits`, higher bits are ignored.
+    #[inline(always)]
+    pub const fn from_bits(bits: u8) -> Self {
+        CastlingRights(bits & 0b1111)
+    }
+
+    /// Checks whether `color` may still castle on `side`.
+    #[inline(always)]
+    pub const fn has(self, color: Color, side: CastlingSide) -> bool {
+        self.0 & Self::bit(color, side) != 0
+    }
+
+    /// Grants `color` the right to castle on `side`.
+    #[inline(always)]
+    pub fn set(&mut self, color: Color, side: CastlingSide) {
+        self.0 |= Self::bit(color, side);
+    }
+
+    /// Removes the right of `color` to castle on `side`.
+    #[inline(always)]
+    pub fn remove(&mut self, color: Color, side: CastlingSide) {
+        self.0 &= !Self::bit(color, side);
+    }
+
+    /// Returns the rights with colors swapped, for a color-flipped position.
+    #[inline(always)]
+    pub const fn flipped(self) -> Self {
+        CastlingRights(((self.0 & 0b0011) << 2) | (self.0 >> 2))
+    }
+
+    /// Removes the rights lost when a piece moves from or to `sq`: a king leaving its square loses both sides, a rook
+    /// leaving or being captured on its corner loses that side.
+    #[inline(always)]
+    pub fn remove_square(&mut self, sq: Square) {
+        match sq {
+            Square::E1 => self.0 &= !(Self::bit(Color::White, CastlingSide::King) | Self::bit(Color::White, CastlingSide::Queen)),
+            Square::E8 => self.0 &= !(Self::bit(Color::Black, CastlingSide::King) | Self::bit(Color::Black, CastlingSide::Queen)),
+            Square::H1 => self.remove(Color::White, CastlingSide::King),
+            Square::A1 => self.remove(Color::White, CastlingSide::Queen),
+            Square::H8 => self.remove(Color::Black, CastlingSide::King),
+            Square::A8 => self.remove(Color::Black, CastlingSide::Queen),
+            _ => {}
+        }
+    }
+}
+
+/// Formats the rights as in FEN: `KQkq`, or `-` if none are left.
+impl fmt::Display for CastlingRights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return write!(f, "-");
+        }
+        for (color, side, ch) in [
+            (Color::White, CastlingSide::King, 'K'),
+            (Color::White, CastlingSide::Queen, 'Q'),
+            (Color::Black, CastlingSide::King, 'k'),
+            (Color::Black, CastlingSide::Queen, 'q'),
+        ] {
+            if self.has(color, side) {
+                write!(f, "{}", ch)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn castling_rights() {
+        let mut rights = CastlingRights::ALL;
+        assert_eq!(rights.to_string(), "KQkq");
+        rights.remove(Color::White, CastlingSide::King);
+        assert_eq!(rights.to_string(), "Qkq");
+        assert!(!rights.has(Color::White, CastlingSide::King) && rights.has(Color::White, CastlingSide::Queen));
+        rights.set(Color::White, CastlingSide::King);
+        assert_eq!(rights, CastlingRights::ALL);
+        assert_eq!(CastlingRights::NONE.to_string(), "-");
+    }
+}
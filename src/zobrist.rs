@@ -0,0 +1,188 @@
+//! Zobrist hashing keys.
+//!
+//! This module contains the random keys used to hash a position into a u64. A position key is the XOR of the keys of
+//! every piece on its square, the side to move, the castling rights and the en-passant file, which allows `make_move`
+//! to update it incrementally by XORing in and out only what changed. The en-passant file is only hashed when a pawn can
+//! actually capture en passant, otherwise the same position would get two keys depending on how it was reached.
+//!
+//! Keys are generated at compile time with a splitmix64 generator, so they are identical across runs and builds.
+
+use crate::types::Square;
+
+/// Random keys for each component of a position.
+pub struct ZobristKeys {
+    pub pieces: [[u64; 64]; 12], // Indexed by [Piece][Square]
+    pub side: u64,               // XORed in when black is to move
+    pub castling: [u64; 16],     // Indexed by the castling rights u8
+    pub en_passant: [u64; 8],    // Indexed by the en-passant file
+}
+
+pub static ZOBRIST: ZobristKeys = ZobristKeys::new();
+
+impl ZobristKeys {
+    const fn new() -> Self {
+        let mut state = 0x4D41_5343_415F_5A42u64; // "MASCA_ZB"
+        let mut keys = Self {
+            pieces: [[0; 64]; 12],
+            side: 0,
+            castling: [0; 16],
+            en_passant: [0; 8],
+        };
+
+        let mut piece = 0;
+        while piece < 12 {
+            let mut sq = 0;
+            while sq < 64 {
+                keys.pieces[piece][sq] = splitmix64(&mut state);
+                sq += 1;
+            }
+            piece += 1;
+        }
+
+        keys.side = splitmix64(&mut state);
+
+        let mut i = 0;
+        while i < 16 {
+            keys.castling[i] = splitmix64(&mut state);
+            i += 1;
+        }
+
+        let mut file = 0;
+        while file < 8 {
+            keys.en_passant[file] = splitmix64(&mut state);
+            file += 1;
+        }
+
+        keys
+    }
+
+    /// Returns the key of the en-passant file of `sq`.
+    #[inline(always)]
+    pub fn en_passant_key(&self, sq: Square) -> u64 {
+        self.en_passant[sq.file() as usize]
+    }
+}
+
+/// splitmix64 pseudo-random generator, usable in const context.
+const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::movegen::{MoveList, generate_legal_moves};
+    use crate::moves::Move;
+    use crate::types::{CastlingRights, CastlingSide, Color, Piece, PieceType};
+
+    /// Returns the key of `piece_type` of `color` on `sq`.
+    fn piece_key(color: Color, piece_type: PieceType, sq: Square) -> u64 {
+        ZOBRIST.pieces[Piece::new(color, piece_type) as usize][sq]
+    }
+
+    /// Returns the key change between two castling rights.
+    fn castling_delta(before: CastlingRights, after: CastlingRights) -> u64 {
+        ZOBRIST.castling[before.bits() as usize] ^ ZOBRIST.castling[after.bits() as usize]
+    }
+
+    /// Makes the UCI move `uci` and returns it with the key change it caused.
+    fn key_delta(board: &mut Board, uci: &str) -> (Move, u64) {
+        let m = board.parse_uci_move(uci).unwrap();
+        let before = board.zobrist_key();
+        board.make_move(m);
+        (m, before ^ board.zobrist_key())
+    }
+
+    /// Lines from the starting position and the FEN they lead to, with an uncapturable and a capturable en-passant square.
+    const EN_PASSANT_CASES: [(&[&str], &str); 2] = [
+        (&["e2e4"], "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+        (&["e2e4", "d7d5", "e4e5", "f7f5"], "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"),
+    ];
+
+    /// Checks that a position reached by moves and the same position loaded from FEN have the same Zobrist key, and that
+    /// the en-passant square changes the key only when it can be captured.
+    #[test]
+    fn en_passant_keys_match_the_fen() {
+        let mut played = Board::new();
+        let mut loaded = Board::new();
+        for (line, fen) in EN_PASSANT_CASES {
+            played.set_startpos();
+            for uci in line {
+                let mut list = MoveList::new();
+                generate_legal_moves(&played, &mut list);
+                let m = list.iter().find(|m| m.to_string() == *uci).unwrap();
+                played.make_move(m);
+            }
+            loaded.from_fen(fen).unwrap();
+            assert_eq!(played.zobrist_key(), loaded.zobrist_key(), "{fen}");
+            assert_eq!(loaded.zobrist_key(), loaded.compute_zobrist_key(), "{fen}");
+
+            // The same placement without en-passant square differs only if the capture was possible
+            let without_ep = fen.replacen(&format!(" {} ", loaded.en_passant_square().unwrap()), " - ", 1);
+            let capturable = loaded.can_capture_en_passant();
+            loaded.from_fen(&without_ep).unwrap();
+            assert_eq!(loaded.zobrist_key() != played.zobrist_key(), capturable, "{fen}");
+        }
+    }
+
+    /// Checks the key of a castling move component by component: castling kingside must change exactly the side key,
+    /// the king and rook squares and the castling key from `KQkq` to `kq`, and unmaking must restore the key exactly.
+    #[test]
+    fn castling_keys() {
+        let mut board = Board::new();
+        board.from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let key = board.zobrist_key();
+        let (m, delta) = key_delta(&mut board, "e1g1");
+
+        let expected = ZOBRIST.side
+            ^ piece_key(Color::White, PieceType::King, Square::E1)
+            ^ piece_key(Color::White, PieceType::King, Square::G1)
+            ^ piece_key(Color::White, PieceType::Rook, Square::H1)
+            ^ piece_key(Color::White, PieceType::Rook, Square::F1)
+            ^ castling_delta(CastlingRights::ALL, board.castling_rights());
+        assert_eq!(delta, expected);
+        assert_eq!(board.castling_rights().bits(), 0b1100);
+        board.unmake_move(m);
+        assert_eq!(board.zobrist_key(), key);
+    }
+
+    /// Checks the en-passant file key: a double push next to an enemy pawn must add exactly the file key of the skipped
+    /// square besides the side and pawn keys, and the next move must remove exactly that file key besides its own.
+    #[test]
+    fn en_passant_key_toggles() {
+        let mut board = Board::new();
+        board.from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1").unwrap();
+        let (_, delta) = key_delta(&mut board, "d7d5");
+        let pawn_keys = piece_key(Color::Black, PieceType::Pawn, Square::D7) ^ piece_key(Color::Black, PieceType::Pawn, Square::D5);
+        assert_eq!(delta, ZOBRIST.side ^ pawn_keys ^ ZOBRIST.en_passant_key(Square::D6));
+
+        let (_, delta) = key_delta(&mut board, "e1e2");
+        let king_keys = piece_key(Color::White, PieceType::King, Square::E1) ^ piece_key(Color::White, PieceType::King, Square::E2);
+        assert_eq!(delta, ZOBRIST.side ^ king_keys ^ ZOBRIST.en_passant_key(Square::D6));
+        assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+    }
+
+    /// Checks the castling key of king moves: a white king step must XOR out exactly White's two rights, leaving
+    /// Black's, and a black king step afterwards exactly Black's.
+    #[test]
+    fn king_moves_clear_their_castling_keys() {
+        let mut board = Board::new();
+        board.from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for (uci, color, from, to) in [("e1f1", Color::White, Square::E1, Square::F1), ("e8d8", Color::Black, Square::E8, Square::D8)] {
+            let before = board.castling_rights();
+            let mut expected_rights = before;
+            expected_rights.remove(color, CastlingSide::King);
+            expected_rights.remove(color, CastlingSide::Queen);
+
+            let (_, delta) = key_delta(&mut board, uci);
+            let king_keys = piece_key(color, PieceType::King, from) ^ piece_key(color, PieceType::King, to);
+            assert_eq!(board.castling_rights(), expected_rights, "{uci}");
+            assert_eq!(delta, ZOBRIST.side ^ king_keys ^ castling_delta(before, expected_rights), "{uci}");
+        }
+    }
+}